@@ -2,6 +2,7 @@
 pub mod cost_factors {
     use crate::models::gpt_models::GptModel;
     use crate::models::req_and_res::Usage;
+    use crate::models::embeddings::EmbeddingUsage;
 
     /// Returns the cost in cents/1000 tokens for a given model
     pub fn compute_cost(usage: &Usage, model: &GptModel) -> f64 {
@@ -28,6 +29,14 @@ pub mod cost_factors {
 
     }
 
+    /// Returns the cost in dollars of an embedding request, at $0.0001/1000 tokens for `text-embedding-ada-002`
+    pub fn compute_embedding_cost(usage: &EmbeddingUsage) -> f64 {
+        usage.total_tokens as f64 * 0.0001 / 1000.0
+    }
+
+    /// Fraction of the usual price charged for completions run through the Batch API
+    pub const BATCH_DISCOUNT: f32 = 0.5;
+
 }
 
 /// These constants encode the strings use to refer to each model in the official OpenAI docs
@@ -43,10 +52,17 @@ pub mod model_strings {
     
     pub const GPT4_32K: &str = "gpt-4-32k"; // 32k
     pub const GPT4_32K_0314: &str = "gpt-4-32k-0314";
+
+    pub const TEXT_EMBEDDING_ADA_002: &str = "text-embedding-ada-002";
 }
 
 pub mod pdf_path {
     pub const DEFAULT_PDF_DIR: &str = "./pdfs/";
+    /// Documents matched by `OpenAIAccount::apply_prompt_to_dir()` when no glob is given
+    pub const DEFAULT_PDF_GLOB: &str = "*.pdf";
 }
 
+/// Rough average length of an English token, for estimating the tokens of text without a tokenizer
+pub const CHARS_PER_TOKEN: usize = 4;
+
 pub const API_URL_V1: &str = "https://api.openai.com/v1";
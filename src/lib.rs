@@ -8,11 +8,26 @@ pub use crate::{
                 OpenAIAccount,
                 Opts,
             },
+            database::PoolOpts,
+            requests::RateLimit,
+            batch::BatchOpts,
+            batch_job::{BatchItem, BatchStatus},
+            files::{FilePurpose, FileSearch},
+            document_records::DocumentRecord,
+            battery::{Battery, BatteryMatrix, BatteryPrompt},
+            db_search::{DbSearch, DbSortBy},
+            db_delete::DeleteMode,
+            full_text::FullTextSearch,
         },
         queries::{*, chat_query::Cacheable},
+        cache::EvictionPolicy,
+        labels::Labels,
+        documents::{Citation, DocumentFormat, DocumentLoader, DocumentStore, PdfCleanup, PdfOpts, Reference, StoredDocument},
+        search::{CacheSearch, SortBy},
+        archive::ConflictPolicy,
         GptModel,
         Query,
-        
+        QueryKind,
     }
 };
 
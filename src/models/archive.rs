@@ -0,0 +1,220 @@
+use std::{
+    fs,
+    io::{Cursor, Write},
+    path::Path,
+};
+use chrono::{DateTime, Utc};
+use serde::{Serialize, Deserialize};
+
+use crate::{
+    models::{
+        cache::Cache,
+        client::core::Status,
+        documents::office::zip_entry,
+        hash::sha256_hex,
+        search::CacheSearch,
+    },
+    Query,
+};
+use super::queries::chat_query::Cacheable;
+
+/// Bumped whenever the layout of an archive changes in a way older versions can't read
+pub const ARCHIVE_FORMAT_VERSION: u32 = 2;
+const MANIFEST_FILE: &str = "manifest.json";
+const ENTRIES_FILE: &str = "queries.jsonl";
+
+/// Describes an exported archive. Written into it next to the entries, as `manifest.json`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Manifest {
+    pub format_version: u32,
+    /// Version of this crate that wrote the archive
+    pub crate_version: String,
+    pub exported_at: DateTime<Utc>,
+    pub entry_count: usize,
+    /// SHA-256 of `queries.jsonl`
+    pub checksum: String,
+}
+
+/// One line of `queries.jsonl`
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+struct ArchiveEntry {
+    key: String,
+    query: Query,
+}
+
+/// What to do when an imported entry has the same key as a local one, but a different response
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictPolicy {
+    /// Leave the local entry as is
+    KeepMine,
+    /// Replace the local entry. The local version goes to the graveyard.
+    TakeTheirs,
+    /// Keep whichever was created later. A replaced local version goes to the graveyard.
+    KeepNewest,
+    /// Leave the local entry as is, and send the imported one to the graveyard, where it can be restored from
+    Graveyard,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resolution {
+    KeptMine,
+    TookTheirs,
+    BuriedTheirs,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Conflict {
+    pub key: String,
+    pub mine_created_at: DateTime<Utc>,
+    pub theirs_created_at: DateTime<Utc>,
+    pub resolution: Resolution,
+}
+
+/// Outcome of an import. On a dry run, this is what would have happened, and the cache is left untouched.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ImportReport {
+    pub dry_run: bool,
+    /// Keys that were not in the local cache
+    pub added: Vec<String>,
+    /// Keys present on both sides with the same response
+    pub unchanged: Vec<String>,
+    pub conflicts: Vec<Conflict>,
+    /// Keys stored in the archive under which their query would not be cached, such as after a hand edit. These entries are not imported.
+    pub rejected: Vec<String>,
+}
+
+impl ImportReport {
+    pub fn print(&self) {
+        println!("\n");
+        println!("🗳️   Import {}", if self.dry_run { "(dry run)" } else { "" });
+        println!("Added: {}", self.added.len());
+        println!("Unchanged: {}", self.unchanged.len());
+        println!("Conflicts: {}", self.conflicts.len());
+        for conflict in &self.conflicts {
+            println!("  {:?} \"{}\"", conflict.resolution, conflict.key);
+        }
+        println!("Rejected: {}", self.rejected.len());
+        for key in &self.rejected {
+            println!("  \"{key}\"");
+        }
+        println!("\n");
+    }
+}
+
+impl Cache {
+    /// Writes the cache, or only the entries matching `search`, to a single zip archive at `path`, holding `queries.jsonl` plus a `manifest.json`.
+    /// <br> An existing file at `path` is overwritten.
+    pub fn export(&self, path: &Path, search: Option<&CacheSearch>) -> Result<Manifest, Status> {
+        let mut entries: Vec<(String, Query)> = match search {
+            Some(search) => search.run(self),
+            None => self.entries.iter().map(|(key, query)| (key.clone(), query.clone())).collect(),
+        };
+        // A stable order makes the checksum reproducible for the same cache
+        entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        let mut jsonl = String::new();
+        for (key, query) in entries.iter() {
+            let line = serde_json::to_string(&ArchiveEntry { key: key.clone(), query: query.clone() }).map_err(|e| Status::Error(e.to_string()))?;
+            jsonl.push_str(&line);
+            jsonl.push('\n');
+        }
+
+        let manifest = Manifest {
+            format_version: ARCHIVE_FORMAT_VERSION,
+            crate_version: env!("CARGO_PKG_VERSION").to_string(),
+            exported_at: Utc::now(),
+            entry_count: entries.len(),
+            checksum: sha256_hex(jsonl.as_bytes()),
+        };
+
+        let manifest_json = serde_json::to_string_pretty(&manifest).map_err(|e| Status::Error(e.to_string()))?;
+        let mut zip = zip::ZipWriter::new(Cursor::new(vec![]));
+        for (name, content) in [(ENTRIES_FILE, jsonl), (MANIFEST_FILE, manifest_json)] {
+            zip.start_file(name, zip::write::FileOptions::default()).map_err(|e| Status::Error(e.to_string()))?;
+            zip.write_all(content.as_bytes()).map_err(|e| Status::Error(e.to_string()))?;
+        }
+        let archive = zip.finish().map_err(|e| Status::Error(e.to_string()))?.into_inner();
+        fs::write(path, archive).map_err(|e| Status::Error(e.to_string()))?;
+
+        println!("🗳️   Exported {} cache entries to: {}", manifest.entry_count, path.display());
+        Ok(manifest)
+    }
+
+    /// Merges an archive written by `export()` into this cache, resolving conflicting keys by `policy`.
+    /// <br> With `dry_run`, nothing is changed, and the report says what would have happened.
+    /// <br> Errors if the archive is from a newer format, or its checksum doesn't match. An entry whose stored key is not the key of its query is rejected, see `ImportReport::rejected`.
+    pub fn import(&mut self, path: &Path, policy: ConflictPolicy, dry_run: bool) -> Result<ImportReport, Status> {
+        let archive = fs::read(path).map_err(|e| Status::Error(format!("{}: {e}", path.display())))?;
+        let manifest: Manifest = serde_json::from_slice(&zip_entry(&archive, MANIFEST_FILE)?).map_err(|e| Status::Error(e.to_string()))?;
+        if manifest.format_version > ARCHIVE_FORMAT_VERSION {
+            return Err(Status::Error(format!("Archive format version {} is newer than the supported {ARCHIVE_FORMAT_VERSION}", manifest.format_version)))
+        }
+
+        let jsonl = zip_entry(&archive, ENTRIES_FILE)?;
+        if sha256_hex(&jsonl) != manifest.checksum {
+            return Err(Status::Error(format!("Checksum of {ENTRIES_FILE} in {} does not match its manifest", path.display())))
+        }
+
+        let jsonl = String::from_utf8(jsonl).map_err(|e| Status::Error(e.to_string()))?;
+        let mut theirs = vec![];
+        for line in jsonl.lines() {
+            if line.trim().is_empty() { continue }
+            let entry: ArchiveEntry = serde_json::from_str(line).map_err(|e| Status::Error(e.to_string()))?;
+            theirs.push(entry);
+        }
+
+        let mut report = ImportReport { dry_run, ..Default::default() };
+        for ArchiveEntry { key, mut query } in theirs {
+            if key != query.key() {
+                report.rejected.push(key);
+                continue
+            }
+            // Whatever is taken from the archive is a local change, as far as syncing with the database goes
+            query.mark_updated();
+            let mine = match self.entries.get(&key) {
+                None => {
+                    report.added.push(key.clone());
+                    if !dry_run { self.entries.insert(key, query); }
+                    continue
+                },
+                Some(mine) => mine,
+            };
+
+            if mine.response_ref() == query.response_ref() {
+                report.unchanged.push(key);
+                continue
+            }
+
+            let resolution = match policy {
+                ConflictPolicy::KeepMine => Resolution::KeptMine,
+                ConflictPolicy::TakeTheirs => Resolution::TookTheirs,
+                ConflictPolicy::KeepNewest => if query.created_at() > mine.created_at() { Resolution::TookTheirs } else { Resolution::KeptMine },
+                ConflictPolicy::Graveyard => Resolution::BuriedTheirs,
+            };
+            report.conflicts.push(Conflict {
+                key: key.clone(),
+                mine_created_at: mine.created_at(),
+                theirs_created_at: query.created_at(),
+                resolution,
+            });
+
+            if dry_run { continue }
+            match resolution {
+                Resolution::KeptMine => (),
+                Resolution::TookTheirs => {
+                    if let Some(mine) = self.entries.insert(key.clone(), query) {
+                        self.graveyard.bury(&key, &mine);
+                    }
+                },
+                Resolution::BuriedTheirs => self.graveyard.bury(&key, &query),
+            }
+        }
+
+        if !dry_run {
+            self.evict_entries();
+            self.save();
+            println!("🗳️   Imported {} new and {} conflicting cache entries from: {}", report.added.len(), report.conflicts.len(), path.display());
+        }
+        Ok(report)
+    }
+}
@@ -1,6 +1,6 @@
 use serde::{Serialize, Deserialize};
-use std::{fs, path::PathBuf};
-use crate::Query;
+use std::{collections::BTreeMap, fs, path::PathBuf};
+use crate::{Query, models::embeddings::EmbeddingResponse};
 
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -17,7 +17,13 @@ pub struct Bill {
     pub(crate) query_count: i32,
     /// Number of times a ChatGPT completion was pulled from the cache instead of the API, because the prompt was found in the cache
     pub(crate) cache_retrievals: i32,
-    pub(super) filepath: PathBuf,
+    /// Total tokens sent to be embedded (by the semantic cache) so far since last `.reset_bill()`. Their cost is included in `cost`
+    #[serde(default)]
+    pub(crate) embedding_tokens: i32,
+    /// Cost in CENTS of the queries recorded so far since last `.reset_bill()`, by `Labels::project`. Queries without a project are left out.
+    #[serde(default)]
+    pub(crate) cost_by_project: BTreeMap<String, f32>,
+    pub(crate) filepath: PathBuf,
 }
 
 impl Default for Bill {
@@ -29,6 +35,8 @@ impl Default for Bill {
             cost: 0.00,
             query_count: 0,
             total_tokens: 0,
+            embedding_tokens: 0,
+            cost_by_project: BTreeMap::new(),
             filepath: "./bill.json".into()
         }
     }
@@ -44,7 +52,11 @@ impl Bill {
             self.prompt_tokens += res.usage.prompt_tokens;
             self.total_tokens += res.usage.total_tokens;
             self.query_count += 1;
-            self.cost += res.cost(&query.model());
+            // As priced on the query, which has any discount applied
+            self.cost += query.cost();
+            if let Some(project) = &query.labels().project {
+                *self.cost_by_project.entry(project.clone()).or_default() += query.cost();
+            }
         }
 
         // Save the state of self.bill to file
@@ -52,11 +64,21 @@ impl Bill {
         serde_json::to_writer_pretty(&bill, &self).expect("Serialization of bill to bill file");
     }
 
+    pub(crate) fn update_embeddings(&mut self, res: &EmbeddingResponse) {
+        self.embedding_tokens += res.usage.total_tokens;
+        self.cost += res.cost();
+
+        let bill = match fs::OpenOptions::new().create(true).truncate(true).write(true).open(&self.filepath) {Ok(f)=>f, Err(e)=>panic!("Could not update bill at {}, due to error:  ❌  {}", self.filepath.display(), e)};
+        serde_json::to_writer_pretty(&bill, &self).expect("Serialization of bill to bill file");
+    }
+
     pub fn reset_bill(&mut self) -> () {
         self.completion_tokens = 0;
         self.prompt_tokens = 0;
         self.total_tokens = 0;
         self.query_count = 0;
+        self.embedding_tokens = 0;
+        self.cost_by_project.clear();
         self.cost = 0.00;
         let bill = match fs::OpenOptions::new().create(true).truncate(true).write(true).open(&self.filepath) {Ok(f)=>f, Err(e)=>panic!("Could not reset bill at {}, due to error:  ❌  {}", self.filepath.display(), e)};
         serde_json::to_writer_pretty(&bill, &self).expect("Serialization of bill to bill file");
@@ -69,6 +91,9 @@ impl Bill {
         println!("Queries: {}", self.query_count);
         println!("Total Tokens: {}", self.total_tokens);
         println!("Bill: ${:.2}", self.cost / 100.0);
+        for (project, cost) in &self.cost_by_project {
+            println!("  {project}: ${:.2}", cost / 100.0);
+        }
         println!("\n");
     }
 
@@ -1,25 +1,94 @@
 use serde::{Serialize, Deserialize};
+use chrono::Utc;
 
-use crate::Query;
+use crate::{Query, QueryKind, models::{client::graveyard::Graveyard, labels::Labels}};
 use std::{
     path::PathBuf,
     collections::HashMap,
-    fs, 
+    fs,
+    io,
+    time::Duration,
 };
 
 use super::queries::chat_query::Cacheable;
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Cache {
     pub entries: HashMap<String,Query>,
     pub filepath: PathBuf,
+    /// Limits applied to the cache on startup and after every insertion. See `EvictionPolicy`
+    pub policy: EvictionPolicy,
+    /// Where overwritten and evicted entries are kept, so that they can be listed and restored
+    pub graveyard: Graveyard,
+    /// Whether entries were read since the cache file was last written, so that their `last_accessed` is not yet saved. See `flush()`
+    #[serde(skip)]
+    pub dirty: bool,
+}
+
+impl Clone for Cache {
+    /// A clone starts clean, so that dropping it never writes the cache file
+    fn clone(&self) -> Self {
+        Cache {
+            entries: self.entries.clone(),
+            filepath: self.filepath.clone(),
+            policy: self.policy.clone(),
+            graveyard: self.graveyard.clone(),
+            dirty: false,
+        }
+    }
+}
+
+impl Drop for Cache {
+    fn drop(&mut self) {
+        self.flush();
+    }
 }
 
 impl Default for Cache {
     fn default() -> Self {
         Cache {
             entries: HashMap::new(),
-            filepath: "./cache.json".into()
+            filepath: "./cache.json".into(),
+            policy: EvictionPolicy::default(),
+            graveyard: Graveyard::default(),
+            dirty: false,
+        }
+    }
+}
+
+/// Optional limits on what the cache keeps. Evicted entries are sent to the graveyard rather than dropped.
+/// <br> Every limit defaults to `None`, meaning the cache grows forever, as it always has.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct EvictionPolicy {
+    /// Maximum age of a `ChatQuery`, counted from its `created_at`
+    pub chat_ttl: Option<Duration>,
+    /// Maximum age of a `TextQuery`, counted from its `created_at`
+    pub text_ttl: Option<Duration>,
+    /// Maximum age of a `MetaQuery`, counted from its `created_at`
+    pub meta_ttl: Option<Duration>,
+    /// Maximum number of entries. Past this, the least recently accessed entries are evicted first.
+    pub max_entries: Option<usize>,
+    /// Maximum total size of the entries once serialized to JSON. Past this, the least recently accessed entries are evicted first.
+    pub max_bytes: Option<usize>,
+}
+
+impl EvictionPolicy {
+    pub fn ttl(&self, kind: QueryKind) -> Option<Duration> {
+        match kind {
+            QueryKind::Chat => self.chat_ttl,
+            QueryKind::Text => self.text_ttl,
+            QueryKind::Meta => self.meta_ttl,
+        }
+    }
+
+    /// Whether the query has outlived the time-to-live set for its kind
+    pub fn is_expired(&self, query: &Query) -> bool {
+        match self.ttl(query.kind()) {
+            None => false,
+            Some(ttl) => match (Utc::now() - query.created_at()).to_std() {
+                Ok(age) => age > ttl,
+                Err(_) => false, // created_at is in the future
+            },
         }
     }
 }
@@ -28,14 +97,40 @@ impl Cache {
     /// Resets both the cache file and in-memory cache to empty
     pub fn clear(&mut self) {
         self.entries.clear();
+        self.dirty = false;
         let cache_file = match fs::OpenOptions::new().create(true).truncate(true).write(true).open(&self.filepath) {Ok(f)=>f, Err(e)=>panic!("🗳️   clear() had trouble initializing a new blank cache file at '{}' : \n❌  {}", self.filepath.display(), e)};
         serde_json::to_writer_pretty(&cache_file, &self.entries).expect("Serialization of cache to cache file");
         println!("🗳️   Cache cleared at: {}", self.filepath.display());
     }
 
+    /// Retrieves a copy of the query at `cache_key`, marking it as accessed. The access is saved with the next write of the cache file, or by `flush()`.
+    /// <br> An entry past its time-to-live is evicted instead, and `None` is returned as if it was never cached.
+    pub fn get(&mut self, cache_key: &str) -> Option<Query> {
+        self.get_if(cache_key, |_| true)
+    }
+
+    /// `get()`, but an entry that `accept` rejects is left as it is and `None` is returned, so that the miss is not counted as an access
+    pub fn get_if(&mut self, cache_key: &str, accept: impl Fn(&Query) -> bool) -> Option<Query> {
+        let expired = self.policy.is_expired(self.entries.get(cache_key)?);
+        if expired {
+            if let Some(query) = self.entries.remove(cache_key) {
+                println!("🗳️   Cache entry at key \"{cache_key}\" expired.");
+                self.graveyard.bury(cache_key, &query);
+            }
+            self.save();
+            return None
+        }
+
+        let query = self.entries.get_mut(cache_key).filter(|query| accept(query))?;
+        query.touch();
+        let query = query.clone();
+        self.dirty = true;
+        Some(query)
+    }
+
     pub fn remove(&mut self, cache_key: String) -> Option<(String, Query)> {
         let entry = self.entries.remove_entry(&cache_key);
-        
+
 
         match entry {
             Some(entry) => {
@@ -43,53 +138,192 @@ impl Cache {
                 // Update the cache file
                 let cache_file = match fs::OpenOptions::new().create(true).truncate(true).write(true).open(&self.filepath) {Ok(f)=>f, Err(e)=>panic!("🗳️   Could not re-write cache file after removal at {}, due to error:  ❌  {}", self.filepath.display(), e)};
                 serde_json::to_writer_pretty(&cache_file, &self.entries).expect("Serialization of cache to cache file");
+                self.dirty = false;
                 Some(entry)
             },
             None => None
         }
     }
 
-    pub(super) fn insert(&mut self, query: &Query) -> () {
+    /// Removes every entry in `cache_keys`, for example the keys of a `CacheSearch`'s results. Unlike `remove()`, the removed queries are sent to the graveyard.
+    pub fn remove_many(&mut self, cache_keys: &[String]) -> Vec<(String, Query)> {
+        let removed: Vec<(String, Query)> = cache_keys.iter()
+            .filter_map(|key| self.entries.remove_entry(key))
+            .collect();
+        for (key, query) in &removed {
+            self.graveyard.bury(key, query);
+        }
+        if !removed.is_empty() {
+            self.save();
+            println!("🗳️   Removed {} cache entries.", removed.len());
+            println!("🪦   The removed queries can be found in the graveyard file.");
+        }
+        removed
+    }
+
+    /// Makes a prior version of `cache_key`, as indexed in `graveyard.versions(cache_key)`, the current entry again.
+    /// <br> The entry it replaces is buried in turn, so no version is lost. Returns the restored query, or `None` if there is no such version.
+    pub fn restore(&mut self, cache_key: &str, version: usize) -> Option<Query> {
+        let mut query = self.graveyard.versions(cache_key).into_iter().nth(version)?.query;
+        query.touch();
+        query.mark_updated();
+        self.insert(&query);
+        println!("🪦   Restored version {version} of \"{cache_key}\" from the graveyard.");
+        Some(query)
+    }
+
+    /// Applies the cache's `EvictionPolicy`: first expired entries are removed, then the least recently accessed until both `max_entries` and `max_bytes` are satisfied.
+    /// <br> Evicted entries are sent to the graveyard, and returned.
+    pub fn evict(&mut self) -> Vec<(String, Query)> {
+        let evicted = self.evict_entries();
+        if !evicted.is_empty() {
+            self.save();
+        }
+        evicted
+    }
+
+    /// `evict()` without saving the cache file, for callers that save afterwards anyway
+    pub(crate) fn evict_entries(&mut self) -> Vec<(String, Query)> {
+        let mut evicted = vec![];
+
+        let expired: Vec<String> = self.entries.iter()
+            .filter(|(_, query)| self.policy.is_expired(query))
+            .map(|(key, _)| key.clone())
+            .collect();
+        for key in expired {
+            if let Some(entry) = self.entries.remove_entry(&key) {
+                evicted.push(entry);
+            }
+        }
+
+        if self.policy.max_entries.is_some() || self.policy.max_bytes.is_some() {
+            // Oldest access first
+            let mut by_access: Vec<(&String, &Query)> = self.entries.iter().collect();
+            by_access.sort_by_key(|(_, query)| query.last_accessed());
+            let by_access: Vec<(String, usize)> = by_access.into_iter()
+                .map(|(key, query)| (key.clone(), serialized_size(query)))
+                .collect();
+
+            let mut count = self.entries.len();
+            let mut bytes: usize = by_access.iter().map(|(_, size)| size).sum();
+
+            for (key, size) in by_access {
+                let over_count = self.policy.max_entries.is_some_and(|max| count > max);
+                let over_bytes = self.policy.max_bytes.is_some_and(|max| bytes > max);
+                if !(over_count || over_bytes) { break }
+
+                if let Some(entry) = self.entries.remove_entry(&key) {
+                    evicted.push(entry);
+                    count -= 1;
+                    bytes -= size;
+                }
+            }
+        }
+
+        if !evicted.is_empty() {
+            for (key, query) in &evicted {
+                self.graveyard.bury(key, query);
+            }
+            println!("🗳️   Evicted {} cache entries.", evicted.len());
+            println!("🪦   The evicted queries can be found in the graveyard file.");
+        }
+
+        evicted
+    }
+
+    pub(crate) fn insert(&mut self, query: &Query) {
 
         let cache_key = query.key();
 
-        match self.entries.insert(cache_key, query.clone()) {None => (), Some(query)=> { 
-            let graveyard = std::fs::OpenOptions::new().create(true).append(true).open("graveyard.json").expect("access to graveyard file");
-            serde_json::to_writer_pretty(graveyard, &query).expect("Serialization of an overwritten model to the graveyard");
+        if let Some(overwritten) = self.entries.insert(cache_key.clone(), query.clone()) {
+            self.graveyard.bury(&cache_key, &overwritten);
             println!("\n\n");
-            println!("🗳️   Caching a query resulted in an overwrite."); 
+            println!("🗳️   Caching a query resulted in an overwrite.");
             println!("🪦   The overwritten query can be found in the graveyard file.");
-        }};
+        };
+        self.evict_entries();
         // Save the state of self.cache to file
-        let cache = match fs::OpenOptions::new().create(true).truncate(true).write(true).open(&self.filepath) {Ok(f)=>f, Err(e)=>panic!("🗳️   Could not cache query at {}, due to error:  ❌  {}", self.filepath.display(), e)};
-        serde_json::to_writer_pretty(&cache, &self.entries).expect("Serialization of cache to cache file");
+        self.save();
     }
 
-    pub(super) fn insert_many(&mut self, queries: Vec<&Query>, overwrite: bool) -> () {
+    pub(crate) fn insert_many(&mut self, queries: Vec<&Query>, overwrite: bool) {
         for query in queries {
             let query_key = query.key();
             match overwrite {
                 true => {
-                    match self.entries.insert(query_key, query.clone()) {None => (), Some(query) => { 
-                        let graveyard = std::fs::OpenOptions::new().create(true).append(true).open("graveyard.json").expect("access to graveyard file");
-                        serde_json::to_writer_pretty(graveyard, &query).expect("Serialization of an overwritten model to the graveyard");
+                    if let Some(overwritten) = self.entries.insert(query_key.clone(), query.clone()) {
+                        self.graveyard.bury(&query_key, &overwritten);
                         println!("\n\n");
-                        println!("🗳️   Caching a query resulted in an overwrite."); 
+                        println!("🗳️   Caching a query resulted in an overwrite.");
                         println!("🪦   The overwritten query can be found in the graveyard file.");
-                    }};
+                    };
                 },
                 false => {
-                    match self.entries.get(&query_key) {
-                        Some(_) => (),
-                        None => {
-                            self.entries.insert(query_key, query.clone());
-                        },
-                    }
+                    self.entries.entry(query_key).or_insert_with(|| query.clone());
                 },
             }
         }
 
+        self.evict_entries();
+        self.save();
+    }
+
+    /// Lays `labels` over those of the entry at `cache_key`, as `Labels::merged()` does, marking it updated and saving the cache if that changed them. Returns whether it did.
+    pub(crate) fn merge_labels(&mut self, cache_key: &str, labels: &Labels) -> bool {
+        let Some(query) = self.entries.get_mut(cache_key) else { return false };
+        let merged = query.labels().merged(labels);
+        if merged == *query.labels() {
+            return false
+        }
+        *query.labels_mut() = merged;
+        query.mark_updated();
+        self.save();
+        true
+    }
+
+    /// Saves the accesses made by `get()` since the cache file was last written, if any. Also done when the cache is dropped.
+    pub fn flush(&mut self) {
+        if self.dirty {
+            self.save();
+        }
+    }
+
+    /// Overwrites the cache file with the in-memory entries
+    pub(crate) fn save(&mut self) {
         let cache = match fs::OpenOptions::new().create(true).truncate(true).write(true).open(&self.filepath) {Ok(f)=>f, Err(e)=>panic!("🗳️   Could not cache query at {}, due to error:  ❌  {}", self.filepath.display(), e)};
         serde_json::to_writer_pretty(&cache, &self.entries).expect("Serialization of cache to cache file");
+        self.dirty = false;
     }
-}
\ No newline at end of file
+}
+
+/// Times that entries cached before they were tracked lack, and that would otherwise default to the time of every load
+const STAMPED_FIELDS: [&str; 3] = ["created_at", "last_accessed", "updated_at"];
+
+/// Reads cache entries from the cache file, stamping the times missing from older entries with the load time.
+/// <br> Returns whether any entry was stamped, so that the cache is marked dirty and the stamps are saved once, instead of being made again on every load.
+pub(crate) fn read_entries(reader: impl io::Read) -> serde_json::Result<(HashMap<String, Query>, bool)> {
+    let raw: HashMap<String, serde_json::Value> = serde_json::from_reader(reader)?;
+    let loaded_at = serde_json::to_value(Utc::now())?;
+    let mut stamped = false;
+    let mut entries = HashMap::with_capacity(raw.len());
+    for (key, mut value) in raw {
+        // Stored tagged by variant, as `{"ChatQuery": {...}}`
+        let fields = value.as_object_mut()
+            .and_then(|tagged| tagged.values_mut().next())
+            .and_then(|query| query.as_object_mut());
+        if let Some(fields) = fields {
+            for field in STAMPED_FIELDS {
+                if !fields.contains_key(field) {
+                    fields.insert(field.to_string(), loaded_at.clone());
+                    stamped = true;
+                }
+            }
+        }
+        entries.insert(key, serde_json::from_value(value)?);
+    }
+    Ok((entries, stamped))
+}
+
+fn serialized_size(query: &Query) -> usize {
+    serde_json::to_vec(query).map(|v| v.len()).unwrap_or(0)
+}
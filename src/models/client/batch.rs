@@ -0,0 +1,226 @@
+use std::{future::Future, path::Path, time::{Duration, Instant}};
+use chrono::Utc;
+use futures::StreamExt;
+
+use crate::{
+    models::{
+        client::{
+            completion::pdf_path,
+            core::{OpenAIAccount, Status},
+        },
+        documents::{PdfOpts, StoredDocument},
+        api_error::APIError,
+        ChatCompletionMessage,
+        ChatCompletionRequest,
+        ChatCompletionResponse,
+    },
+    ChatQuery,
+    Query,
+    TextQuery,
+};
+
+/// How a batch is run
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BatchOpts {
+    /// Most requests in flight at once. `Opts::rate_limit` still applies on top of this.
+    pub concurrency: usize,
+}
+
+impl Default for BatchOpts {
+    fn default() -> Self {
+        BatchOpts { concurrency: 4 }
+    }
+}
+
+/// One item of a batch that has to be sent, at `index` in the input
+struct Pending {
+    index: usize,
+    key: String,
+    req: ChatCompletionRequest,
+    /// Kept on the query: those of `req`, with a document sent replaced by its `StoredDocument::reference()`
+    messages: Vec<ChatCompletionMessage>,
+}
+
+impl OpenAIAccount {
+    /// `get_completion()` of each of `prompts`, up to `opts.concurrency` at a time. Results come in the order of `prompts`.
+    /// <br> Each answer is cached and billed as it arrives, so a failed item leaves the others in place. Only exact cache keys are looked up, not the semantic cache, and a prompt repeated within the batch is sent once.
+    pub async fn get_completions(&mut self, prompts: &[&str], opts: &BatchOpts) -> Vec<Result<ChatQuery, Status>> {
+        let sender = self.sender();
+        self.get_completions_over(prompts, opts, |req| {
+            let sender = sender.clone();
+            async move { sender.send_completion_request(req).await }
+        }).await
+    }
+
+    /// `get_completions()`, with each request sent by `send` instead of to OpenAI
+    pub(crate) async fn get_completions_over<F>(&mut self, prompts: &[&str], opts: &BatchOpts, send: impl Fn(ChatCompletionRequest) -> F) -> Vec<Result<ChatQuery, Status>>
+    where F: Future<Output = Result<ChatCompletionResponse, APIError>> {
+        let mut results: Vec<Option<Result<ChatQuery, Status>>> = prompts.iter().map(|_| None).collect();
+        let mut pending = vec![];
+        let mut repeats: Vec<(usize, String)> = vec![];
+
+        for (index, prompt) in prompts.iter().enumerate() {
+            let key = ChatQuery::key(prompt);
+            if pending.iter().any(|p: &Pending| p.key == key) {
+                repeats.push((index, key));
+                continue
+            }
+            match self.cache.get(&key) {
+                Some(Query::ChatQuery(mut query)) => {
+                    query.from_cache = true;
+                    self.bill.cache_retrievals += 1;
+                    results[index] = Some(Ok(query));
+                },
+                Some(_) => results[index] = Some(Err(Status::RetrievedUnexpectedQueryType)),
+                None => {
+                    let req = self.chat_request(prompt);
+                    pending.push(Pending { index, key, messages: req.messages.clone(), req })
+                },
+            }
+        }
+        println!("📦 Batch of {} prompts: {} cached, {} to send", prompts.len(), prompts.len() - pending.len() - repeats.len(), pending.len());
+
+        self.run_batch(pending, opts, send, |account, index, response, messages, process_time| {
+            let query = ChatQuery {
+                prompt: prompts[index].to_string(),
+                cost: response.cost(&account.model),
+                response,
+                process_time: process_time.as_millis() as u64,
+                model: account.model,
+                temperature: account.temperature,
+                from_cache: false,
+                similarity: None,
+                created_at: Utc::now(),
+                last_accessed: Utc::now(),
+                updated_at: Utc::now(),
+                messages,
+                labels: account.labels.clone(),
+            };
+            (Query::ChatQuery(query.clone()), query)
+        }, &mut results).await;
+
+        for (index, key) in repeats {
+            results[index] = Some(match self.cache.get(&key) {
+                Some(query) => Ok(ChatQuery { from_cache: true, ..query.expect_as_chat() }),
+                None => Err(Status::Error(format!("Request for \"{key}\" failed earlier in the batch"))),
+            });
+        }
+        self.bill.update(None);
+        results.into_iter().map(|result| result.expect("every item of the batch resolved")).collect()
+    }
+
+    /// `apply_prompt_to_pdf()` of `prompt` to each of `pdf_titles` in `input_dir`, up to `opts.concurrency` at a time. Results come in the order of `pdf_titles`.
+    /// <br> Each answer is cached and billed as it arrives, so a failed item, such as an unreadable PDF, leaves the others in place. Only exact cache keys are looked up, not the semantic cache.
+    pub async fn apply_prompt_to_pdfs(&mut self, pdf_titles: &[&str], prompt: &str, input_dir: Option<String>, opts: &BatchOpts) -> Vec<Result<TextQuery, Status>> {
+        let mut results: Vec<Option<Result<TextQuery, Status>>> = pdf_titles.iter().map(|_| None).collect();
+        let mut pending = vec![];
+        let mut repeats: Vec<(usize, String)> = vec![];
+
+        // How each PDF is read, since an answer to it read with other pages or cleanup is not served. Batches don't ask for citations.
+        let read_as: Vec<PdfOpts> = pdf_titles.iter().map(|pdf_title| PdfOpts { cite_pages: false, ..StoredDocument::read_opts(Path::new(&pdf_path(input_dir.clone(), pdf_title)), &self.pdf) }).collect();
+        let mut tokens_saved = vec![0; pdf_titles.len()];
+
+        for (index, pdf_title) in pdf_titles.iter().enumerate() {
+            let key = TextQuery::key(prompt, pdf_title);
+            if pending.iter().any(|p: &Pending| p.key == key) {
+                repeats.push((index, key));
+                continue
+            }
+            match self.cache.get_if(&key, |query| !matches!(query, Query::TextQuery(query) if query.pdf != read_as[index])) {
+                Some(Query::TextQuery(mut query)) => {
+                    query.from_cache = true;
+                    self.bill.cache_retrievals += 1;
+                    results[index] = Some(Ok(query));
+                },
+                Some(Query::ChatQuery(_) | Query::MetaQuery(_)) => results[index] = Some(Err(Status::RetrievedUnexpectedQueryType)),
+                None => match self.read_document(Path::new(&pdf_path(input_dir.clone(), pdf_title))) {
+                    Ok(doc) => {
+                        let req = self.document_request(&doc.text, prompt);
+                        tokens_saved[index] = doc.tokens_saved;
+                        pending.push(Pending { index, key, messages: doc.referenced_in(&req.messages), req })
+                    },
+                    Err(e) => results[index] = Some(Err(e)),
+                },
+            }
+        }
+        println!("📦 Batch of {} documents: {} to send", pdf_titles.len(), pending.len());
+
+        let sender = self.sender();
+        let send = |req| {
+            let sender = sender.clone();
+            async move { sender.send_completion_request(req).await }
+        };
+        self.run_batch(pending, opts, send, |account, index, response, messages, process_time| {
+            let query = TextQuery {
+                prompt: prompt.to_string(),
+                document_title: pdf_titles[index].to_string(),
+                cost: response.cost(&account.model),
+                response,
+                process_time: process_time.as_millis() as u64,
+                model: account.model,
+                temperature: account.temperature,
+                from_cache: false,
+                similarity: None,
+                created_at: Utc::now(),
+                last_accessed: Utc::now(),
+                updated_at: Utc::now(),
+                messages,
+                labels: account.labels.clone(),
+                citations: vec![],
+                pdf: read_as[index].clone(),
+                tokens_saved: tokens_saved[index],
+            };
+            (Query::TextQuery(query.clone()), query)
+        }, &mut results).await;
+
+        for (index, key) in repeats {
+            results[index] = Some(match self.cache.get_if(&key, |query| query.pdf() == read_as[index]) {
+                Some(query) => Ok(TextQuery { from_cache: true, ..query.expect_as_text() }),
+                None => Err(Status::Error(format!("Request for \"{key}\" failed earlier in the batch"))),
+            });
+        }
+        self.bill.update(None);
+        results.into_iter().map(|result| result.expect("every item of the batch resolved")).collect()
+    }
+
+    /// Sends `pending` with `send`, up to `opts.concurrency` at a time, and as each response arrives builds its query with `build`, caches and bills it, and puts it at its index in `results`
+    async fn run_batch<T, F: Future<Output = Result<ChatCompletionResponse, APIError>>>(
+        &mut self,
+        pending: Vec<Pending>,
+        opts: &BatchOpts,
+        send: impl Fn(ChatCompletionRequest) -> F,
+        build: impl Fn(&OpenAIAccount, usize, ChatCompletionResponse, Vec<ChatCompletionMessage>, Duration) -> (Query, T),
+        results: &mut [Option<Result<T, Status>>],
+    ) {
+        let total = pending.len();
+        // The requests only hold what `send` captured, such as a `Sender`, leaving the account free to record each result as it comes in
+        let send = &send;
+        let mut responses = futures::stream::iter(pending)
+            .map(|p| {
+                async move {
+                    let start_time = Instant::now();
+                    let response = send(p.req).await;
+                    (p.index, response, p.messages, start_time.elapsed())
+                }
+            })
+            .buffer_unordered(opts.concurrency.max(1));
+
+        let mut done = 0;
+        while let Some((index, response, messages, process_time)) = responses.next().await {
+            done += 1;
+            results[index] = Some(match response {
+                Ok(response) => {
+                    let (query_for_cache, query) = build(self, index, response, messages, process_time);
+                    self.cache.insert(&query_for_cache);
+                    self.bill.update(Some(query_for_cache));
+                    println!("📦 [{done}/{total}] Completed. Bill now shows: ${:.2}", self.bill.cost / 100.0);
+                    Ok(query)
+                },
+                Err(e) => {
+                    println!("📦 [{done}/{total}] Failed:  ❌  {e}");
+                    Err(Status::Error(e.to_string()))
+                },
+            });
+        }
+    }
+}
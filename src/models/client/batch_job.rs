@@ -0,0 +1,374 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path,
+    time::Duration,
+};
+use chrono::Utc;
+use serde::{Serialize, Deserialize};
+
+use crate::{
+    constants::cost_factors::BATCH_DISCOUNT,
+    models::{
+        client::{
+            completion::pdf_path,
+            core::{OpenAIAccount, Status},
+            files::FilePurpose,
+            json_file::{load_json, save_json},
+            requests::new_error,
+        },
+        documents::PdfOpts,
+        labels::Labels,
+        ChatCompletionMessage,
+        ChatCompletionRequest,
+        ChatCompletionResponse,
+    },
+    ChatQuery,
+    Cacheable,
+    GptModel,
+    Query,
+    TextQuery,
+};
+
+/// How long OpenAI has to finish a batch. The only window offered at half price.
+pub const BATCH_COMPLETION_WINDOW: &str = "24h";
+
+/// One completion to run in a batch job. Build with `OpenAIAccount::batch_chat_item()` or `OpenAIAccount::batch_pdf_item()`
+#[derive(Debug)]
+pub struct BatchItem {
+    pub entry: BatchEntry,
+    pub request: ChatCompletionRequest,
+}
+
+/// What is needed to turn the result of a `BatchItem` into a cache entry. Kept in `Opts::batch_jobs_filepath` until the job is collected.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct BatchEntry {
+    pub prompt: String,
+    /// Set for text queries, else the item is a chat query. Other kinds can't be batched.
+    pub document_title: Option<String>,
+    pub model: GptModel,
+    pub temperature: f32,
+    pub messages: Vec<ChatCompletionMessage>,
+    pub labels: Labels,
+    /// How the document of a text query was read. See `TextQuery::pdf`
+    #[serde(default, skip_serializing_if = "PdfOpts::is_default")]
+    pub pdf: PdfOpts,
+    /// See `TextQuery::tokens_saved`
+    #[serde(default)]
+    pub tokens_saved: usize,
+}
+
+impl BatchEntry {
+    pub fn key(&self) -> String {
+        match &self.document_title {
+            Some(title) => TextQuery::key(&self.prompt, title),
+            None => ChatQuery::key(&self.prompt),
+        }
+    }
+
+    /// The query answered by `response`, priced at the batch discount
+    fn to_query(&self, response: ChatCompletionResponse) -> Query {
+        let cost = response.cost(&self.model) * BATCH_DISCOUNT;
+        match &self.document_title {
+            Some(title) => Query::TextQuery(TextQuery {
+                prompt: self.prompt.clone(),
+                document_title: title.clone(),
+                response,
+                process_time: 0,
+                model: self.model,
+                temperature: self.temperature,
+                cost,
+                from_cache: false,
+                similarity: None,
+                created_at: Utc::now(),
+                last_accessed: Utc::now(),
+                updated_at: Utc::now(),
+                messages: self.messages.clone(),
+                labels: self.labels.clone(),
+                citations: vec![],
+                pdf: self.pdf.clone(),
+                tokens_saved: self.tokens_saved,
+            }),
+            None => Query::ChatQuery(ChatQuery {
+                prompt: self.prompt.clone(),
+                response,
+                process_time: 0,
+                model: self.model,
+                temperature: self.temperature,
+                cost,
+                from_cache: false,
+                similarity: None,
+                created_at: Utc::now(),
+                last_accessed: Utc::now(),
+                updated_at: Utc::now(),
+                messages: self.messages.clone(),
+                labels: self.labels.clone(),
+            }),
+        }
+    }
+}
+
+/// One line of the JSONL input file of a batch
+#[derive(Debug, Serialize)]
+struct BatchInputLine<'a> {
+    custom_id: String,
+    method: &'static str,
+    url: &'static str,
+    body: &'a ChatCompletionRequest,
+}
+
+/// One line of the output or error file of a batch
+#[derive(Debug, Deserialize)]
+struct BatchOutputLine {
+    custom_id: String,
+    response: Option<BatchOutputResponse>,
+    error: Option<BatchError>,
+}
+
+#[derive(Debug, Deserialize)]
+struct BatchOutputResponse {
+    status_code: u16,
+    body: serde_json::Value,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct BatchError {
+    pub code: Option<String>,
+    pub message: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum BatchStatus {
+    Validating,
+    Failed,
+    InProgress,
+    Finalizing,
+    Completed,
+    Expired,
+    Cancelling,
+    Cancelled,
+}
+
+impl BatchStatus {
+    /// Whether the job will change no more
+    pub fn is_done(&self) -> bool {
+        matches!(self, BatchStatus::Failed | BatchStatus::Completed | BatchStatus::Expired | BatchStatus::Cancelled)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+pub struct BatchRequestCounts {
+    pub total: u32,
+    pub completed: u32,
+    pub failed: u32,
+}
+
+/// A batch job, as returned by `/v1/batches`
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct BatchJob {
+    pub id: String,
+    pub status: BatchStatus,
+    pub input_file_id: String,
+    /// Set once some requests have succeeded
+    #[serde(default)]
+    pub output_file_id: Option<String>,
+    /// Set once some requests have failed
+    #[serde(default)]
+    pub error_file_id: Option<String>,
+    /// Unix seconds
+    pub created_at: i64,
+    #[serde(default)]
+    pub request_counts: Option<BatchRequestCounts>,
+}
+
+/// `POST /v1/batches`
+#[derive(Debug, Serialize)]
+struct CreateBatchRequest<'a> {
+    input_file_id: &'a str,
+    endpoint: &'static str,
+    completion_window: &'static str,
+}
+
+/// What came of a collected batch job
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct BatchCollection {
+    /// Cache keys of the answers now in the cache
+    pub cached: Vec<String>,
+    /// Cache keys of the requests that failed, with why
+    pub failed: Vec<(String, String)>,
+    /// In CENTS, with the batch discount applied
+    pub cost: f32,
+}
+
+/// Entries of the jobs submitted but not yet collected, by job id
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+pub struct PendingBatches {
+    pub jobs: HashMap<String, HashMap<String, BatchEntry>>,
+}
+
+impl PendingBatches {
+    /// Reads the file, or starts empty if it is absent. See `load_json()`
+    pub fn load(filepath: &Path) -> Result<PendingBatches, Status> {
+        load_json(filepath)
+    }
+
+    pub fn save(&self, filepath: &Path) -> Result<(), Status> {
+        save_json(self, filepath)
+    }
+}
+
+/// The JSONL input file of a batch, with the custom id of each item by line
+pub fn batch_input_file(items: &[BatchItem]) -> Result<(Vec<u8>, HashMap<String, BatchEntry>), Status> {
+    let mut jsonl = vec![];
+    let mut entries = HashMap::new();
+    for (n, item) in items.iter().enumerate() {
+        let custom_id = format!("item-{n}");
+        let line = BatchInputLine { custom_id: custom_id.clone(), method: "POST", url: "/v1/chat/completions", body: &item.request };
+        jsonl.extend(serde_json::to_vec(&line).map_err(|e| Status::Error(e.to_string()))?);
+        jsonl.push(b'\n');
+        entries.insert(custom_id, item.entry.clone());
+    }
+    Ok((jsonl, entries))
+}
+
+impl OpenAIAccount {
+    /// A batch item asking `prompt`, as `get_completion()` would
+    pub fn batch_chat_item(&self, prompt: &str) -> BatchItem {
+        let request = self.chat_request(prompt);
+        BatchItem {
+            entry: BatchEntry { prompt: prompt.to_string(), document_title: None, model: self.model, temperature: self.temperature, messages: request.messages.clone(), labels: self.labels.clone(), pdf: PdfOpts::default(), tokens_saved: 0 },
+            request,
+        }
+    }
+
+    /// A batch item applying `prompt` to a PDF, as `apply_prompt_to_pdf()` would. Errors if the PDF can't be read.
+    pub fn batch_pdf_item(&self, pdf_title: &str, prompt: &str, input_dir: Option<String>) -> Result<BatchItem, Status> {
+        let doc = self.read_document(Path::new(&pdf_path(input_dir, pdf_title)))?;
+        let request = self.document_request(&doc.text, prompt);
+        Ok(BatchItem {
+            entry: BatchEntry { prompt: prompt.to_string(), document_title: Some(pdf_title.to_string()), model: self.model, temperature: self.temperature, messages: doc.referenced_in(&request.messages), labels: self.labels.clone(), pdf: doc.pdf.clone(), tokens_saved: doc.tokens_saved },
+            request,
+        })
+    }
+
+    /// Uploads `items` as a batch job through the Files API, and starts it. Items already answered in the cache, or repeated, are left out.
+    /// <br> The job runs at OpenAI for up to 24 hours. Its items are kept in `Opts::batch_jobs_filepath`, so that it can be collected by `collect_batch()` from a later process.
+    pub async fn submit_batch(&mut self, items: Vec<BatchItem>) -> Result<BatchJob, Status> {
+        let total = items.len();
+        let mut keys = vec![];
+        let items: Vec<BatchItem> = items.into_iter()
+            .filter(|item| {
+                let key = item.entry.key();
+                if self.cache.entries.get(&key).is_some_and(|cached| cached.pdf() == item.entry.pdf) || keys.contains(&key) { return false }
+                keys.push(key);
+                true
+            })
+            .collect();
+        if items.is_empty() { return Err(Status::Error("Every item of the batch is already cached".to_string())) }
+        println!("📦 Submitting batch of {} items, {} left out as cached or repeated", items.len(), total - items.len());
+
+        // Read before anything is paid for, so that a jobs file that can't be read stops the batch
+        let mut pending = PendingBatches::load(&self.batch_jobs_filepath)?;
+        let (jsonl, entries) = batch_input_file(&items)?;
+        let file = self.upload_file(jsonl, "batch.jsonl", FilePurpose::Batch).await?;
+
+        let req = CreateBatchRequest { input_file_id: &file.id, endpoint: "/v1/chat/completions", completion_window: BATCH_COMPLETION_WINDOW };
+        let res = self.post("/batches", &req).await.map_err(|e| Status::Error(e.to_string()))?;
+        let job = res.json::<BatchJob>().await.map_err(|e| Status::Error(new_error(e).to_string()))?;
+
+        pending.jobs.insert(job.id.clone(), entries);
+        pending.save(&self.batch_jobs_filepath)?;
+        println!("📦 Batch job {} created, status: {:?}", job.id, job.status);
+        Ok(job)
+    }
+
+    pub async fn batch_status(&self, job_id: &str) -> Result<BatchJob, Status> {
+        let res = self.sender().get(&format!("/batches/{job_id}")).await.map_err(|e| Status::Error(e.to_string()))?;
+        res.json::<BatchJob>().await.map_err(|e| Status::Error(new_error(e).to_string()))
+    }
+
+    /// Polls the job every `poll_interval` until it is done
+    pub async fn wait_for_batch(&self, job_id: &str, poll_interval: Duration) -> Result<BatchJob, Status> {
+        loop {
+            let job = self.batch_status(job_id).await?;
+            if job.status.is_done() { return Ok(job) }
+            if let Some(counts) = job.request_counts {
+                println!("📦 Batch job {job_id} {:?}: {}/{} done, {} failed", job.status, counts.completed, counts.total, counts.failed);
+            }
+            tokio::time::sleep(poll_interval).await;
+        }
+    }
+
+    /// Downloads the results of a finished job, and caches and bills each answer at the batch discount. The job is then forgotten from `Opts::batch_jobs_filepath`.
+    /// <br> Errors without collecting anything if the job is still running, or was not submitted from this client's jobs file.
+    pub async fn collect_batch(&mut self, job_id: &str) -> Result<BatchCollection, Status> {
+        let mut pending = PendingBatches::load(&self.batch_jobs_filepath)?;
+        let entries = pending.jobs.get(job_id).cloned().ok_or(Status::NotFoundError)?;
+        let job = self.batch_status(job_id).await?;
+        if !job.status.is_done() {
+            return Err(Status::Error(format!("Batch job {job_id} is still {:?}", job.status)))
+        }
+
+        let mut lines = vec![];
+        for file_id in [&job.output_file_id, &job.error_file_id].into_iter().flatten() {
+            lines.extend(self.file_content(file_id).await?);
+            lines.push(b'\n');
+        }
+
+        let collection = self.record_batch_output(&entries, &lines)?;
+        pending.jobs.remove(job_id);
+        pending.save(&self.batch_jobs_filepath)?;
+        println!("📦 Batch job {job_id} collected. Cached: {}, failed: {}, cost: ¢{:.4}", collection.cached.len(), collection.failed.len(), collection.cost);
+        Ok(collection)
+    }
+
+    /// Caches and bills the answers in the JSONL `output` of a batch of `entries`
+    fn record_batch_output(&mut self, entries: &HashMap<String, BatchEntry>, output: &[u8]) -> Result<BatchCollection, Status> {
+        let results = batch_results(entries, output)?;
+        let mut collection = BatchCollection { failed: results.failed, ..Default::default() };
+        for query in results.queries {
+            collection.cost += query.cost();
+            collection.cached.push(query.key());
+            self.cache.insert(&query);
+            self.bill.update(Some(query));
+        }
+        Ok(collection)
+    }
+}
+
+/// What the output of a batch holds, before it is cached
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct BatchResults {
+    /// Priced at the batch discount
+    pub queries: Vec<Query>,
+    /// Cache keys of the items that failed, with why. Items missing from the output count as failed.
+    pub failed: Vec<(String, String)>,
+}
+
+/// The results in the JSONL `output` of a batch of `entries`
+pub fn batch_results(entries: &HashMap<String, BatchEntry>, output: &[u8]) -> Result<BatchResults, Status> {
+    let mut results = BatchResults::default();
+    let mut seen: HashSet<String> = HashSet::new();
+    for line in output.split(|byte| *byte == b'\n').filter(|line| !line.iter().all(u8::is_ascii_whitespace)) {
+        let line: BatchOutputLine = serde_json::from_slice(line).map_err(|e| Status::Error(e.to_string()))?;
+        let entry = match entries.get(&line.custom_id) { Some(entry) => entry, None => continue };
+        seen.insert(line.custom_id.clone());
+
+        let response = match (line.response, line.error) {
+            (Some(response), _) if response.status_code == 200 => serde_json::from_value::<ChatCompletionResponse>(response.body).map_err(|e| e.to_string()),
+            (Some(response), _) => Err(format!("Status {}: {}", response.status_code, response.body)),
+            (None, Some(error)) => Err(error.message.unwrap_or_default()),
+            (None, None) => Err("No response".to_string()),
+        };
+        match response {
+            Ok(response) => results.queries.push(entry.to_query(response)),
+            Err(e) => results.failed.push((entry.key(), e)),
+        }
+    }
+    for (custom_id, entry) in entries {
+        if !seen.contains(custom_id) {
+            results.failed.push((entry.key(), "Missing from the batch output".to_string()));
+        }
+    }
+    Ok(results)
+}
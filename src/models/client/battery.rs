@@ -0,0 +1,236 @@
+use std::{
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
+};
+use serde::{Serialize, Deserialize};
+
+use crate::{
+    constants::pdf_path::DEFAULT_PDF_GLOB,
+    models::{labels::Labels, MetaQuery},
+    Query,
+    TextQuery,
+};
+use super::{
+    core::{OpenAIAccount, Status},
+    directory::{document_title, find_documents, title_clash, title_clashes},
+};
+
+/// One question of a `Battery`
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct BatteryPrompt {
+    /// Short and unique within the battery. Heads the column of its answers in a `BatteryMatrix`
+    pub id: String,
+    pub prompt: String,
+}
+
+/// A named, versioned set of prompts, run together against each document so that every document is asked the same questions.
+/// <br> Read from TOML or YAML, such as:
+/// ```toml
+/// name = "lipids"
+/// version = "2"
+///
+/// [[prompts]]
+/// id = "population"
+/// prompt = "Which population was studied?"
+/// ```
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Battery {
+    pub name: String,
+    /// Bump when the prompts change, so that answers to the new prompts can be told from the old by their stamp
+    pub version: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    pub prompts: Vec<BatteryPrompt>,
+}
+
+impl Battery {
+    /// Reads a battery from a `.toml`, `.yaml` or `.yml` file
+    pub fn load(path: &Path) -> Result<Battery, Status> {
+        let text = fs::read_to_string(path).map_err(|e| Status::Error(format!("{}: {e}", path.display())))?;
+        let extension = path.extension().map(|extension| extension.to_string_lossy().to_lowercase()).unwrap_or_default();
+        match extension.as_str() {
+            "toml" => Battery::from_toml(&text),
+            "yaml" | "yml" => Battery::from_yaml(&text),
+            _ => Err(Status::Error(format!("{}: a battery is read from a .toml, .yaml or .yml file", path.display()))),
+        }
+    }
+
+    pub fn from_toml(text: &str) -> Result<Battery, Status> {
+        let battery: Battery = toml::from_str(text).map_err(|e| Status::Error(format!("Invalid battery: {e}")))?;
+        battery.validated()
+    }
+
+    pub fn from_yaml(text: &str) -> Result<Battery, Status> {
+        let battery: Battery = serde_yaml::from_str(text).map_err(|e| Status::Error(format!("Invalid battery: {e}")))?;
+        battery.validated()
+    }
+
+    /// Tagged on every query the battery makes or serves from the cache, such as `battery:lipids@2`. Find them with `CacheSearch::tag()`
+    pub fn stamp(&self) -> String {
+        format!("battery:{}@{}", self.name, self.version)
+    }
+
+    fn validated(self) -> Result<Battery, Status> {
+        if self.name.trim().is_empty() || self.version.trim().is_empty() {
+            return Err(Status::Error("Invalid battery: the name and version must not be empty".to_string()))
+        }
+        if self.prompts.is_empty() {
+            return Err(Status::Error(format!("Invalid battery {}: it has no prompts", self.stamp())))
+        }
+        let mut ids = HashSet::new();
+        if let Some(prompt) = self.prompts.iter().find(|prompt| !ids.insert(prompt.id.as_str())) {
+            return Err(Status::Error(format!("Invalid battery {}: the prompt id \"{}\" is used twice", self.stamp(), prompt.id)))
+        }
+        Ok(self)
+    }
+}
+
+/// The answers of one document to every prompt of a battery
+#[derive(Debug, Clone, PartialEq)]
+pub struct BatteryRow {
+    pub path: PathBuf,
+    pub document_title: String,
+    /// One per prompt of the battery, in order. The error is kept for a prompt that failed.
+    pub cells: Vec<Result<TextQuery, String>>,
+}
+
+/// What `OpenAIAccount::apply_battery_to_dir()` or `apply_battery_to_document()` got: a row per document, and a column per prompt
+#[derive(Debug, Clone, PartialEq)]
+pub struct BatteryMatrix {
+    pub battery: Battery,
+    pub rows: Vec<BatteryRow>,
+}
+
+impl BatteryMatrix {
+    pub fn new(battery: &Battery) -> Self {
+        BatteryMatrix { battery: battery.clone(), rows: vec![] }
+    }
+
+    /// The answer of the document titled `document_title` to the prompt `prompt_id`, if it was answered
+    pub fn answer(&self, document_title: &str, prompt_id: &str) -> Option<String> {
+        let column = self.battery.prompts.iter().position(|prompt| prompt.id == prompt_id)?;
+        let row = self.rows.iter().find(|row| row.document_title == document_title)?;
+        row.cells.get(column).and_then(answer_text)
+    }
+
+    /// Every answered query, row by row
+    pub fn text_queries(&self) -> Vec<&TextQuery> {
+        self.rows.iter().flat_map(|row| row.cells.iter().filter_map(|cell| cell.as_ref().ok())).collect()
+    }
+
+    /// `text_queries()`, for `OpenAIAccount::meta_complete_queries()`
+    pub fn queries(&self) -> Vec<Query> {
+        self.text_queries().into_iter().map(|query| Query::TextQuery(query.clone())).collect()
+    }
+
+    /// The document and prompt id of every cell that failed, with why
+    pub fn failed(&self) -> Vec<(&str, &str, &str)> {
+        self.rows.iter().flat_map(|row| {
+            row.cells.iter().zip(&self.battery.prompts).filter_map(|(cell, prompt)| match cell {
+                Err(e) => Some((row.document_title.as_str(), prompt.id.as_str(), e.as_str())),
+                Ok(_) => None,
+            })
+        }).collect()
+    }
+
+    /// In CENTS. Answers served from the cache cost nothing
+    pub fn cost(&self) -> f32 {
+        self.text_queries().iter().filter(|query| !query.from_cache).map(|query| query.cost).sum()
+    }
+
+    /// A header of `document` and the prompt ids, then a line per document with its answers. A cell that failed is left empty.
+    pub fn to_csv(&self) -> String {
+        let mut lines = vec![];
+        lines.push(std::iter::once("document").chain(self.battery.prompts.iter().map(|prompt| prompt.id.as_str())).map(csv_field).collect::<Vec<_>>().join(","));
+        for row in &self.rows {
+            let answers = row.cells.iter().map(|cell| answer_text(cell).unwrap_or_default());
+            lines.push(std::iter::once(row.document_title.clone()).chain(answers).map(|field| csv_field(&field)).collect::<Vec<_>>().join(","));
+        }
+        lines.join("\n") + "\n"
+    }
+
+    pub fn save_csv(&self, filepath: &Path) -> Result<(), Status> {
+        fs::write(filepath, self.to_csv()).map_err(|e| Status::Error(e.to_string()))
+    }
+
+    pub fn print(&self) {
+        println!("\n");
+        println!("🔋 Battery {}: {} documents × {} prompts", self.battery.stamp(), self.rows.len(), self.battery.prompts.len());
+        println!("Answered: {}", self.text_queries().len());
+        println!("Failed: {}", self.failed().len());
+        for (document_title, prompt_id, e) in self.failed() {
+            println!("  {document_title} / {prompt_id}:  ❌  {e}");
+        }
+        println!("Cost: ${:.2}", self.cost() / 100.0);
+        println!("\n");
+    }
+}
+
+fn answer_text(cell: &Result<TextQuery, String>) -> Option<String> {
+    cell.as_ref().ok()?.response.choices.first().and_then(|choice| choice.message.content.clone())
+}
+
+/// Quoted if it holds a comma, quote or line break, with quotes doubled
+fn csv_field(field: &str) -> String {
+    match field.contains([',', '"', '\n', '\r']) {
+        true => format!("\"{}\"", field.replace('"', "\"\"")),
+        false => field.to_string(),
+    }
+}
+
+impl OpenAIAccount {
+    /// Applies every prompt of `battery` to the document at `path`, as `apply_prompt_to_document()` does, tagging new queries with `Battery::stamp()`.
+    /// <br> A prompt already answered for the document is served from the cache, and the stamp is added to the labels of its cache entry, so that the stamp finds every cell of the battery.
+    pub async fn apply_battery_to_document(&mut self, battery: &Battery, path: &Path) -> BatteryMatrix {
+        let mut matrix = BatteryMatrix::new(battery);
+        matrix.rows.push(self.battery_row(battery, path).await);
+        matrix
+    }
+
+    /// Applies every prompt of `battery` to every document under `dir` matching `glob` (`*.pdf` if `None`). See `apply_battery_to_document()`
+    /// <br> Documents sharing a title get a row of failed cells, as in `apply_prompt_to_dir()`.
+    /// <br> A run that is interrupted is picked up by calling again, since the prompts already answered are served from the cache.
+    pub async fn apply_battery_to_dir(&mut self, battery: &Battery, dir: &Path, glob: Option<&str>) -> Result<BatteryMatrix, Status> {
+        let glob = glob.unwrap_or(DEFAULT_PDF_GLOB);
+        let documents = find_documents(dir, glob)?;
+        let clashes = title_clashes(&documents);
+        println!("🔋 Running battery {} over {} documents in {} matching \"{glob}\"", battery.stamp(), documents.len(), dir.display());
+        let mut matrix = BatteryMatrix::new(battery);
+        for path in documents {
+            let row = match title_clash(&clashes, &path) {
+                Some(e) => BatteryRow { document_title: document_title(&path), cells: battery.prompts.iter().map(|_| Err(e.clone())).collect(), path },
+                None => self.battery_row(battery, &path).await,
+            };
+            matrix.rows.push(row);
+        }
+        Ok(matrix)
+    }
+
+    /// Applies `prompt` to the answers of `matrix`, sent as its CSV after a legend of the prompts, such as to compare documents or summarize the answers to each prompt
+    pub async fn meta_complete_battery(&mut self, prompt: &str, matrix: &BatteryMatrix) -> Result<MetaQuery, Status> {
+        let legend: String = matrix.battery.prompts.iter().map(|battery_prompt| format!("{}: {}\n", battery_prompt.id, battery_prompt.prompt)).collect();
+        let data = format!("A table of the answers of documents (rows) to prompts (columns), as CSV.\n\nColumns:\n{legend}\n{}", matrix.to_csv());
+        let labels = Labels::default().with_tag(&matrix.battery.stamp());
+        self.meta_complete_data_labeled(prompt, &data, &labels).await
+    }
+
+    async fn battery_row(&mut self, battery: &Battery, path: &Path) -> BatteryRow {
+        let labels = Labels::default().with_tag(&battery.stamp());
+        let mut cells = vec![];
+        for battery_prompt in &battery.prompts {
+            let cell = match self.apply_prompt_to_document_labeled(path, &battery_prompt.prompt, &labels).await {
+                Ok(mut query) => {
+                    if query.from_cache {
+                        self.cache.merge_labels(&TextQuery::key(&query.prompt, &query.document_title), &labels);
+                    }
+                    query.labels = query.labels.merged(&labels);
+                    Ok(query)
+                },
+                Err(e) => Err(format!("{e:?}")),
+            };
+            cells.push(cell);
+        }
+        BatteryRow { path: path.to_path_buf(), document_title: document_title(path), cells }
+    }
+}
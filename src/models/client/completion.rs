@@ -1,3 +1,5 @@
+use std::path::Path;
+use chrono::Utc;
 use crate::{
     models::{
         client::core::{OpenAIAccount, Status},
@@ -7,6 +9,14 @@ use crate::{
         ChatQuery, 
         TextQuery,
         MetaQuery,
+        labels::Labels,
+        search::CacheSearch,
+        documents::{
+            pdf::{PdfOpts, PdfPage},
+            citations::{page_marked_text, parse_citations, CITATION_INSTRUCTIONS},
+            DocumentStore,
+            StoredDocument,
+        },
     }, 
     constants::{pdf_path::DEFAULT_PDF_DIR},
     Query,
@@ -19,16 +29,30 @@ impl OpenAIAccount {
     /// Sends the prompt as the first message, and returns the chat completion response.
     /// <br> Checks cache for presence of prompt, and returns the cache value if present instead of repeating request.
     pub async fn get_completion(&mut self, prompt: &str) -> Result<ChatQuery, Status> {
+        self.get_completion_labeled(prompt, &Labels::default()).await
+    }
+
+    /// `get_completion()`, with `labels` laid over the ambient `OpenAIAccount::labels` of a new query. A cached answer keeps the labels it was made with.
+    pub async fn get_completion_labeled(&mut self, prompt: &str, labels: &Labels) -> Result<ChatQuery, Status> {
 
         let model = self.model;
         let key = ChatQuery::key(prompt);
 
-        let query = match self.cache.entries.get(&key) {
+        // Exact key first, then a near-duplicate prompt if the semantic cache is on
+        let (cached, embedding) = match self.cache.get(&key) {
+            Some(query) => (Some((query, None)), None),
+            None => {
+                let (hit, embedding) = self.semantic_lookup(prompt, None, |_| true).await;
+                (hit.map(|(query, similarity)| (query, Some(similarity))), embedding)
+            },
+        };
+
+        let query = match cached {
             // If found in cache, retrieve the query
-            Some(query) => {
-                if let Query::ChatQuery(cq) = query {
-                    let mut cq = cq.clone(); 
+            Some((query, similarity)) => {
+                if let Query::ChatQuery(mut cq) = query {
                     cq.from_cache = true;
+                    cq.similarity = similarity;
                     self.bill.cache_retrievals += 1; 
                     self.bill.update(None); 
                     println!("--[Cached Answer]--");
@@ -40,29 +64,21 @@ impl OpenAIAccount {
             // If absent, send to OpenAI
             None => {
                 let from_cache = false;
-                let req = ChatCompletionRequest {
-                    model: model,
-                    messages: vec![ChatCompletionMessage {
-                        role: MessageRole::user,
-                        content: Some(prompt.to_string()),
-                        name: None,
-                        function_call: None,
-                    }],
-                    temperature: Some(self.temperature.into()),
-                    ..Default::default()
-                };
+                let req = self.chat_request(prompt);
 
+                let messages = req.messages.clone();
                 let start_time = std::time::Instant::now();
                 let response = match self.send_completion_request(req).await {Ok(res) => res, Err(e) => return Err(Status::Error(e.to_string()))};
-                let process_time = start_time.elapsed().as_secs();
+                let process_time = start_time.elapsed().as_millis() as u64;
 
-                let query = ChatQuery {prompt: prompt.to_string(), response: response.clone(), cost: response.cost(&model), process_time, model, temperature: self.temperature, from_cache };
+                let query = ChatQuery {prompt: prompt.to_string(), response: response.clone(), cost: response.cost(&model), process_time, model, temperature: self.temperature, from_cache, similarity: None, created_at: Utc::now(), last_accessed: Utc::now(), updated_at: Utc::now(), messages, labels: self.labels.merged(labels) };
                 
                 self.cache.insert(&Query::ChatQuery( query.clone() ));
+                self.semantic_remember(key.clone(), embedding);
                 self.bill.update(Some(Query::ChatQuery( query.clone() )));
 
                 println!("--[Bill so far: ${:.2}]--", self.bill.cost / 100.0);
-                println!("--[Took: {}, Cost: ¢{:.4}]--", process_time, (response.cost(&model)));
+                println!("--[Took: {}ms, Cost: ¢{:.4}]--", process_time, (response.cost(&model)));
                 query
             },
         };
@@ -75,16 +91,43 @@ impl OpenAIAccount {
     /// `input_dir`
     /// `pdf_title` filename without extension
     pub async fn apply_prompt_to_pdf(&mut self, pdf_title: &str, prompt: &str, input_dir: Option<String>) -> Result<TextQuery, Status> {
+        self.apply_prompt_to_pdf_labeled(pdf_title, prompt, input_dir, &Labels::default()).await
+    }
+
+    /// `apply_prompt_to_pdf()`, with `labels` laid over the ambient `OpenAIAccount::labels` of a new query. A cached answer keeps the labels it was made with.
+    pub async fn apply_prompt_to_pdf_labeled(&mut self, pdf_title: &str, prompt: &str, input_dir: Option<String>, labels: &Labels) -> Result<TextQuery, Status> {
+        self.apply_prompt_to_document_labeled(Path::new(&pdf_path(input_dir, pdf_title)), prompt, labels).await
+    }
+
+    /// Applies `prompt` to the document at `path`, read by the `DocumentLoader` of its format: PDF, plain text, markdown, HTML, DOCX or EPUB. See `DocumentFormat::detect()`
+    /// <br> The document title of the query is the filename without extension, as for `apply_prompt_to_pdf()`, so a PDF gets the same `TextQuery` either way. Checks cache for the title and prompt, and returns the cache value if present and the document was read with the same pages and cleanup, instead of loading the document. See `TextQuery::pdf`
+    pub async fn apply_prompt_to_document(&mut self, path: &Path, prompt: &str) -> Result<TextQuery, Status> {
+        self.apply_prompt_to_document_labeled(path, prompt, &Labels::default()).await
+    }
+
+    /// `apply_prompt_to_document()`, with `labels` laid over the ambient `OpenAIAccount::labels` of a new query. A cached answer keeps the labels it was made with.
+    pub async fn apply_prompt_to_document_labeled(&mut self, path: &Path, prompt: &str, labels: &Labels) -> Result<TextQuery, Status> {
         println!("\n--🗳️");
-        let dir = match input_dir { None => DEFAULT_PDF_DIR.to_string(), Some(s) => s };
-        let path_to_pdf = if dir.ends_with("/") {format!("{dir}{pdf_title}.pdf")} else if dir.contains("\\") {format!("{dir}\\{pdf_title}.pdf")} else {format!("{dir}/{pdf_title}.pdf")};
-        let key = TextQuery::key(prompt, pdf_title);
+        let title = path.file_stem().map(|stem| stem.to_string_lossy().to_string()).unwrap_or_default();
+        let key = TextQuery::key(prompt, &title);
+        // An answer to the document read with other pages, cleanup or citations is not served
+        let read_as = StoredDocument::read_opts(path, &self.pdf);
 
-        let query = match self.cache.entries.get(&key) {
+        // Exact key first, then a near-duplicate prompt on the same document if the semantic cache is on
+        let (cached, embedding) = match self.cache.get_if(&key, |query| query.pdf() == read_as) {
+            Some(query) => (Some((query, None)), None),
+            None => {
+                let (hit, embedding) = self.semantic_lookup(prompt, Some(&title), |query| query.pdf() == read_as).await;
+                (hit.map(|(query, similarity)| (query, Some(similarity))), embedding)
+            },
+        };
+
+        let query = match cached {
             // If found in cache, retrieve the query
-            Some(query) => {
-                let mut query = query.clone().expect_as_text();
+            Some((query, similarity)) => {
+                let mut query = query.expect_as_text();
                 query.from_cache = true; 
+                query.similarity = similarity;
                 self.bill.cache_retrievals += 1; 
                 self.bill.update(None); 
                 println!("--[Cached Answer]--");
@@ -94,49 +137,37 @@ impl OpenAIAccount {
             None => {
                 let from_cache = false;
                 println!("--[Sending to GPT]--");
-                // Load the pdf from the provided file path, or else return to the caller a NotFoundError 
-                let pdf = lopdf::Document::load(path_to_pdf).map_err(|e| return Status::Error(e.to_string()))?;
-            
-                let mut doc = String::new();
-                for page in 1..=pdf.get_pages().len() {
-                    let content = pdf.extract_text(&[page as u32]).expect("parse");
-                    doc.push_str(&content);
-                }
-                
-                let req = ChatCompletionRequest {
-                    model: self.model,
-                    temperature: Some(self.temperature.into()),
-                    messages: vec![
-                        ChatCompletionMessage {
-                            role: MessageRole::system,
-                            content: Some(String::from("You will receive a document, and a prompt regarding the document.")),
-                            ..Default::default()
-                        },
-                        ChatCompletionMessage {
-                            role: MessageRole::system,
-                            content: Some(format!("{doc}")),
-                            ..Default::default()
-                        },
-                        ChatCompletionMessage {
-                            role: MessageRole::user,
-                            content: Some(format!("{prompt}")),
-                            ..Default::default()
-                        },
-                    ], 
-                    ..Default::default()
+                // Read the document's text from the document store, extracting it if needed, or else return to the caller the error
+                let doc = self.read_document(path)?;
+                println!("--[Read {:?} document: {}]--", doc.metadata.format, path.display());
+                let pages = doc.pages()?;
+                let cite_pages = self.pdf.cite_pages && !pages.is_empty();
+                let req = match cite_pages {
+                    true => self.cited_document_request(&pages, prompt),
+                    false => self.document_request(&doc.text, prompt),
                 };
 
+                let messages = doc.referenced_in(&req.messages);
                 let start_time = std::time::Instant::now();
                 let response = self.send_completion_request(req).await.map_err(|e| Status::Error(e.to_string()))?;
                 let process_time = start_time.elapsed().as_millis() as u64;
                 
                 println!("--[Completion received]--");
 
+                let citations = match cite_pages {
+                    true => parse_citations(&response.choices.first().and_then(|choice| choice.message.content.clone()).unwrap_or_default(), &pages),
+                    false => vec![],
+                };
+                if cite_pages {
+                    println!("--[{} citations, {} flagged]--", citations.len(), citations.iter().filter(|citation| !citation.verified).count());
+                }
+
                 // Build Query from Response
-                let text_query = TextQuery { prompt: prompt.to_string(), response: response.clone(), document_title: pdf_title.to_string(), model: self.model, process_time, cost: response.cost(&self.model), temperature: self.temperature, from_cache };
+                let text_query = TextQuery { prompt: prompt.to_string(), response: response.clone(), document_title: title.clone(), model: self.model, process_time, cost: response.cost(&self.model), temperature: self.temperature, from_cache, similarity: None, created_at: Utc::now(), last_accessed: Utc::now(), updated_at: Utc::now(), messages, labels: self.labels.merged(labels), citations, pdf: PdfOpts { cite_pages, ..doc.pdf.clone() }, tokens_saved: doc.tokens_saved };
                 let query_for_cache = Query::TextQuery(text_query.clone());
                 
                 self.cache.insert(&query_for_cache); // Add Query to Cache
+                self.semantic_remember(key.clone(), embedding);
                 self.bill.update(Some(query_for_cache)); // Add data to Bill
                 println!("--[Bill now shows: ${:.2}]--", self.bill.cost / 100.0);
                 println!("--[Took: {}ms, Cost: ¢{:.4}]--", process_time, (response.cost(&self.model)));
@@ -149,34 +180,53 @@ impl OpenAIAccount {
     }
 
 
+    /// Applies `prompt` to the responses of every chat and text query in the cache
     pub async fn meta_complete_cache(&mut self, prompt: &str) -> Result<MetaQuery, Status>  {
+        let queries: Vec<Query> = self.cache.entries.values()
+            .filter(|query| matches!(query, Query::TextQuery(_) | Query::ChatQuery(_)))
+            .cloned()
+            .collect();
+        self.meta_complete_queries(prompt, &queries).await
+    }
+
+    /// Applies `prompt` to the responses of the chat and text queries matching `search`, such as those of one project or run
+    pub async fn meta_complete_search(&mut self, prompt: &str, search: &CacheSearch) -> Result<MetaQuery, Status>  {
+        let queries: Vec<Query> = search.run(&self.cache).into_iter()
+            .map(|(_, query)| query)
+            .filter(|query| matches!(query, Query::TextQuery(_) | Query::ChatQuery(_)))
+            .collect();
+        self.meta_complete_queries(prompt, &queries).await
+    }
+
+    /// Applies `prompt` to the responses of the given queries, such as the results of a `CacheSearch`
+    pub async fn meta_complete_queries(&mut self, prompt: &str, queries: &[Query]) -> Result<MetaQuery, Status>  {
+        self.meta_complete_queries_labeled(prompt, queries, &Labels::default()).await
+    }
+
+    /// `meta_complete_queries()`, with `labels` laid over the ambient `OpenAIAccount::labels` of the new query
+    pub async fn meta_complete_queries_labeled(&mut self, prompt: &str, queries: &[Query], labels: &Labels) -> Result<MetaQuery, Status>  {
         println!("\n--🗳️  Meta Completion");
-        
-        let key = MetaQuery::key(&prompt);
+
+        // Convert the queries into a list of responses
+        let mut build_response_list = String::new();
+        println!("--[Combining Essays:");
+        for (iter, query) in queries.iter().enumerate() {
+            build_response_list.push_str(format!("\n\n{})\n", iter + 1).as_str());
+            let content = query.content().expect("presence of content field in GPT-response");
+            build_response_list.push_str(content.as_str());
+        }
+        let response_list = build_response_list;
+        println!("\n--Essays combined and ready for meta-completion.]--");
+
+        self.meta_complete_data_labeled(prompt, &response_list, labels).await
+    }
+
+    /// Applies `prompt` to `data`, such as the combined responses of earlier queries, with `labels` laid over the ambient `OpenAIAccount::labels` of the new query
+    pub(super) async fn meta_complete_data_labeled(&mut self, prompt: &str, data: &str, labels: &Labels) -> Result<MetaQuery, Status>  {
+        let key = MetaQuery::key(prompt);
         
         let query = {
                 let from_cache = false;
-                // Convert the cache's PdfCompletions into a list of responses
-                let mut build_response_list = String::new();
-                let mut iter = 0;
-                println!("--[Combining Essays:");
-                for (_cache_key, query) in &self.cache.entries {
-                    if let Query::TextQuery(query) = query {
-                        iter += 1;
-                        build_response_list.push_str(format!("\n\n{iter})\n").as_str());
-                        let content = query.response.choices[0].message.content.clone().expect("presence of content field in GPT-response");
-                        build_response_list.push_str(content.as_str());
-                    }
-                    if let Query::ChatQuery(query) = query {
-                        iter += 1;
-                        build_response_list.push_str(format!("\n\n{iter})\n").as_str());
-                        let content = query.response.choices[0].message.content.clone().expect("presence of content field in GPT-response");
-                        build_response_list.push_str(content.as_str());
-                    }
-                    //if iter == 3 {println!("--Current state of the input at 3:\n{build_input}");}
-                }
-                let response_list = build_response_list;
-                println!("\n--Essays combined and ready for meta-completion.]--");
 
                 println!("--[Sending to GPT]--");
                 let req = ChatCompletionRequest {
@@ -189,7 +239,7 @@ impl OpenAIAccount {
                         },
                         ChatCompletionMessage {
                             role: MessageRole::system,
-                            content: Some(format!("Data: {response_list}")),
+                            content: Some(format!("Data: {data}")),
                             ..Default::default()
                         },
                         ChatCompletionMessage {
@@ -200,13 +250,14 @@ impl OpenAIAccount {
                     ],..Default::default()
                 };
 
+                let messages = req.messages.clone();
                 let start_time = std::time::Instant::now();
                 let response = self.send_completion_request(req).await.map_err(|e| Status::Error(e.to_string()))?;
                 let process_time = start_time.elapsed().as_millis() as u64;
                 
                 println!("--[Completion received]--");
 
-                let meta_query = MetaQuery { prompt: prompt.to_string(), response: response.clone(), model: self.model, process_time, cost: response.cost(&self.model), temperature: self.temperature, from_cache };
+                let meta_query = MetaQuery { prompt: prompt.to_string(), response: response.clone(), model: self.model, process_time, cost: response.cost(&self.model), temperature: self.temperature, from_cache, similarity: None, created_at: Utc::now(), last_accessed: Utc::now(), updated_at: Utc::now(), messages, labels: self.labels.merged(labels) };
                 let query_for_cache = Query::MetaQuery(meta_query.clone());
 
                 self.cache.insert(&query_for_cache);
@@ -223,4 +274,67 @@ impl OpenAIAccount {
         Ok(query)
 
     }
-}
\ No newline at end of file
+
+    /// The request asking `prompt` as the only message
+    pub(super) fn chat_request(&self, prompt: &str) -> ChatCompletionRequest {
+        ChatCompletionRequest {
+            model: self.model,
+            messages: vec![ChatCompletionMessage {
+                role: MessageRole::user,
+                content: Some(prompt.to_string()),
+                name: None,
+                function_call: None,
+            }],
+            temperature: Some(self.temperature.into()),
+            ..Default::default()
+        }
+    }
+
+    /// The text of the document at `path`, from `Opts::document_store_dir` if this version of it was read before with the same `Opts::pdf`, else extracted and stored there
+    pub fn read_document(&self, path: &Path) -> Result<StoredDocument, Status> {
+        DocumentStore::new(&self.document_store_dir).read(path, &self.pdf)
+    }
+
+    /// The request applying `prompt` to the text of `pages`, each marked with its number, asking for the pages to be cited. See `Citation`
+    pub(super) fn cited_document_request(&self, pages: &[PdfPage], prompt: &str) -> ChatCompletionRequest {
+        let mut req = self.document_request(&page_marked_text(pages), prompt);
+        req.messages.insert(1, ChatCompletionMessage {
+            role: MessageRole::system,
+            content: Some(CITATION_INSTRUCTIONS.to_string()),
+            ..Default::default()
+        });
+        req
+    }
+
+    /// The request applying `prompt` to the text `doc` of a document
+    pub(super) fn document_request(&self, doc: &str, prompt: &str) -> ChatCompletionRequest {
+        ChatCompletionRequest {
+            model: self.model,
+            temperature: Some(self.temperature.into()),
+            messages: vec![
+                ChatCompletionMessage {
+                    role: MessageRole::system,
+                    content: Some(String::from("You will receive a document, and a prompt regarding the document.")),
+                    ..Default::default()
+                },
+                ChatCompletionMessage {
+                    role: MessageRole::system,
+                    content: Some(doc.to_string()),
+                    ..Default::default()
+                },
+                ChatCompletionMessage {
+                    role: MessageRole::user,
+                    content: Some(prompt.to_string()),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        }
+    }
+}
+
+/// Path of the PDF titled `pdf_title` in `input_dir`, or in `DEFAULT_PDF_DIR` if `None`
+pub(super) fn pdf_path(input_dir: Option<String>, pdf_title: &str) -> String {
+    let dir = match input_dir { None => DEFAULT_PDF_DIR.to_string(), Some(s) => s };
+    if dir.ends_with("/") {format!("{dir}{pdf_title}.pdf")} else if dir.contains("\\") {format!("{dir}\\{pdf_title}.pdf")} else {format!("{dir}/{pdf_title}.pdf")}
+}
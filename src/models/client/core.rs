@@ -2,18 +2,20 @@ use std::{
     io,
     fs,
     collections::HashMap, 
-    path::PathBuf
+    path::PathBuf,
+    sync::Arc,
 };
-use sea_orm::{DatabaseConnection, Database};
 
 use crate::{
     models::{
-        client::database::DbMethods,
-        cache::Cache, 
+        client::{database::{DbMethods, PoolOpts}, graveyard::Graveyard, requests::{RateLimit, RateLimiter}},
+        cache::{Cache, EvictionPolicy, read_entries},
+        semantic::{SemanticIndex, SemanticOpts},
+        labels::Labels,
+        documents::PdfOpts,
         Bill, 
     },
     GptModel, 
-    Query, 
 };
 
 
@@ -23,6 +25,8 @@ pub struct OpenAIAccount  {
     pub(super) model: GptModel,
     /// Default value looks for `CHATGPT_API_KEY` environment var
     pub(super) api_key: String,
+    /// Shared by every request, so that concurrent ones keep to `Opts::rate_limit` together
+    pub(super) rate_limiter: Option<Arc<RateLimiter>>,
     pub(super) temperature: f32,
     /// Attribute used to save and retrieve running metrics, which are running totals of Query metrics. 
     /// <br> This variable is serialized into and deserialized from this OpenAIAccount's `.self.cache_filepath` attribute. The running total can be reset with ...
@@ -33,19 +37,65 @@ pub struct OpenAIAccount  {
     /// If a query completion is sent, and the prompt is already found in the cache, the cached response is retrieved, and a new API request is not sent.
     /// Keys are prompts, values are Queries (which themselves hold the prompt, model, etc.)
     pub cache: Cache,
+    /// Prompt embeddings for near-duplicate cache matches. `None` unless `Opts::semantic` was set.
+    pub semantic: Option<SemanticIndex>,
     pub db: DbMethods,
+    /// Where the watermark of the last sync with the database is kept. See `OpenAIAccount::sync()`
+    pub(super) sync_filepath: PathBuf,
+    /// Where the progress of `OpenAIAccount::apply_prompt_to_dir()` is kept
+    pub(super) checkpoint_filepath: PathBuf,
+    /// Where the items of submitted batch jobs are kept until collected
+    pub(super) batch_jobs_filepath: PathBuf,
+    /// Where the files uploaded from our documents are registered
+    pub(super) files_filepath: PathBuf,
+    /// Where the metadata and references of analyzed documents are kept
+    pub(super) document_records_filepath: PathBuf,
+    /// Where the text extracted from documents is kept
+    pub(super) document_store_dir: PathBuf,
+    /// Stamped on every new query, merged with any labels given per call. See `Labels`
+    pub labels: Labels,
+    /// Pages and cleanup of the PDFs sent. Can be changed between calls.
+    pub pdf: PdfOpts,
 }
 
 pub struct Opts {
     pub model: GptModel,
     /// `0.0 - 0.4`: Produces more focused, conservative, and consistent responses. <br> `0.5 - 0.7`: Strikes a balance between creativity and consistency. <br> `0.8 - 1.0`: Generates more creative, diverse, and unexpected outputs. <br> Default sets to 0.0
     pub temperature: f32,
-    /// `bool` for whether you plan to use database functionality. If not, it will not try to connect, and panic on usage of db methods. Else if true, connects once on initialization.
+    /// `bool` for whether you plan to use database functionality. If true, failing to connect on initialization is an error. Else a missing database is tolerated, and db methods return `Status::DatabaseNotConfigured`.
     pub database: bool,
+    /// Size and timeouts of the database connection pool
+    pub pool: PoolOpts,
+    /// Whether to apply pending migrations of the bundled schema on connecting to the database. See `DbMethods::migration_status()`
+    pub run_migrations: bool,
     /// If path does not exist, error. Will not create path for you.
     pub bill_filepath: PathBuf, 
     /// If path does not exist, error. Will not create path for you.
-    pub cache_filepath: PathBuf 
+    pub cache_filepath: PathBuf,
+    /// History of overwritten and evicted cache entries. Created if absent, and kept across restarts.
+    pub graveyard_filepath: PathBuf,
+    /// When the cache was last synced with the database. Created on the first sync. See `OpenAIAccount::sync()`
+    pub sync_filepath: PathBuf,
+    /// Progress of the current `OpenAIAccount::apply_prompt_to_dir()` run, so that it can resume if interrupted. Removed when the run completes.
+    pub checkpoint_filepath: PathBuf,
+    /// Items of the batch jobs submitted but not yet collected, so that a job can be collected from a later run. See `OpenAIAccount::submit_batch()`
+    pub batch_jobs_filepath: PathBuf,
+    /// Files uploaded to OpenAI from our documents, by content, so that none is uploaded twice. See `OpenAIAccount::upload_document()`
+    pub files_filepath: PathBuf,
+    /// Metadata, DOIs and reference lists of documents, by content hash, so that each version of a document is analyzed once. See `OpenAIAccount::analyze_document()`
+    pub document_records_filepath: PathBuf,
+    /// The text extracted from documents, by content hash, so that each version of a document is parsed once for all the prompts run over it. See `DocumentStore`
+    pub document_store_dir: PathBuf,
+    /// Time-to-live and size limits for the cache. Applied on startup, and after every new completion is cached.
+    pub eviction: EvictionPolicy,
+    /// Serve cached queries for prompts that differ only trivially from a cached one. Off when `None`. See `SemanticOpts`
+    pub semantic: Option<SemanticOpts>,
+    /// Spaces out requests to OpenAI to stay under the limit of the account. Unlimited when `None`.
+    pub rate_limit: Option<RateLimit>,
+    /// Ambient project, tags and run id for the queries of this client. See `OpenAIAccount::set_labels()`
+    pub labels: Labels,
+    /// Which pages of a PDF are sent, and how its text is cleaned up first. Every page, as extracted, by default.
+    pub pdf: PdfOpts,
 }
 
 impl Default for Opts {
@@ -57,8 +107,22 @@ impl Default for Opts {
     ///     model: GptModel::Gpt35Turbo,
     ///     temperature: 0.5,
     ///     database: false,
+    ///     pool: PoolOpts::default(),
+    ///     run_migrations: false,
     ///     bill_filepath: "./bill.json".into(),
-    ///     cache_filepath: "./cache.json".into() 
+    ///     cache_filepath: "./cache.json".into(),
+    ///     graveyard_filepath: "./graveyard.json".into(),
+    ///     sync_filepath: "./sync.json".into(),
+    ///     checkpoint_filepath: "./checkpoint.json".into(),
+    ///     batch_jobs_filepath: "./batch_jobs.json".into(),
+    ///     files_filepath: "./files.json".into(),
+    ///     document_records_filepath: "./document_records.json".into(),
+    ///     document_store_dir: "./document_store".into(),
+    ///     eviction: EvictionPolicy::default(),
+    ///     semantic: None,
+    ///     rate_limit: None,
+    ///     labels: Labels::default(),
+    ///     pdf: PdfOpts::default(),
     /// };
     /// ```
     fn default() -> Self {
@@ -66,8 +130,22 @@ impl Default for Opts {
             model: GptModel::Gpt35Turbo,
             temperature: 0.5,
             database: false,
+            pool: PoolOpts::default(),
+            run_migrations: false,
             bill_filepath: "./bill.json".into(),
-            cache_filepath: "./cache.json".into() 
+            cache_filepath: "./cache.json".into(),
+            graveyard_filepath: "./graveyard.json".into(),
+            sync_filepath: "./sync.json".into(),
+            checkpoint_filepath: "./checkpoint.json".into(),
+            batch_jobs_filepath: "./batch_jobs.json".into(),
+            files_filepath: "./files.json".into(),
+            document_records_filepath: "./document_records.json".into(),
+            document_store_dir: "./document_store".into(),
+            eviction: EvictionPolicy::default(),
+            semantic: None,
+            rate_limit: None,
+            labels: Labels::default(),
+            pdf: PdfOpts::default(),
         }
     }
 }
@@ -76,10 +154,21 @@ impl Default for Opts {
 impl Default for OpenAIAccount {
     fn default() -> OpenAIAccount {
         OpenAIAccount {
-            api_key: dotenvy::var("CHATGPT_API_KEY").unwrap().to_string(),
+            // Empty if unset, so that an account can be made without a key, such as to run a batch over another sender
+            api_key: dotenvy::var("CHATGPT_API_KEY").unwrap_or_default(),
+            rate_limiter: None,
             temperature: 0.0,
             db: DbMethods { conn: None },
-            cache: Cache { ..Default::default() },
+            cache: Cache::default(),
+            semantic: None,
+            sync_filepath: "./sync.json".into(),
+            checkpoint_filepath: "./checkpoint.json".into(),
+            batch_jobs_filepath: "./batch_jobs.json".into(),
+            files_filepath: "./files.json".into(),
+            document_records_filepath: "./document_records.json".into(),
+            document_store_dir: "./document_store".into(),
+            labels: Labels::default(),
+            pdf: PdfOpts::default(),
             bill: Bill { ..Default::default() },
             model: GptModel::Gpt35Turbo16k,
         }
@@ -91,11 +180,12 @@ impl OpenAIAccount {
 
         let bill_filepath = opts.bill_filepath;
         let cache_filepath = opts.cache_filepath;
+        let graveyard = Graveyard { filepath: opts.graveyard_filepath };
 
         let api_key = dotenvy::var("CHATGPT_API_KEY").expect("CHATGPT_API_KEY environment variable").to_string();
         
         let mut res = Ok(());
-        let db = DbMethods::try_init().await.map_err(|e| { res = Err(e) }).ok();
+        let db = DbMethods::try_init(&opts.pool, opts.run_migrations).await.map_err(|e| { res = Err(e) }).ok();
 
         match db {
             None => match opts.database {
@@ -125,15 +215,19 @@ impl OpenAIAccount {
         };
 
         // Read the cache into memory or else initialize empty
-        let cache: Cache = match fs::File::open(&cache_filepath) {
+        let mut cache: Cache = match fs::File::open(&cache_filepath) {
             Ok(f) => {
                 let reader = io::BufReader::new(f);
-                let entries: HashMap<String, Query> = serde_json::from_reader(reader).unwrap_or_else(|e| { 
-                    if let serde_json::error::Category::Eof = e.classify() { HashMap::new() } else { println!("🗳️ Initializing client with blank cache due to:  ❌  {e}"); HashMap::new() }  
+                let (entries, stamped) = read_entries(reader).unwrap_or_else(|e| { 
+                    if let serde_json::error::Category::Eof = e.classify() { (HashMap::new(), false) } else { println!("🗳️ Initializing client with blank cache due to:  ❌  {e}"); (HashMap::new(), false) }  
                 });
                 let cache = Cache {
                     entries,
-                    filepath: cache_filepath
+                    filepath: cache_filepath,
+                    policy: opts.eviction,
+                    graveyard,
+                    // So that the load time stamped on older entries is saved, and kept on later loads
+                    dirty: stamped,
                 };
                 println!("🗳️   Cache read from: {}", &cache.filepath.display());
                 cache
@@ -141,28 +235,48 @@ impl OpenAIAccount {
             Err(_) => { // HashMap<String, Query>
                 fs::File::create(&cache_filepath).expect(format!("Tried but failed to create a new cache file, after having not being able to open:  {}", cache_filepath.display()).as_str() );
                 let blank_cache = Cache {
+                    entries: HashMap::new(),
                     filepath: cache_filepath,
-                    ..Default::default()
+                    policy: opts.eviction,
+                    graveyard,
+                    dirty: false,
                 };
                 println!("🗳️   Empty Cache created at: {}", blank_cache.filepath.display());
                 blank_cache
             },
         };
 
-        let _graveyard = std::fs::OpenOptions::new().create(true).truncate(true).write(true).open("graveyard.json").expect("access to graveyard file");
-        println!("🪦  Graveyard backups cleared.");
+        println!("🪦  Graveyard kept at: {}", cache.graveyard.filepath.display());
+
+        // Anything that expired or no longer fits since the last run is cleared out before use
+        cache.evict();
+
+        let semantic = opts.semantic.map(|semantic_opts| {
+            let index = SemanticIndex::load(semantic_opts)?;
+            println!("🧭 Semantic cache enabled with {} prompt embeddings from: {}", index.embeddings.len(), index.opts.embeddings_filepath.display());
+            Ok(index)
+        }).transpose()?;
 
         println!("🌡️   Model initialized at temperature {}", opts.temperature);
         Ok(OpenAIAccount {
             bill,
             cache,
+            semantic,
             api_key,
+            rate_limiter: opts.rate_limit.map(|limit| Arc::new(RateLimiter::new(limit))),
             model: opts.model,
             temperature: opts.temperature,
             db: DbMethods {
                 conn: db
             },
-            ..Default::default()
+            sync_filepath: opts.sync_filepath,
+            checkpoint_filepath: opts.checkpoint_filepath,
+            batch_jobs_filepath: opts.batch_jobs_filepath,
+            files_filepath: opts.files_filepath,
+            document_records_filepath: opts.document_records_filepath,
+            document_store_dir: opts.document_store_dir,
+            labels: opts.labels,
+            pdf: opts.pdf,
         })
     }
 
@@ -171,6 +285,12 @@ impl OpenAIAccount {
         if self.temperature < temperature {println!("🌡️  Temperature raised to {temperature}")} else {println!("🌡️  Temperature lowered to {temperature}")}
         self.temperature = temperature; 
     }
+
+    /// Replaces the ambient labels stamped on new queries
+    pub fn set_labels(&mut self, labels: Labels) {
+        println!("🏷️  Labels set to {labels:?}");
+        self.labels = labels;
+    }
 }
 
 #[derive(Debug)]
@@ -180,5 +300,11 @@ pub enum Status {
     NotFoundError,
     OpenAIError,
     APIReachedLimit,
-    RetrievedUnexpectedQueryType
+    RetrievedUnexpectedQueryType,
+    /// A database method was called on a client started without a database
+    DatabaseNotConfigured,
+    /// A deletion was given a confirmation token that doesn't match what it would delete now. See `DbMethods::preview_deletion()`
+    ConfirmationMismatch,
+    /// A document whose format could not be told from its extension or content. See `DocumentFormat::detect()`
+    UnsupportedDocument(String),
 }
\ No newline at end of file
@@ -1,239 +1,177 @@
-use chrono::Utc;
-use std::fs;
-use sea_orm::{DatabaseConnection, Database, EntityTrait, QueryFilter, ColumnTrait, ActiveValue, Set};
+use sea_orm::{ActiveValue, ConnectOptions, DatabaseConnection, DatabaseTransaction, Database, EntityTrait, TransactionTrait, QueryFilter, ColumnTrait, Iterable, sea_query::OnConflict};
 use crate::{
     models::{
         db::{
-            chat_completions::ActiveModel as ActiveChatQueryModel, 
-            text_completions::ActiveModel as ActiveTextQueryModel,
-            meta_completions::ActiveModel as ActiveMetaQueryModel,
-            chat_completions::Model as ChatQueryModel, 
-            text_completions::Model as TextQueryModel,
-            meta_completions::Model as MetaQueryModel,
-            chat_completions::Column as ChatQueryColumn, 
-            text_completions::Column as TextQueryColumn,
-            meta_completions::Column as MetaQueryColumn,
-            prelude::{
-                ChatCompletions,
-                MetaCompletions,
-                TextCompletions
-            }
+            completions::{ActiveModel, Column},
+            prelude::Completions,
+            migration::{Migrator, MigratorTrait},
         }, 
-        hash::calculate_hash, cache::Cache, queries::chat_query::Cacheable,
+        hash::query_key_hash, cache::Cache, client::graveyard::Graveyard,
     }, 
     Query,
-    ChatQuery,
-    TextQuery,
-    MetaQuery
+    QueryKind,
 };
-use std::{error::Error, collections::HashMap};
+use std::{collections::HashMap, time::Duration};
+use chrono::{DateTime, SubsecRound, Utc};
 
-use super::core::Status;
+use super::{
+    core::Status,
+    db_delete::{move_to_trash, DeleteMode, DeletionReport},
+    db_search::DbSearch,
+};
 
 #[derive(Debug)]
 pub struct DbMethods {
-    pub(super) conn: Option<DatabaseConnection>
+    pub(crate) conn: Option<DatabaseConnection>
+}
+
+/// Connection pool settings. Every DB operation goes through the one pool opened at startup. Settings left as `None` use the driver's defaults.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct PoolOpts {
+    pub max_connections: Option<u32>,
+    pub min_connections: Option<u32>,
+    /// How long to wait for a new connection to be established
+    pub connect_timeout: Option<Duration>,
+    /// How long to wait for a free connection from the pool
+    pub acquire_timeout: Option<Duration>,
+    /// How long an unused connection is kept open
+    pub idle_timeout: Option<Duration>,
+}
+
+/// Rows per statement when saving the cache to the database
+pub const DEFAULT_UPSERT_CHUNK_SIZE: usize = 500;
+
+/// Outcome of saving the cache to the database, counted in cache entries
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct UpsertReport {
+    /// Entries not yet in the database
+    pub inserted: usize,
+    /// Entries whose stored row differed, in its response, labels, request or metadata, and was replaced
+    pub updated: usize,
+    /// Entries already stored as the same row
+    pub unchanged: usize,
+}
+
+/// What one upsert wrote, as returned by `upsert_entries_in()`
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Upserted {
+    pub report: UpsertReport,
+    /// Keys of the rows inserted or updated. Unchanged rows are left out.
+    pub written: Vec<String>,
+    /// The `timestamp` of every written row, to the microsecond so that it reads back the same on every backend
+    pub written_at: DateTime<Utc>,
+    /// The rows replaced, as (query_key, query), to be buried once the transaction commits. See `bury_replaced()`
+    pub replaced: Vec<(String, Query)>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MigrationStatus {
+    /// Name of the latest applied migration, or `None` on a database that was never migrated
+    pub version: Option<String>,
+    pub applied: Vec<String>,
+    pub pending: Vec<String>,
 }
 
 /// Methods for coordinating the current cache state and the DB
 impl DbMethods {
 
-    pub(super) async fn try_init() -> Result<DatabaseConnection, Status> {
+    /// Connects to `DATABASE_URL`, then applies any pending migrations if `run_migrations` is set
+    pub(super) async fn try_init(pool: &PoolOpts, run_migrations: bool) -> Result<DatabaseConnection, Status> {
         let key = dotenvy::var("DATABASE_URL").map_err(|e| {
             println!("🗄️  No 'DATABASE_URL' environment variable found. Starting without DB...");
             Status::Error(e.to_string())
         })?;
-        Database::connect(key).await.map_err(|e| {
+        Self::open(&key, pool, run_migrations).await
+    }
+
+    /// Connects to the database at `url` rather than `DATABASE_URL`, e.g. `sqlite://./openai_rs.db?mode=rwc` or `sqlite::memory:`.
+    /// <br> The backend for the URL's scheme must be enabled as a cargo feature: `postgres` (default) or `sqlite`.
+    pub async fn connect(url: &str, pool: &PoolOpts, run_migrations: bool) -> Result<DbMethods, Status> {
+        Ok(DbMethods { conn: Some(Self::open(url, pool, run_migrations).await?) })
+    }
+
+    async fn open(url: &str, pool: &PoolOpts, run_migrations: bool) -> Result<DatabaseConnection, Status> {
+        let mut options = ConnectOptions::new(url.to_string());
+        if let Some(n) = pool.max_connections { options.max_connections(n); }
+        if let Some(n) = pool.min_connections { options.min_connections(n); }
+        if let Some(d) = pool.connect_timeout { options.connect_timeout(d); }
+        if let Some(d) = pool.acquire_timeout { options.acquire_timeout(d); }
+        if let Some(d) = pool.idle_timeout { options.idle_timeout(d); }
+
+        let conn = Database::connect(options).await.map_err(|e| {
             println!("🗄️  Failed to connect to database! ❌");
             Status::Error(e.to_string())
-        })
+        })?;
+        if run_migrations {
+            Self::apply_migrations(&conn).await?;
+        }
+        Ok(conn)
     }
 
-    pub async fn insert_cache(&self, cache: &Cache) -> std::result::Result<(), Box<dyn Error>> {
-        println!("🗄️  Saving cache to database...");
-        
-        let mut overwritten = false;
-        let db: DatabaseConnection = Database::connect(dotenvy::var("DATABASE_URL")?).await?;
-
-        let mut chat_models: Vec<ActiveChatQueryModel> = vec![]; // We will build up lists and then do a single SQL insert for each
-        let mut text_models: Vec<ActiveTextQueryModel> = vec![];
-        let mut meta_models: Vec<ActiveMetaQueryModel> = vec![];
-
-        for (cache_key, query) in &cache.entries {
-            let query_key_hash = calculate_hash(cache_key);
-
-            match query {
-                Query::ChatQuery(query) => {
-                    let extant_at_id = ChatCompletions::find().filter(ChatQueryColumn::QueryKeyHash.eq(&query_key_hash)).one(&db).await.expect("Database check for query");
-
-                    if let Some(model) = extant_at_id {
-                        ChatCompletions::delete_by_id(model.rid).exec(&db).await.expect("success of deletion by id during insert_cache()");
-                        println!("🗄️  Model overwritten at query key hash: {query_key_hash}"); 
-                        overwritten = true;
-                        let graveyard = std::fs::OpenOptions::new().create(true).append(true).open("graveyard.json").expect("access to graveyard file");
-                        serde_json::to_writer_pretty(graveyard, &model).expect("Serialization of an overwritten model to the graveyard");
-                    }
-                    let model = ActiveChatQueryModel { 
-                        timestamp: ActiveValue::Set(Utc::now().naive_local()), 
-                        model: ActiveValue::Set(query.model.to_string()), 
-                        temperature: ActiveValue::Set(query.temperature as f64), 
-                        prompt: ActiveValue::Set(query.prompt.to_string()),
-                        query_key: ActiveValue::Set(cache_key.to_string()), 
-                        prompt_tokens: ActiveValue::Set(query.response.usage.prompt_tokens), 
-                        completion_tokens: ActiveValue::Set(query.response.usage.completion_tokens), 
-                        total_tokens: ActiveValue::Set(query.response.usage.total_tokens), 
-                        process_time: ActiveValue::Set(query.process_time as i32), 
-                        response: ActiveValue::Set(serde_json::to_value(query.response.clone()).expect("conversion to JSON value of query.response")), 
-                        cost: ActiveValue::Set(query.cost as f64),
-                        query_key_hash: ActiveValue::Set(query_key_hash), 
-                        rid: ActiveValue::NotSet
-                    };
-
-                    chat_models.push(model)
-                },
-                Query::TextQuery(query) => {
-                    let extant_at_id = TextCompletions::find().filter(TextQueryColumn::QueryKeyHash.eq(&query_key_hash)).one(&db).await.expect("Database check for query");
-
-                    if let Some(model) = extant_at_id {
-                        TextCompletions::delete_by_id(model.rid).exec(&db).await.expect("success of deletion by id during insert_cache()");
-                        println!("🗄️  Model overwritten at query key hash: {query_key_hash}"); 
-                        overwritten = true;
-                        let graveyard = std::fs::OpenOptions::new().create(true).append(true).open("graveyard.json").expect("access to graveyard file");
-                        serde_json::to_writer_pretty(graveyard, &model).expect("Serialization of an overwritten model to the graveyard");
-                    }
-                    let model = ActiveTextQueryModel { 
-                        timestamp: ActiveValue::Set(Utc::now().naive_local()), 
-                        model: ActiveValue::Set(query.model.to_string()), 
-                        temperature: ActiveValue::Set(query.temperature as f64), 
-                        prompt: ActiveValue::Set(query.prompt.to_string()),
-                        document_title: ActiveValue::Set(query.document_title.to_string()),
-                        query_key: ActiveValue::Set(cache_key.to_string()), 
-                        prompt_tokens: ActiveValue::Set(query.response.usage.prompt_tokens), 
-                        completion_tokens: ActiveValue::Set(query.response.usage.completion_tokens), 
-                        total_tokens: ActiveValue::Set(query.response.usage.total_tokens), 
-                        process_time: ActiveValue::Set(query.process_time as i32), 
-                        response: ActiveValue::Set(serde_json::to_value(query.response.clone()).expect("conversion to JSON value of query.response")), 
-                        cost: ActiveValue::Set(query.cost as f64),
-                        query_key_hash: ActiveValue::Set(query_key_hash), 
-                        rid: ActiveValue::NotSet
-                    };
-                    text_models.push(model)
-                },
-                Query::MetaQuery(query) => {
-                    let extant_at_id = MetaCompletions::find().filter(MetaQueryColumn::QueryKeyHash.eq(&query_key_hash)).one(&db).await.expect("Database check for query");
-
-                    if let Some(model) = extant_at_id {
-                        MetaCompletions::delete_by_id(model.rid).exec(&db).await.expect("success of deletion by id during insert_cache()");
-                        println!("🗄️  Model overwritten at query key hash: {query_key_hash}"); 
-                        overwritten = true;
-                        let graveyard = std::fs::OpenOptions::new().create(true).append(true).open("graveyard.json").expect("access to graveyard file");
-                        serde_json::to_writer_pretty(graveyard, &model).expect("Serialization of an overwritten model to the graveyard");
-                    }
-                    let model = ActiveMetaQueryModel { 
-                        timestamp: ActiveValue::Set(Utc::now().naive_local()), 
-                        model: ActiveValue::Set(query.model.to_string()), 
-                        temperature: ActiveValue::Set(query.temperature as f64), 
-                        prompt: ActiveValue::Set(query.prompt.to_string()),
-                        query_key: ActiveValue::Set(cache_key.to_string()), 
-                        prompt_tokens: ActiveValue::Set(query.response.usage.prompt_tokens), 
-                        completion_tokens: ActiveValue::Set(query.response.usage.completion_tokens), 
-                        total_tokens: ActiveValue::Set(query.response.usage.total_tokens), 
-                        process_time: ActiveValue::Set(query.process_time as i32), 
-                        response: ActiveValue::Set(serde_json::to_value(query.response.clone()).expect("conversion to JSON value of query.response")), 
-                        cost: ActiveValue::Set(query.cost as f64),
-                        query_key_hash: ActiveValue::Set(query_key_hash), 
-                        rid: ActiveValue::NotSet
-                    };
-
-                    meta_models.push(model)
-                },
-            }
-            
+    async fn apply_migrations(conn: &DatabaseConnection) -> Result<(), Status> {
+        let pending = Migrator::get_pending_migrations(conn).await.map_err(|e| Status::Error(e.to_string()))?;
+        if pending.is_empty() {
+            println!("🗄️  Database schema is up to date.");
+            return Ok(())
         }
-        let _chat_res = ChatCompletions::insert_many(chat_models).exec(&db).await?;
-        let _text_res = TextCompletions::insert_many(text_models).exec(&db).await?;
-        let _meta_res = MetaCompletions::insert_many(meta_models).exec(&db).await?;
-
-        if overwritten {println!("🪦  Any overwritten models can be recovered in graveyard file.")};
-        println!("🗄️  Cache saved to database.");
+        println!("🗄️  Applying {} database migrations...", pending.len());
+        Migrator::up(conn, None).await.map_err(|e| Status::Error(e.to_string()))?;
+        println!("🗄️  Database migrated.");
         Ok(())
     }
 
+    /// The pool opened at startup, or `Status::DatabaseNotConfigured` if the client was started without a database
+    pub(crate) fn conn(&self) -> Result<&DatabaseConnection, Status> {
+        self.conn.as_ref().ok_or(Status::DatabaseNotConfigured)
+    }
+
+    /// Applies any pending migrations of the bundled schema. See `models::db::migration`
+    pub async fn migrate(&self) -> Result<(), Status> {
+        let conn = self.conn()?;
+        Self::apply_migrations(conn).await
+    }
+
+    /// Which of the bundled migrations have been applied to the database, and which are pending
+    pub async fn migration_status(&self) -> Result<MigrationStatus, Status> {
+        let conn = self.conn()?;
+        let applied: Vec<String> = Migrator::get_applied_migrations(conn).await.map_err(|e| Status::Error(e.to_string()))?
+            .iter().map(|m| m.name().to_string()).collect();
+        let pending: Vec<String> = Migrator::get_pending_migrations(conn).await.map_err(|e| Status::Error(e.to_string()))?
+            .iter().map(|m| m.name().to_string()).collect();
+        Ok(MigrationStatus { version: applied.last().cloned(), applied, pending })
+    }
+
+    /// Upserts every cache entry into the database in one transaction, in chunks of `DEFAULT_UPSERT_CHUNK_SIZE`. See `insert_cache_in_chunks()`
+    pub async fn insert_cache(&self, cache: &Cache) -> Result<UpsertReport, Status> {
+        self.insert_cache_in_chunks(cache, DEFAULT_UPSERT_CHUNK_SIZE).await
+    }
+
+    /// Upserts every cache entry into the database on its `query_key_hash`, `chunk_size` rows per statement, all in one transaction.
+    /// <br> Rows whose response already matches the cache are left as is. Rows that are replaced are buried in the cache's graveyard once the transaction commits.
+    pub async fn insert_cache_in_chunks(&self, cache: &Cache, chunk_size: usize) -> Result<UpsertReport, Status> {
+        println!("🗄️  Saving cache to database...");
+        let report = self.upsert_entries(cache.entries.iter().collect(), &cache.graveyard, chunk_size).await?.report;
+        println!("🗄️  Cache saved to database. Inserted: {}, updated: {}, unchanged: {}", report.inserted, report.updated, report.unchanged);
+        Ok(report)
+    }
+
+    /// The upsert behind `insert_cache_in_chunks()`, for any set of cache entries
+    pub(crate) async fn upsert_entries(&self, entries: Vec<(&String, &Query)>, graveyard: &Graveyard, chunk_size: usize) -> Result<Upserted, Status> {
+        let txn = self.conn()?.begin().await.map_err(|e| Status::Error(e.to_string()))?;
+        let upserted = upsert_entries_in(&txn, entries, chunk_size).await?;
+        txn.commit().await.map_err(|e| Status::Error(e.to_string()))?;
+        bury_replaced(graveyard, &upserted.replaced);
+        Ok(upserted)
+    }
+
     /// Provide the `cache_key` of an cached query, to insert that query into the DB. (The cached entry is not modified)
     pub async fn insert_query(&self, cache_key: String, cache: &Cache) -> Result<i32, Status> {
-        
         let query = match cache.entries.get(&cache_key) {Some(s) => s, None => return Err(Status::NotFoundError)};
-        
-        match query {
-            Query::ChatQuery(query) => {
-                let model = ActiveChatQueryModel { 
-                    timestamp: ActiveValue::Set(Utc::now().naive_local()), 
-                    model: ActiveValue::Set(query.model.to_string()), 
-                    temperature: ActiveValue::Set(query.temperature as f64), 
-                    prompt: ActiveValue::Set(query.prompt.to_string()),
-                    query_key: ActiveValue::Set(cache_key.to_string()), 
-                    prompt_tokens: ActiveValue::Set(query.response.usage.prompt_tokens), 
-                    completion_tokens: ActiveValue::Set(query.response.usage.completion_tokens), 
-                    total_tokens: ActiveValue::Set(query.response.usage.total_tokens), 
-                    process_time: ActiveValue::Set(query.process_time as i32), 
-                    response: ActiveValue::Set(serde_json::to_value(query.response.clone()).expect("conversion to JSON value of query.response")), 
-                    cost: ActiveValue::Set(query.cost as f64),
-                    query_key_hash: ActiveValue::Set(calculate_hash(&cache_key)), 
-                    rid: ActiveValue::NotSet
-                };
-        
-                let res = ChatCompletions::insert(model).exec(self.conn.as_ref().unwrap()).await.expect("insertion of ActiveModel to db during .insert_query()");
-        
-                println!("🗄️  Inserted into database query \"{key}\"", key = cache_key);
-                Ok(res.last_insert_id)
-            },
-            Query::TextQuery(query) => {
-                let model = ActiveTextQueryModel { 
-                    timestamp: ActiveValue::Set(Utc::now().naive_local()), 
-                    model: ActiveValue::Set(query.model.to_string()), 
-                    temperature: ActiveValue::Set(query.temperature as f64), 
-                    prompt: ActiveValue::Set(query.prompt.to_string()),
-                    document_title: ActiveValue::Set(query.document_title.to_string()),
-                    query_key: ActiveValue::Set(cache_key.to_string()), 
-                    prompt_tokens: ActiveValue::Set(query.response.usage.prompt_tokens), 
-                    completion_tokens: ActiveValue::Set(query.response.usage.completion_tokens), 
-                    total_tokens: ActiveValue::Set(query.response.usage.total_tokens), 
-                    process_time: ActiveValue::Set(query.process_time as i32), 
-                    response: ActiveValue::Set(serde_json::to_value(query.response.clone()).expect("conversion to JSON value of query.response")), 
-                    cost: ActiveValue::Set(query.cost as f64),
-                    query_key_hash: ActiveValue::Set(calculate_hash(&cache_key)), 
-                    rid: ActiveValue::NotSet
-                };
-        
-                let res = TextCompletions::insert(model).exec(self.conn.as_ref().unwrap()).await.expect("insertion of ActiveModel to db during .insert_query()");
-        
-                println!("🗄️  Inserted into database query \"{key}\"", key = cache_key);
-                Ok(res.last_insert_id)
-            },
-            Query::MetaQuery(query) => {
-                let model = ActiveMetaQueryModel { 
-                    timestamp: ActiveValue::Set(Utc::now().naive_local()), 
-                    model: ActiveValue::Set(query.model.to_string()), 
-                    temperature: ActiveValue::Set(query.temperature as f64), 
-                    prompt: ActiveValue::Set(query.prompt.to_string()),
-                    query_key: ActiveValue::Set(cache_key.to_string()), 
-                    prompt_tokens: ActiveValue::Set(query.response.usage.prompt_tokens), 
-                    completion_tokens: ActiveValue::Set(query.response.usage.completion_tokens), 
-                    total_tokens: ActiveValue::Set(query.response.usage.total_tokens), 
-                    process_time: ActiveValue::Set(query.process_time as i32), 
-                    response: ActiveValue::Set(serde_json::to_value(query.response.clone()).expect("conversion to JSON value of query.response")), 
-                    cost: ActiveValue::Set(query.cost as f64),
-                    query_key_hash: ActiveValue::Set(calculate_hash(&cache_key)), 
-                    rid: ActiveValue::NotSet
-                };
-
-                let res = MetaCompletions::insert(model).exec(self.conn.as_ref().unwrap()).await.expect("insertion of ActiveModel to db during .insert_query()");
-
-                println!("🗄️  Inserted into database query \"{key}\"", key = cache_key);
-                Ok(res.last_insert_id)
-            },
-        }
-        
+
+        let res = Completions::insert(ActiveModel::from_query(&cache_key, query)).exec(self.conn()?).await.map_err(|e| Status::Error(e.to_string()))?;
+
+        println!("🗄️  Inserted into database query \"{key}\"", key = cache_key);
+        Ok(res.last_insert_id)
     }
 
 
@@ -241,127 +179,119 @@ impl DbMethods {
     /// then overwrite the cache file with the new state of the cache.  Returns the previous state of the cache, before db addition.
     /// 
     /// Pass in your `OpenAIAccount`'s cache
-    pub async fn read_all_to_cache(&mut self, cache: &mut Cache, overwrite: bool) -> Result<HashMap<String,Query> , Box<dyn Error> > {
+    pub async fn read_all_to_cache(&mut self, cache: &mut Cache, overwrite: bool) -> Result<HashMap<String,Query>, Status> {
         println!("🗄️  Reading database into cache...");
-        let db: DatabaseConnection = Database::connect(dotenvy::var("DATABASE_URL")?).await?;
         let previous_state = cache.entries.clone();
-        
-        let text_models = TextCompletions::find().all(&db).await?;
-        let chat_models = ChatCompletions::find().all(&db).await?;
-        let meta_models = MetaCompletions::find().all(&db).await?;
-
-        let text_queries: Vec<Query> = text_models.iter().cloned().map(|m| { Query::TextQuery( m.to_query() ) }).collect();
-        let chat_queries: Vec<Query> = chat_models.iter().cloned().map(|m| { Query::ChatQuery( m.to_query() ) }).collect();
-        let meta_queries: Vec<Query> = meta_models.iter().cloned().map(|m| { Query::MetaQuery( m.to_query() ) }).collect();
 
-        let mut queries: Vec<&Query> = vec![];
-        queries.reserve(text_queries.len() + chat_queries.len() + meta_queries.len());
-
-        queries.extend(text_queries.iter());
-        queries.extend(chat_queries.iter());
-        queries.extend(meta_queries.iter());
-
-        cache.insert_many(queries, overwrite);
-
-        // let cache_file = match fs::OpenOptions::new().create(true).write(true).open(&cache.filepath) {Ok(f)=>f, Err(e)=>panic!("Could not cache query at {}, due to error:  ❌  {}", cache.filepath.display(), e)};
-        // serde_json::to_writer_pretty(&cache_file, &cache).expect("Serialization of cache to cache file during read_to_cache");
+        let queries: Vec<Query> = self.read_all().await?.into_values().collect();
+        cache.insert_many(queries.iter().collect(), overwrite);
 
         println!("🗄️  Database added to cache.");
         Ok(previous_state)
     }
 
-    pub async fn get_text_query(&self, cache_key: String) -> Option<TextQuery> {
-        let model = TextCompletions::find().filter(TextQueryColumn::QueryKey.eq(cache_key)).one(self.conn.as_ref().unwrap()).await.expect("Database .find() call response success");
-        model.map(|q| q.to_query())
-    }
-    pub async fn get_chat_query(&self, cache_key: String) -> Option<ChatQuery> {
-        let model = ChatCompletions::find().filter(ChatQueryColumn::QueryKey.eq(cache_key)).one(self.conn.as_ref().unwrap()).await.expect("Database .find() call response success");
-        model.map(|q| q.to_query())
-    }
-    pub async fn get_meta_query(&self, cache_key: String) -> Option<MetaQuery> {
-        let model = MetaCompletions::find().filter(MetaQueryColumn::QueryKey.eq(cache_key)).one(self.conn.as_ref().unwrap()).await.expect("Database .find() call response success");
-        model.map(|q| q.to_query())
+    /// The stored query at `cache_key`, of whichever kind
+    pub async fn get_query(&self, cache_key: &str) -> Result<Option<Query>, Status> {
+        let model = Completions::find().filter(Column::QueryKey.eq(cache_key)).one(self.conn()?).await.map_err(|e| Status::Error(e.to_string()))?;
+        model.map(|m| m.to_query()).transpose()
     }
 
-    pub async fn delete_text_query_by_id(&self, id: i32) -> Result<(), Box<dyn Error>> {
-        println!("🗄️  Deleting text query by id: {id}");
-        let _res = TextCompletions::delete( ActiveTextQueryModel { rid: Set(id), ..Default::default() } )
-            .exec(self.conn.as_ref().unwrap())
-            .await?;
-        Ok(())
-    }
-    pub async fn delete_chat_query_by_id(&self, id: i32) -> Result<(), Box<dyn Error>> {
-        println!("🗄️  Deleting text query by id: {id}");
-        let _res = ChatCompletions::delete( ActiveChatQueryModel { rid: Set(id), ..Default::default() } )
-            .exec(self.conn.as_ref().unwrap())
-            .await?;
-        Ok(())
-    }
-    pub async fn delete_meta_query_by_id(&self, id: i32) -> Result<(), Box<dyn Error>> {
-        println!("🗄️  Deleting text query by id: {id}");
-        let _res = MetaCompletions::delete( ActiveMetaQueryModel { rid: Set(id), ..Default::default() } )
-            .exec(self.conn.as_ref().unwrap())
-            .await?;
-        Ok(())
+    /// Every stored query of `kind`, by cache key
+    pub async fn get_queries(&self, kind: QueryKind) -> Result<HashMap<String, Query>, Status> {
+        let models = Completions::find().filter(Column::Kind.eq(kind.as_str())).all(self.conn()?).await.map_err(|e| Status::Error(e.to_string()))?;
+        models.into_iter().map(|m| Ok((m.query_key.clone(), m.to_query()?))).collect()
     }
 
-    pub async fn delete_all(&self) -> Result<(), Box<dyn Error>> {
-        println!("🗄️  Delete database requested...");
-        
-        let mut line = String::new();
-        println!("🗄️  Press Enter to continue...");
-        let _input = std::io::stdin().read_line(&mut line).expect("Failed to read line");
-
-        let _res = ChatCompletions::delete_many().exec(self.conn.as_ref().unwrap()).await?;
-        let _res = TextCompletions::delete_many().exec(self.conn.as_ref().unwrap()).await?;
-        let _res = MetaCompletions::delete_many().exec(self.conn.as_ref().unwrap()).await?;
-
-        println!("🗄️  Database cleared.\n");
-        Ok(())
+    /// Moves the stored query with row id `id` to the trash. See `DbMethods::trash()`
+    pub async fn delete_query_by_id(&self, id: i32) -> Result<(), Status> {
+        println!("🗄️  Trashing query by id: {id}");
+        let txn = self.conn()?.begin().await.map_err(|e| Status::Error(e.to_string()))?;
+        let model = Completions::find_by_id(id).one(&txn).await.map_err(|e| Status::Error(e.to_string()))?.ok_or(Status::NotFoundError)?;
+        move_to_trash(&txn, &[model]).await?;
+        Completions::delete_by_id(id).exec(&txn).await.map_err(|e| Status::Error(e.to_string()))?;
+        txn.commit().await.map_err(|e| Status::Error(e.to_string()))
     }
 
-    /// This will NOT ask for confirmation.
-    pub async fn delete_chat_queries(&self) -> Result<(), Box<dyn Error>> {
-        println!("🗄️  Delete chat queries requested...");
-        let _res = ChatCompletions::delete_many().exec(self.conn.as_ref().unwrap()).await?;
-        println!("🗄️  Chat queries cleared.\n");
-        Ok(())
-    }
-    /// This will NOT ask for confirmation.
-    pub async fn delete_text_queries(&self) -> Result<(), Box<dyn Error>> {
-        println!("🗄️  Delete text queries requested...");
-        let _res = TextCompletions::delete_many().exec(self.conn.as_ref().unwrap()).await?;
-        println!("🗄️  Text queries cleared.\n");
-        Ok(())
+    /// Deletes every stored query of `kind`, given the token of `preview_deletion()` of `DbSearch::new().kind(kind)` in `mode`. See `DbMethods::delete()`
+    pub async fn delete_queries(&self, kind: QueryKind, mode: DeleteMode, token: &str, graveyard: &Graveyard) -> Result<DeletionReport, Status> {
+        println!("🗄️  Delete {} queries requested...", kind.as_str());
+        self.delete(&DbSearch::new().kind(kind), mode, token, graveyard).await
     }
-    /// This will NOT ask for confirmation.
-    pub async fn delete_meta_queries(&self) -> Result<(), Box<dyn Error>> {
-        println!("🗄️  Delete meta queries requested...");
-        let _res = MetaCompletions::delete_many().exec(self.conn.as_ref().unwrap()).await?;
-        println!("🗄️  Meta queries cleared.\n");
-        Ok(())
+
+    /// Deletes every stored query, given the token of `preview_deletion()` of `DbSearch::new()` in `mode`. See `DbMethods::delete()`
+    pub async fn delete_all(&self, mode: DeleteMode, token: &str, graveyard: &Graveyard) -> Result<DeletionReport, Status> {
+        println!("🗄️  Delete database requested...");
+        self.delete(&DbSearch::new(), mode, token, graveyard).await
     }
 
     /// Returns a fresh read of the database
-    pub async fn read_all(&self) -> Result< HashMap<String,Query> , Box<dyn Error> > {
+    pub async fn read_all(&self) -> Result<HashMap<String,Query>, Status> {
         println!("🗄️  Reading all from database...");
+        let models = Completions::find().all(self.conn()?).await.map_err(|e| Status::Error(e.to_string()))?;
+        models.into_iter().map(|m| Ok((m.query_key.clone(), m.to_query()?))).collect()
+    }
 
-        let mut cache: HashMap<String, Query> = HashMap::new();
-        let chat_models = ChatCompletions::find().all(self.conn.as_ref().unwrap()).await?;
-        let text_models = TextCompletions::find().all(self.conn.as_ref().unwrap()).await?;
-        let meta_models = MetaCompletions::find().all(self.conn.as_ref().unwrap()).await?;
-        
-        for model in chat_models {
-            cache.extend([ ( model.query_key.clone(), Query::ChatQuery( model.to_query() ))])
-        }
-        for model in text_models {
-            cache.extend([ ( model.query_key.clone(), Query::TextQuery( model.to_query() ))])
+}
+
+/// Upserts `entries` within `txn`, all written with the same `timestamp`, and returns what it did. See `Upserted`
+pub(crate) async fn upsert_entries_in(txn: &DatabaseTransaction, entries: Vec<(&String, &Query)>, chunk_size: usize) -> Result<Upserted, Status> {
+    let chunk_size = chunk_size.max(1);
+    let written_at = Utc::now().trunc_subsecs(6);
+
+    // What is stored under the keys being saved, as (query_key, query) by query_key_hash
+    let hashes: Vec<String> = entries.iter().map(|(key, _)| query_key_hash(key)).collect();
+    let mut stored: HashMap<String, (String, Query)> = HashMap::new();
+    for chunk in hashes.chunks(chunk_size) {
+        for model in Completions::find().filter(Column::QueryKeyHash.is_in(chunk.to_vec())).all(txn).await.map_err(|e| Status::Error(e.to_string()))? {
+            stored.insert(model.query_key_hash.clone(), (model.query_key.clone(), model.to_query()?));
         }
-        for model in meta_models {
-            cache.extend([ ( model.query_key.clone(), Query::MetaQuery( model.to_query() ))])
+    }
+
+    let mut report = UpsertReport::default();
+    let mut written: Vec<String> = vec![];
+    let mut replaced: Vec<(String, Query)> = vec![];
+    let mut models: Vec<ActiveModel> = vec![];
+
+    for (cache_key, query) in entries {
+        match stored.remove(&query_key_hash(cache_key)) {
+            Some((_, old)) if same_row(cache_key, &old, query) => { report.unchanged += 1; continue },
+            Some(old) => { report.updated += 1; replaced.push(old) },
+            None => report.inserted += 1,
         }
+        written.push(cache_key.clone());
+        models.push(ActiveModel { timestamp: ActiveValue::Set(written_at.naive_utc()), ..ActiveModel::from_query(cache_key, query) });
+    }
+
+    upsert_in_chunks(txn, models, chunk_size).await?;
+    Ok(Upserted { report, written, written_at, replaced })
+}
+
+/// Whether `old` and `new` would be stored as the same row, labels, request and metadata included, whenever they were saved
+pub(crate) fn same_row(cache_key: &str, old: &Query, new: &Query) -> bool {
+    let row = |query| ActiveModel { timestamp: ActiveValue::NotSet, ..ActiveModel::from_query(cache_key, query) };
+    row(old) == row(new)
+}
 
-        Ok(cache)
+/// Buries the rows an upsert replaced, as returned by `upsert_entries_in()`
+pub(crate) fn bury_replaced(graveyard: &Graveyard, replaced: &[(String, Query)]) {
+    for (query_key, old) in replaced {
+        graveyard.bury(query_key, old);
     }
+    if !replaced.is_empty() {println!("🪦  Any overwritten models can be recovered in graveyard file.")};
+}
 
-}
\ No newline at end of file
+/// `INSERT ... ON CONFLICT (query_key_hash) DO UPDATE` of `models`, `chunk_size` rows per statement. Every column but `rid` is overwritten on conflict.
+async fn upsert_in_chunks(txn: &DatabaseTransaction, models: Vec<ActiveModel>, chunk_size: usize) -> Result<(), Status> {
+    let on_conflict = OnConflict::column(Column::QueryKeyHash)
+        .update_columns(Column::iter().filter(|c| !matches!(c, Column::Rid | Column::QueryKeyHash)))
+        .to_owned();
+
+    for chunk in models.chunks(chunk_size) {
+        Completions::insert_many(chunk.to_vec())
+            .on_conflict(on_conflict.clone())
+            .exec_without_returning(txn)
+            .await
+            .map_err(|e| Status::Error(e.to_string()))?;
+    }
+    Ok(())
+}
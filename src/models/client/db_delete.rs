@@ -0,0 +1,197 @@
+use chrono::{DateTime, Utc};
+use sea_orm::{ActiveValue, ColumnTrait, ConnectionTrait, EntityTrait, QueryFilter, QueryOrder, QuerySelect, TransactionTrait};
+
+use crate::{
+    models::{
+        db::{completions, completions_trash, prelude::{Completions, CompletionsTrash}},
+        hash::calculate_hash,
+    },
+    Query,
+};
+use super::{
+    core::Status,
+    database::{bury_replaced, upsert_entries_in, DbMethods, UpsertReport, DEFAULT_UPSERT_CHUNK_SIZE},
+    db_search::DbSearch,
+    graveyard::Graveyard,
+};
+
+/// Where deleted rows go. Either way they are kept somewhere they can be restored from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeleteMode {
+    /// Moved to the `completions_trash` table. See `DbMethods::trash()` and `DbMethods::restore_from_trash()`
+    Trash,
+    /// Removed from the database, and buried in the graveyard. See `Cache::restore()`
+    Purge,
+}
+
+/// What a deletion would remove, without removing anything. Pass `token` to `DbMethods::delete()` to carry it out.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeletionPreview {
+    pub mode: DeleteMode,
+    /// Number of rows the filter selects
+    pub count: u64,
+    /// Only valid for the same filter, mode and rows. Any row added to or removed from the selection since the preview makes it stale.
+    pub token: String,
+}
+
+/// What a deletion removed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeletionReport {
+    pub mode: DeleteMode,
+    /// Cache keys of the deleted rows
+    pub deleted: Vec<String>,
+}
+
+/// A soft-deleted query, as held in the trash
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrashedQuery {
+    /// Id in the trash, as taken by `DbMethods::restore_from_trash()`
+    pub id: i32,
+    pub key: String,
+    pub deleted_at: DateTime<Utc>,
+    pub query: Query,
+}
+
+/// Confirmation token for deleting the rows `rids` in `mode`
+fn confirmation_token(mode: DeleteMode, rids: &[i32]) -> String {
+    calculate_hash(&format!("{mode:?}:{rids:?}"))
+}
+
+impl DbMethods {
+    /// Counts what `delete()` would remove for `search` in `mode`, and the token to confirm it with. Nothing is deleted. Sorting in `search` is ignored.
+    pub async fn preview_deletion(&self, search: &DbSearch, mode: DeleteMode) -> Result<DeletionPreview, Status> {
+        let rids = selected_rids(self.conn()?, search).await?;
+        let preview = DeletionPreview { mode, count: rids.len() as u64, token: confirmation_token(mode, &rids) };
+        println!("🗄️  Deletion would {} {} queries", match mode { DeleteMode::Trash => "trash", DeleteMode::Purge => "purge" }, preview.count);
+        Ok(preview)
+    }
+
+    /// Deletes the rows matching `search`, if `token` is that of a `preview_deletion()` of the same search and mode, and the rows it selects have not changed since.
+    /// <br> Else nothing is deleted, and `Status::ConfirmationMismatch` is returned. Trashing or purging happens in one transaction, and purged rows are buried in `graveyard` before it commits.
+    pub async fn delete(&self, search: &DbSearch, mode: DeleteMode, token: &str, graveyard: &Graveyard) -> Result<DeletionReport, Status> {
+        let txn = self.conn()?.begin().await.map_err(|e| Status::Error(e.to_string()))?;
+
+        let models = search.select().all(&txn).await.map_err(|e| Status::Error(e.to_string()))?;
+        let rids: Vec<i32> = models.iter().map(|model| model.rid).collect();
+        if confirmation_token(mode, &rids) != token {
+            return Err(Status::ConfirmationMismatch)
+        }
+        println!("🗄️  Deleting {} queries...", models.len());
+
+        // Every purged row is converted before anything is deleted, so that one that can't be read back fails the purge rather than being lost
+        let deleted: Vec<String> = models.iter().map(|model| model.query_key.clone()).collect();
+        let purged: Vec<(String, Query)> = match mode {
+            DeleteMode::Trash => vec![],
+            DeleteMode::Purge => models.iter().map(|model| Ok((model.query_key.clone(), model.clone().to_query()?))).collect::<Result<_, Status>>()?,
+        };
+
+        if mode == DeleteMode::Trash {
+            move_to_trash(&txn, &models).await?;
+        }
+        for chunk in rids.chunks(DEFAULT_UPSERT_CHUNK_SIZE) {
+            Completions::delete_many().filter(completions::Column::Rid.is_in(chunk.to_vec())).exec(&txn).await.map_err(|e| Status::Error(e.to_string()))?;
+        }
+        // Buried before the commit, so that a committed purge never leaves a row unarchived
+        for (key, query) in &purged {
+            graveyard.bury(key, query);
+        }
+        txn.commit().await.map_err(|e| Status::Error(e.to_string()))?;
+        if !purged.is_empty() {println!("🪦  Purged queries can be recovered in graveyard file.")};
+        println!("🗄️  {} queries deleted.\n", deleted.len());
+        Ok(DeletionReport { mode, deleted })
+    }
+
+    /// Every trashed query, oldest deletion first
+    pub async fn trash(&self) -> Result<Vec<TrashedQuery>, Status> {
+        let rows = CompletionsTrash::find().all(self.conn()?).await.map_err(|e| Status::Error(e.to_string()))?;
+        let mut trashed = vec![];
+        for row in rows {
+            let model: completions::Model = serde_json::from_value(row.row).map_err(|e| Status::Error(e.to_string()))?;
+            trashed.push(TrashedQuery { id: row.rid, key: row.query_key, deleted_at: row.deleted_at.and_utc(), query: model.to_query()? });
+        }
+        trashed.sort_by_key(|trashed| (trashed.deleted_at, trashed.id));
+        Ok(trashed)
+    }
+
+    /// Moves the trashed queries `ids` back. A query saved under the same key since it was trashed is replaced, and buried in `graveyard`.
+    pub async fn restore_from_trash(&self, ids: &[i32], graveyard: &Graveyard) -> Result<UpsertReport, Status> {
+        // The restored rows leave the trash in the same transaction that puts them back, so that a failure leaves both as they were
+        let txn = self.conn()?.begin().await.map_err(|e| Status::Error(e.to_string()))?;
+        let rows = CompletionsTrash::find().filter(completions_trash::Column::Rid.is_in(ids.to_vec())).order_by_asc(completions_trash::Column::Rid).all(&txn).await.map_err(|e| Status::Error(e.to_string()))?;
+
+        // Should a key be trashed more than once, its latest deletion wins
+        let mut restored: Vec<(String, Query)> = vec![];
+        for row in rows.iter() {
+            let model: completions::Model = serde_json::from_value(row.row.clone()).map_err(|e| Status::Error(e.to_string()))?;
+            restored.retain(|(key, _)| *key != row.query_key);
+            restored.push((row.query_key.clone(), model.to_query()?));
+        }
+        let upserted = upsert_entries_in(&txn, restored.iter().map(|(key, query)| (key, query)).collect(), DEFAULT_UPSERT_CHUNK_SIZE).await?;
+
+        let restored_ids: Vec<i32> = rows.iter().map(|row| row.rid).collect();
+        CompletionsTrash::delete_many().filter(completions_trash::Column::Rid.is_in(restored_ids)).exec(&txn).await.map_err(|e| Status::Error(e.to_string()))?;
+        txn.commit().await.map_err(|e| Status::Error(e.to_string()))?;
+
+        bury_replaced(graveyard, &upserted.replaced);
+        println!("🗄️  {} queries restored from trash.", restored.len());
+        Ok(upserted.report)
+    }
+
+    /// Purges trashed queries deleted before `before`, or all of them if `None`, burying them in `graveyard`. Returns how many were purged.
+    pub async fn empty_trash(&self, before: Option<DateTime<Utc>>, graveyard: &Graveyard) -> Result<usize, Status> {
+        let conn = self.conn()?;
+        let mut find = CompletionsTrash::find();
+        if let Some(before) = before {
+            find = find.filter(completions_trash::Column::DeletedAt.lt(before.naive_utc()));
+        }
+        let rows = find.all(conn).await.map_err(|e| Status::Error(e.to_string()))?;
+
+        // Every row is read and buried before any is deleted, so that one unreadable row fails the purge rather than losing the rest
+        let mut purged: Vec<(String, Query)> = vec![];
+        for row in &rows {
+            let model: completions::Model = serde_json::from_value(row.row.clone()).map_err(|e| Status::Error(e.to_string()))?;
+            purged.push((row.query_key.clone(), model.to_query()?));
+        }
+        for (key, query) in &purged {
+            graveyard.bury(key, query);
+        }
+
+        let txn = conn.begin().await.map_err(|e| Status::Error(e.to_string()))?;
+        let ids: Vec<i32> = rows.iter().map(|row| row.rid).collect();
+        for chunk in ids.chunks(DEFAULT_UPSERT_CHUNK_SIZE) {
+            CompletionsTrash::delete_many().filter(completions_trash::Column::Rid.is_in(chunk.to_vec())).exec(&txn).await.map_err(|e| Status::Error(e.to_string()))?;
+        }
+        txn.commit().await.map_err(|e| Status::Error(e.to_string()))?;
+        println!("🗑️  Trash emptied of {} queries.", rows.len());
+        Ok(rows.len())
+    }
+}
+
+/// Row ids selected by `search`, in order
+async fn selected_rids<C: ConnectionTrait>(conn: &C, search: &DbSearch) -> Result<Vec<i32>, Status> {
+    search.select()
+        .select_only()
+        .column(completions::Column::Rid)
+        .into_tuple()
+        .all(conn)
+        .await
+        .map_err(|e| Status::Error(e.to_string()))
+}
+
+/// Copies `models` into `completions_trash`
+pub(super) async fn move_to_trash<C: ConnectionTrait>(conn: &C, models: &[completions::Model]) -> Result<(), Status> {
+    let deleted_at = Utc::now().naive_utc();
+    let rows: Vec<completions_trash::ActiveModel> = models.iter().map(|model| completions_trash::ActiveModel {
+        rid: ActiveValue::NotSet,
+        kind: ActiveValue::Set(model.kind.clone()),
+        query_key: ActiveValue::Set(model.query_key.clone()),
+        query_key_hash: ActiveValue::Set(model.query_key_hash.clone()),
+        deleted_at: ActiveValue::Set(deleted_at),
+        row: ActiveValue::Set(serde_json::to_value(model).expect("conversion to JSON value of a completions row")),
+    }).collect();
+
+    for chunk in rows.chunks(DEFAULT_UPSERT_CHUNK_SIZE) {
+        CompletionsTrash::insert_many(chunk.to_vec()).exec_without_returning(conn).await.map_err(|e| Status::Error(e.to_string()))?;
+    }
+    Ok(())
+}
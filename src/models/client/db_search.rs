@@ -0,0 +1,222 @@
+use chrono::{DateTime, Utc};
+use futures::{Stream, StreamExt};
+use sea_orm::{
+    ColumnTrait,
+    EntityTrait,
+    Order,
+    PaginatorTrait,
+    QueryFilter,
+    QueryOrder,
+    Select,
+    sea_query::{Alias, Expr, Func, LikeExpr},
+};
+
+use crate::{
+    models::db::{completions::Column, prelude::Completions},
+    GptModel,
+    Query,
+    QueryKind,
+};
+use super::{core::Status, database::DbMethods};
+
+/// Filters, ordering and paging over the stored queries, run by the database rather than in memory. Every filter left as `None` matches everything.
+/// <br> Build with the chained setters, then pass to `DbMethods::search()`, `DbMethods::count()` or `DbMethods::stream()`.
+///
+/// ```
+/// use openai_rs::*;
+/// use openai_rs::models::client::db_search::{DbSearch, DbSortBy};
+///
+/// let search = DbSearch::new()
+///     .kind(QueryKind::Text)
+///     .document_title("Cholesterol Paradox")
+///     .prompt_contains("ldl")
+///     .sort_by(DbSortBy::Cost, true);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct DbSearch {
+    pub kinds: Option<Vec<QueryKind>>,
+    pub models: Option<Vec<GptModel>>,
+    /// Exact match. Only text queries have a document title, so this excludes the other kinds.
+    pub document_title: Option<String>,
+    /// Inclusive lower bound on when the row was last saved to the database
+    pub saved_after: Option<DateTime<Utc>>,
+    /// Exclusive upper bound on when the row was last saved to the database
+    pub saved_before: Option<DateTime<Utc>>,
+    /// Inclusive, in CENTS
+    pub min_cost: Option<f32>,
+    /// Inclusive, in CENTS
+    pub max_cost: Option<f32>,
+    /// Case-insensitive substring of the prompt, as with `ILIKE '%text%'`
+    pub prompt_contains: Option<String>,
+    /// Case-sensitive prefix of the cache key, such as `"Text: "`
+    pub key_prefix: Option<String>,
+    /// Exact match on the `Labels::project` kept in the `metadata` column
+    pub project: Option<String>,
+    /// Column to sort by, and whether to sort descending. Ties, and unsorted results, come in the order the rows were first inserted.
+    pub sort: Option<(DbSortBy, bool)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DbSortBy {
+    Key,
+    SavedAt,
+    Cost,
+    ProcessTime,
+    TotalTokens,
+}
+
+/// One page of the results of a `DbSearch`
+#[derive(Debug, Clone, PartialEq)]
+pub struct DbPage {
+    /// As `(cache_key, query)`
+    pub queries: Vec<(String, Query)>,
+    /// Index of this page, from `0`
+    pub page: u64,
+    pub page_size: u64,
+    /// Number of matching rows across all pages
+    pub total: u64,
+    pub pages: u64,
+}
+
+impl DbSearch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds `kind` to the kinds to match
+    pub fn kind(mut self, kind: QueryKind) -> Self {
+        self.kinds.get_or_insert_with(Vec::new).push(kind);
+        self
+    }
+
+    /// Adds `model` to the models to match
+    pub fn model(mut self, model: GptModel) -> Self {
+        self.models.get_or_insert_with(Vec::new).push(model);
+        self
+    }
+
+    pub fn document_title(mut self, document_title: &str) -> Self {
+        self.document_title = Some(document_title.to_string());
+        self
+    }
+
+    /// Rows last saved at or after `after` and before `before`, compared as instants against the UTC `timestamp` column
+    pub fn saved_between(mut self, after: Option<DateTime<Utc>>, before: Option<DateTime<Utc>>) -> Self {
+        self.saved_after = after;
+        self.saved_before = before;
+        self
+    }
+
+    /// Bounds in CENTS
+    pub fn cost_between(mut self, min: Option<f32>, max: Option<f32>) -> Self {
+        self.min_cost = min;
+        self.max_cost = max;
+        self
+    }
+
+    pub fn prompt_contains(mut self, text: &str) -> Self {
+        self.prompt_contains = Some(text.to_string());
+        self
+    }
+
+    pub fn key_prefix(mut self, prefix: &str) -> Self {
+        self.key_prefix = Some(prefix.to_string());
+        self
+    }
+
+    pub fn project(mut self, project: &str) -> Self {
+        self.project = Some(project.to_string());
+        self
+    }
+
+    pub fn sort_by(mut self, sort_by: DbSortBy, descending: bool) -> Self {
+        self.sort = Some((sort_by, descending));
+        self
+    }
+
+    /// The `SELECT` this search runs
+    pub(crate) fn select(&self) -> Select<Completions> {
+        let mut select = Completions::find();
+
+        if let Some(kinds) = &self.kinds {
+            select = select.filter(Column::Kind.is_in(kinds.iter().map(|kind| kind.as_str())));
+        }
+        if let Some(models) = &self.models {
+            select = select.filter(Column::Model.is_in(models.iter().map(|model| model.to_string())));
+        }
+        if let Some(title) = &self.document_title {
+            select = select.filter(Column::DocumentTitle.eq(title.as_str()));
+        }
+        if let Some(after) = self.saved_after {
+            select = select.filter(Column::Timestamp.gte(after.naive_utc()));
+        }
+        if let Some(before) = self.saved_before {
+            select = select.filter(Column::Timestamp.lt(before.naive_utc()));
+        }
+        if let Some(min) = self.min_cost {
+            select = select.filter(Column::Cost.gte(min as f64));
+        }
+        if let Some(max) = self.max_cost {
+            select = select.filter(Column::Cost.lte(max as f64));
+        }
+        if let Some(text) = &self.prompt_contains {
+            // LOWER() on both sides rather than ILIKE, which SQLite lacks
+            let escaped = text.to_lowercase().replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_");
+            let pattern = LikeExpr::new(format!("%{escaped}%")).escape('\\');
+            select = select.filter(Expr::expr(Func::lower(Expr::col(Column::Prompt))).like(pattern));
+        }
+        if let Some(prefix) = &self.key_prefix {
+            // Compared rather than LIKE, which ignores case on SQLite
+            let length = prefix.chars().count() as i32;
+            let start = Func::cust(Alias::new("substr")).arg(Expr::col(Column::QueryKey)).arg(1).arg(length);
+            select = select.filter(Expr::expr(start).eq(prefix.as_str()));
+        }
+        if let Some(project) = &self.project {
+            // `->` and `->>` read JSON the same way on Postgres and on SQLite 3.38+
+            select = select.filter(Expr::expr(Expr::cust("metadata -> 'labels' ->> 'project'")).eq(project.as_str()));
+        }
+
+        if let Some((sort_by, descending)) = self.sort {
+            let column = match sort_by {
+                DbSortBy::Key => Column::QueryKey,
+                DbSortBy::SavedAt => Column::Timestamp,
+                DbSortBy::Cost => Column::Cost,
+                DbSortBy::ProcessTime => Column::ProcessTime,
+                DbSortBy::TotalTokens => Column::TotalTokens,
+            };
+            select = select.order_by(column, if descending { Order::Desc } else { Order::Asc });
+        }
+        // Keeps pages stable
+        select.order_by(Column::Rid, Order::Asc)
+    }
+}
+
+impl DbMethods {
+    /// Page `page` (from `0`) of the stored queries matching `search`, `page_size` at a time
+    pub async fn search(&self, search: &DbSearch, page: u64, page_size: u64) -> Result<DbPage, Status> {
+        let page_size = page_size.max(1);
+        let paginator = search.select().paginate(self.conn()?, page_size);
+        let counts = paginator.num_items_and_pages().await.map_err(|e| Status::Error(e.to_string()))?;
+        let models = paginator.fetch_page(page).await.map_err(|e| Status::Error(e.to_string()))?;
+
+        let mut queries = vec![];
+        for model in models {
+            queries.push((model.query_key.clone(), model.to_query()?));
+        }
+        Ok(DbPage { queries, page, page_size, total: counts.number_of_items, pages: counts.number_of_pages })
+    }
+
+    /// How many stored queries match `search`
+    pub async fn count(&self, search: &DbSearch) -> Result<u64, Status> {
+        search.select().count(self.conn()?).await.map_err(|e| Status::Error(e.to_string()))
+    }
+
+    /// Every stored query matching `search`, as `(cache_key, query)`, read one row at a time rather than loaded all at once
+    pub async fn stream(&self, search: &DbSearch) -> Result<impl Stream<Item = Result<(String, Query), Status>> + '_, Status> {
+        let rows = search.select().stream(self.conn()?).await.map_err(|e| Status::Error(e.to_string()))?;
+        Ok(rows.map(|row| {
+            let model = row.map_err(|e| Status::Error(e.to_string()))?;
+            Ok((model.query_key.clone(), model.to_query()?))
+        }))
+    }
+}
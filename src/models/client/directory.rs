@@ -0,0 +1,230 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+use chrono::{DateTime, Utc};
+use regex::Regex;
+use serde::{Serialize, Deserialize};
+
+use crate::{
+    constants::pdf_path::DEFAULT_PDF_GLOB,
+    models::documents::StoredDocument,
+    TextQuery,
+};
+use super::{
+    core::{OpenAIAccount, Status},
+    json_file::{load_json, save_json},
+};
+
+/// What became of one document of a directory run
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub enum DirOutcome {
+    /// Sent to OpenAI, and the answer cached
+    Succeeded,
+    /// Already answered in the cache, so not sent
+    Cached,
+    /// Left unanswered, and retried on the next run
+    Failed(String),
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct DirFileReport {
+    pub path: PathBuf,
+    /// Cache key of the answer
+    pub key: String,
+    pub outcome: DirOutcome,
+    /// In CENTS. `0.0` unless sent to OpenAI
+    pub cost: f32,
+    /// In milliseconds
+    pub process_time: u64,
+}
+
+/// Progress of `OpenAIAccount::apply_prompt_to_dir()`. Saved as the checkpoint after every document, and returned once every document has been tried.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct DirReport {
+    pub dir: PathBuf,
+    pub prompt: String,
+    pub glob: String,
+    pub started_at: DateTime<Utc>,
+    /// In the order they were processed. Documents that failed in an earlier run are replaced by their retry.
+    pub files: Vec<DirFileReport>,
+    /// How many of `files` were done by an earlier, interrupted run
+    #[serde(default)]
+    pub resumed: usize,
+}
+
+impl DirReport {
+    fn new(dir: &Path, prompt: &str, glob: &str) -> Self {
+        DirReport { dir: dir.to_path_buf(), prompt: prompt.to_string(), glob: glob.to_string(), started_at: Utc::now(), files: vec![], resumed: 0 }
+    }
+
+    /// The checkpoint at `filepath` if it is of the same directory, prompt and glob, with its failures dropped so that they are retried. Else a fresh report.
+    /// <br> Errors if the checkpoint can't be read or parsed, rather than overwrite it. See `load_json()`
+    pub fn resume(filepath: &Path, dir: &Path, prompt: &str, glob: &str) -> Result<Self, Status> {
+        let checkpoint: Option<DirReport> = load_json(filepath)?;
+        Ok(match checkpoint {
+            Some(mut checkpoint) if checkpoint.dir == dir && checkpoint.prompt == prompt && checkpoint.glob == glob => {
+                checkpoint.files.retain(|file| !matches!(file.outcome, DirOutcome::Failed(_)));
+                checkpoint.resumed = checkpoint.files.len();
+                println!("📂 Resuming run started at {} from checkpoint: {}", checkpoint.started_at.to_rfc3339(), filepath.display());
+                checkpoint
+            },
+            Some(_) => {
+                println!("📂 Checkpoint at {} is of another run, and will be replaced", filepath.display());
+                DirReport::new(dir, prompt, glob)
+            },
+            None => DirReport::new(dir, prompt, glob),
+        })
+    }
+
+    pub fn save(&self, filepath: &Path) -> Result<(), Status> {
+        save_json(self, filepath)
+    }
+
+    pub fn is_done(&self, path: &Path) -> bool {
+        self.files.iter().any(|file| file.path == path)
+    }
+
+    pub fn succeeded(&self) -> Vec<&DirFileReport> {
+        self.files.iter().filter(|file| file.outcome == DirOutcome::Succeeded).collect()
+    }
+
+    pub fn cached(&self) -> Vec<&DirFileReport> {
+        self.files.iter().filter(|file| file.outcome == DirOutcome::Cached).collect()
+    }
+
+    pub fn failed(&self) -> Vec<&DirFileReport> {
+        self.files.iter().filter(|file| matches!(file.outcome, DirOutcome::Failed(_))).collect()
+    }
+
+    /// In CENTS
+    pub fn cost(&self) -> f32 {
+        self.files.iter().map(|file| file.cost).sum()
+    }
+
+    pub fn print(&self) {
+        println!("\n");
+        println!("📂 Prompt applied to \"{}\" in {}", self.glob, self.dir.display());
+        println!("Succeeded: {}", self.succeeded().len());
+        println!("Cached: {}", self.cached().len());
+        println!("Failed: {}", self.failed().len());
+        for file in self.failed() {
+            if let DirOutcome::Failed(e) = &file.outcome {
+                println!("  {}:  ❌  {e}", file.path.display());
+            }
+        }
+        println!("Cost: ${:.2}", self.cost() / 100.0);
+        println!("\n");
+    }
+}
+
+/// Files under `dir` whose path relative to it matches `glob`, sorted. In `glob`, `*` and `?` match within one path segment, and `**/` matches any number of directories. Symlinked directories are not descended into.
+pub fn find_documents(dir: &Path, glob: &str) -> Result<Vec<PathBuf>, Status> {
+    let pattern = glob_regex(glob);
+    let mut found = vec![];
+    let mut dirs = vec![dir.to_path_buf()];
+    while let Some(current) = dirs.pop() {
+        for entry in fs::read_dir(&current).map_err(|e| Status::Error(format!("{}: {e}", current.display())))? {
+            let entry = entry.map_err(|e| Status::Error(e.to_string()))?;
+            let file_type = entry.file_type().map_err(|e| Status::Error(format!("{}: {e}", entry.path().display())))?;
+            let path = entry.path();
+            if file_type.is_dir() {
+                dirs.push(path);
+                continue
+            }
+            // Links to files are kept, but links to directories are not followed, so that one pointing back up the tree can't loop forever
+            if file_type.is_symlink() && !path.is_file() { continue }
+            let relative = path.strip_prefix(dir).unwrap_or(&path).to_string_lossy().replace('\\', "/");
+            if pattern.is_match(&relative) {
+                found.push(path);
+            }
+        }
+    }
+    found.sort();
+    Ok(found)
+}
+
+/// The title a document is cached under: its filename without extension
+pub fn document_title(path: &Path) -> String {
+    path.file_stem().map(|stem| stem.to_string_lossy().to_string()).unwrap_or_default()
+}
+
+/// The titles shared by more than one of `documents`, such as `a/report.pdf` and `b/report.pdf`, with the documents sharing each. Answers are cached by title, so these would be served each other's answers.
+pub fn title_clashes(documents: &[PathBuf]) -> BTreeMap<String, Vec<PathBuf>> {
+    let mut by_title: BTreeMap<String, Vec<PathBuf>> = BTreeMap::new();
+    for path in documents {
+        by_title.entry(document_title(path)).or_default().push(path.clone());
+    }
+    by_title.retain(|_, paths| paths.len() > 1);
+    by_title
+}
+
+/// Why `path` is left unanswered, if its title is one of `clashes`
+pub(super) fn title_clash(clashes: &BTreeMap<String, Vec<PathBuf>>, path: &Path) -> Option<String> {
+    let title = document_title(path);
+    let others: Vec<String> = clashes.get(&title)?.iter().filter(|other| *other != path).map(|other| other.display().to_string()).collect();
+    Some(format!("Shares its title \"{title}\" with {}, and answers are cached by title. Rename one of them to answer both", others.join(", ")))
+}
+
+fn glob_regex(glob: &str) -> Regex {
+    let mut regex = String::from("^");
+    let mut chars = glob.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                if chars.peek() == Some(&'/') {
+                    chars.next();
+                    regex.push_str("(?:.*/)?");
+                } else {
+                    regex.push_str(".*");
+                }
+            },
+            '*' => regex.push_str("[^/]*"),
+            '?' => regex.push_str("[^/]"),
+            c => regex.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    regex.push('$');
+    Regex::new(&regex).expect("escaped glob is a valid regex")
+}
+
+impl OpenAIAccount {
+    /// Applies `prompt` to every document under `dir` matching `glob` (`*.pdf` if `None`), in any format `apply_prompt_to_document()` reads. Documents already answered in the cache are not sent again.
+    /// <br> Documents sharing a title, such as `a/report.pdf` and `b/report.pdf` under `**/*.pdf`, are failed rather than served each other's answer. See `title_clashes()`
+    /// <br> Progress is checkpointed to `Opts::checkpoint_filepath` after every document, so a run that is interrupted picks up where it stopped when called again with the same arguments. The checkpoint is removed once every document has succeeded.
+    pub async fn apply_prompt_to_dir(&mut self, dir: &Path, prompt: &str, glob: Option<&str>) -> Result<DirReport, Status> {
+        let glob = glob.unwrap_or(DEFAULT_PDF_GLOB);
+        let documents = find_documents(dir, glob)?;
+        let clashes = title_clashes(&documents);
+        let mut report = DirReport::resume(&self.checkpoint_filepath, dir, prompt, glob)?;
+        println!("📂 {} documents in {} match \"{glob}\", {} of them done before", documents.len(), dir.display(), report.resumed);
+
+        for path in documents {
+            if report.is_done(&path) { continue }
+            let key = TextQuery::key(prompt, &document_title(&path));
+
+            let file = if let Some(e) = title_clash(&clashes, &path) {
+                DirFileReport { path, key, outcome: DirOutcome::Failed(e), cost: 0.0, process_time: 0 }
+            } else if self.cache.entries.get(&key).is_some_and(|cached| cached.pdf() == StoredDocument::read_opts(&path, &self.pdf)) {
+                DirFileReport { path, key, outcome: DirOutcome::Cached, cost: 0.0, process_time: 0 }
+            } else {
+                match self.apply_prompt_to_document(&path, prompt).await {
+                    Ok(query) if query.from_cache => DirFileReport { path, key, outcome: DirOutcome::Cached, cost: 0.0, process_time: 0 },
+                    Ok(query) => DirFileReport { path, key, outcome: DirOutcome::Succeeded, cost: query.cost, process_time: query.process_time },
+                    Err(e) => DirFileReport { path, key, outcome: DirOutcome::Failed(format!("{e:?}")), cost: 0.0, process_time: 0 },
+                }
+            };
+            report.files.push(file);
+            report.save(&self.checkpoint_filepath)?;
+        }
+
+        if report.failed().is_empty() {
+            let _ = fs::remove_file(&self.checkpoint_filepath);
+        } else {
+            println!("📂 {} documents failed. Call again to retry them, from checkpoint: {}", report.failed().len(), self.checkpoint_filepath.display());
+        }
+        Ok(report)
+    }
+}
@@ -0,0 +1,124 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+use chrono::{DateTime, Utc};
+use serde::{Serialize, Deserialize};
+
+use crate::models::{
+    client::{
+        core::{OpenAIAccount, Status},
+        json_file::{load_json, save_json},
+    },
+    documents::{
+        bibliography::{find_doi, parse_references, reference_section_start, Reference},
+        DocumentMetadata,
+        DocumentStore,
+        PdfOpts,
+        StoredDocument,
+    },
+    hash::sha256_hex,
+};
+
+/// What `OpenAIAccount::analyze_document()` found out about a document, kept by the hash of its content
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct DocumentRecord {
+    /// Hex SHA-256 of the file
+    pub sha256: String,
+    /// Where the file was when analyzed
+    pub source: PathBuf,
+    /// From the PDF info dictionary, or what the format carries instead
+    pub metadata: DocumentMetadata,
+    /// From the metadata if given there, else the first in the text before the references
+    pub doi: Option<String>,
+    pub references: Vec<Reference>,
+    pub analyzed_at: DateTime<Utc>,
+}
+
+impl DocumentRecord {
+    /// The document title of the `TextQuery`s of this document: its filename without extension
+    pub fn document_title(&self) -> String {
+        self.source.file_stem().map(|stem| stem.to_string_lossy().to_string()).unwrap_or_default()
+    }
+
+    /// A few lines on the document, to give a prompt as context in place of the document itself
+    pub fn describe(&self) -> String {
+        let mut lines = vec![];
+        if let Some(title) = &self.metadata.title { lines.push(format!("Title: {title}")) }
+        if let Some(author) = &self.metadata.author { lines.push(format!("Authors: {author}")) }
+        if let Some(year) = self.metadata.year { lines.push(format!("Year: {year}")) }
+        if let Some(doi) = &self.doi { lines.push(format!("DOI: {doi}")) }
+        if !self.metadata.keywords.is_empty() { lines.push(format!("Keywords: {}", self.metadata.keywords.join("; "))) }
+        if let Some(pages) = self.metadata.page_count { lines.push(format!("Pages: {pages}")) }
+        lines.push(format!("References: {}", self.references.len()));
+        lines.join("\n")
+    }
+}
+
+/// The records of every document analyzed, by content hash, kept at `Opts::document_records_filepath`
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct DocumentRecords {
+    pub records: BTreeMap<String, DocumentRecord>,
+}
+
+impl DocumentRecords {
+    /// Reads the file, or starts empty if it is absent. See `load_json()`
+    pub fn load(filepath: &Path) -> Result<DocumentRecords, Status> {
+        load_json(filepath)
+    }
+
+    pub fn save(&self, filepath: &Path) -> Result<(), Status> {
+        save_json(self, filepath)
+    }
+
+    /// Keeps `record`, in place of any earlier record of a file at the same path, which has since changed
+    pub fn insert(&mut self, record: DocumentRecord) {
+        self.records.retain(|_, kept| kept.source != record.source);
+        self.records.insert(record.sha256.clone(), record);
+    }
+
+    /// The record of the document a `TextQuery` with this `document_title` was made on
+    pub fn by_title(&self, document_title: &str) -> Option<&DocumentRecord> {
+        self.records.values().find(|record| record.document_title() == document_title)
+    }
+}
+
+/// Reads the metadata, DOI and reference list of a document, from its text as stored. Give it the whole text, as extracted with `PdfOpts::default()`, for the references to be found.
+pub fn analyze(doc: &StoredDocument) -> DocumentRecord {
+    let body = &doc.text[..reference_section_start(&doc.text).unwrap_or(doc.text.len())];
+    let doi = [&doc.metadata.subject, &doc.metadata.title].into_iter().flatten().find_map(|field| find_doi(field))
+        .or_else(|| find_doi(body));
+    DocumentRecord {
+        sha256: doc.sha256.clone(),
+        source: doc.source.clone(),
+        metadata: doc.metadata.clone(),
+        doi,
+        references: parse_references(&doc.text),
+        analyzed_at: Utc::now(),
+    }
+}
+
+impl OpenAIAccount {
+    /// The metadata, DOI and reference list of the document at `path`, read once per version of the file and then served from `Opts::document_records_filepath`
+    /// <br> The text is read through the `DocumentStore` at `Opts::document_store_dir`, as a whole document whatever `Opts::pdf` selects or strips from what is sent, so that the references are there to be found.
+    pub fn analyze_document(&self, path: &Path) -> Result<DocumentRecord, Status> {
+        let bytes = fs::read(path).map_err(|e| Status::Error(format!("{}: {e}", path.display())))?;
+        let sha256 = sha256_hex(&bytes);
+        let mut records = DocumentRecords::load(&self.document_records_filepath)?;
+        if let Some(record) = records.records.get(&sha256) {
+            return Ok(record.clone())
+        }
+        let doc = DocumentStore::new(&self.document_store_dir).read(path, &PdfOpts::default())?;
+        let record = analyze(&doc);
+        println!("📑 Analyzed {}: DOI {}, {} references", path.display(), record.doi.as_deref().unwrap_or("not found"), record.references.len());
+        records.insert(record.clone());
+        records.save(&self.document_records_filepath)?;
+        Ok(record)
+    }
+
+    /// The record of the document a `TextQuery` with this `document_title` was made on, if it was analyzed. Errors if the records file can't be read.
+    pub fn document_record(&self, document_title: &str) -> Result<Option<DocumentRecord>, Status> {
+        Ok(DocumentRecords::load(&self.document_records_filepath)?.by_title(document_title).cloned())
+    }
+}
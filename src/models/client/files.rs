@@ -0,0 +1,271 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+};
+use chrono::{DateTime, TimeZone, Utc};
+use serde::{Serialize, Deserialize};
+
+use crate::models::{
+    client::{
+        core::{OpenAIAccount, Status},
+        json_file::{load_json, save_json},
+        requests::new_error,
+    },
+    hash::sha256_hex,
+};
+
+/// What an uploaded file is for. OpenAI only lets a file be used by features of its purpose.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum FilePurpose {
+    /// Input of a batch job. See `OpenAIAccount::submit_batch()`
+    #[serde(rename = "batch")]
+    Batch,
+    /// Results of a batch job, written by OpenAI
+    #[serde(rename = "batch_output")]
+    BatchOutput,
+    #[serde(rename = "fine-tune")]
+    FineTune,
+    #[serde(rename = "assistants")]
+    Assistants,
+}
+
+impl FilePurpose {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FilePurpose::Batch => "batch",
+            FilePurpose::BatchOutput => "batch_output",
+            FilePurpose::FineTune => "fine-tune",
+            FilePurpose::Assistants => "assistants",
+        }
+    }
+}
+
+/// A file stored with OpenAI, as returned by `/v1/files`
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct FileObject {
+    pub id: String,
+    pub bytes: u64,
+    /// Unix seconds
+    pub created_at: i64,
+    pub filename: String,
+    /// As sent, such as `"batch"`. See `FilePurpose::as_str()`
+    pub purpose: String,
+}
+
+/// Files per page of `/v1/files`, the most OpenAI returns at once
+const FILES_PAGE_SIZE: usize = 10_000;
+
+/// One page of `/v1/files`
+#[derive(Debug, Deserialize)]
+struct FileList {
+    data: Vec<FileObject>,
+    /// Whether more files follow the last one of `data`
+    #[serde(default)]
+    has_more: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct FileDeleted {
+    deleted: bool,
+}
+
+/// Filters over the files stored with OpenAI. Every filter left as `None` matches everything.
+/// <br> Build with the chained setters, then hand to `OpenAIAccount::list_files()`. The purpose is filtered by OpenAI, the rest locally.
+#[derive(Debug, Clone, Default)]
+pub struct FileSearch {
+    pub purpose: Option<FilePurpose>,
+    /// Case-sensitive substring of the filename
+    pub filename_contains: Option<String>,
+    /// Inclusive lower bound on `created_at`
+    pub created_after: Option<DateTime<Utc>>,
+    /// Exclusive upper bound on `created_at`
+    pub created_before: Option<DateTime<Utc>>,
+}
+
+impl FileSearch {
+    pub fn new() -> Self {
+        FileSearch::default()
+    }
+
+    pub fn purpose(mut self, purpose: FilePurpose) -> Self {
+        self.purpose = Some(purpose);
+        self
+    }
+
+    pub fn filename_contains(mut self, text: &str) -> Self {
+        self.filename_contains = Some(text.to_string());
+        self
+    }
+
+    pub fn created_after(mut self, created_after: DateTime<Utc>) -> Self {
+        self.created_after = Some(created_after);
+        self
+    }
+
+    pub fn created_before(mut self, created_before: DateTime<Utc>) -> Self {
+        self.created_before = Some(created_before);
+        self
+    }
+
+    pub fn matches(&self, file: &FileObject) -> bool {
+        let created_at = Utc.timestamp_opt(file.created_at, 0).single().unwrap_or_default();
+        self.purpose.is_none_or(|purpose| file.purpose == purpose.as_str())
+            && self.filename_contains.as_ref().is_none_or(|text| file.filename.contains(text.as_str()))
+            && self.created_after.is_none_or(|after| created_at >= after)
+            && self.created_before.is_none_or(|before| created_at < before)
+    }
+}
+
+/// A file uploaded from one of our documents
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct RegisteredFile {
+    pub file: FileObject,
+    /// The document it was uploaded from
+    pub source: PathBuf,
+    /// Hex SHA-256 of the uploaded content
+    pub sha256: String,
+}
+
+/// The files uploaded from our documents, by file id, so that the same content is not uploaded twice for the same purpose.
+/// <br> Kept at `Opts::files_filepath`. See `OpenAIAccount::upload_document()`
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct FileRegistry {
+    pub files: BTreeMap<String, RegisteredFile>,
+}
+
+impl FileRegistry {
+    /// Reads the file, or starts empty if it is absent. See `load_json()`
+    pub fn load(filepath: &Path) -> Result<FileRegistry, Status> {
+        load_json(filepath)
+    }
+
+    pub fn save(&self, filepath: &Path) -> Result<(), Status> {
+        save_json(self, filepath)
+    }
+
+    /// The file already uploaded with this content for `purpose`, if any
+    pub fn find(&self, sha256: &str, purpose: FilePurpose) -> Option<&RegisteredFile> {
+        self.files.values().find(|registered| registered.sha256 == sha256 && registered.file.purpose == purpose.as_str())
+    }
+
+    /// The files uploaded from `source`, whatever its content was at the time
+    pub fn by_source(&self, source: &Path) -> Vec<&RegisteredFile> {
+        self.files.values().filter(|registered| registered.source == source).collect()
+    }
+
+    pub fn register(&mut self, file: FileObject, source: &Path, sha256: &str) {
+        self.files.insert(file.id.clone(), RegisteredFile { file, source: source.to_path_buf(), sha256: sha256.to_string() });
+    }
+
+    pub fn forget(&mut self, file_id: &str) -> Option<RegisteredFile> {
+        self.files.remove(file_id)
+    }
+}
+
+/// The path of the page of `/v1/files` that follows the file `after`, or of the first page. Only files of `purpose` are listed, if given.
+pub(crate) fn files_page_path(purpose: Option<FilePurpose>, after: Option<&str>) -> String {
+    let mut path = format!("/files?limit={FILES_PAGE_SIZE}");
+    if let Some(purpose) = purpose {
+        path.push_str(&format!("&purpose={}", purpose.as_str()));
+    }
+    if let Some(after) = after {
+        path.push_str(&format!("&after={after}"));
+    }
+    path
+}
+
+impl OpenAIAccount {
+    /// Uploads `bytes` as a file named `filename`. Not recorded in the `FileRegistry`; see `upload_document()` for that.
+    pub async fn upload_file(&self, bytes: Vec<u8>, filename: &str, purpose: FilePurpose) -> Result<FileObject, Status> {
+        println!("📤 Uploading {filename} ({} bytes) for {}", bytes.len(), purpose.as_str());
+        let form = reqwest::multipart::Form::new()
+            .text("purpose", purpose.as_str())
+            .part("file", reqwest::multipart::Part::bytes(bytes).file_name(filename.to_string()));
+        let res = self.sender().post_multipart("/files", form).await.map_err(|e| Status::Error(e.to_string()))?;
+        res.json::<FileObject>().await.map_err(|e| Status::Error(new_error(e).to_string()))
+    }
+
+    /// Uploads the document at `path`, unless a file with the same content was already uploaded for `purpose`, in which case that file is returned.
+    /// <br> Uploads are recorded in the `FileRegistry` at `Opts::files_filepath`.
+    pub async fn upload_document(&self, path: &Path, purpose: FilePurpose) -> Result<FileObject, Status> {
+        let bytes = fs::read(path).map_err(|e| Status::Error(format!("{}: {e}", path.display())))?;
+        let sha256 = sha256_hex(&bytes);
+        let mut registry = FileRegistry::load(&self.files_filepath)?;
+        if let Some(registered) = registry.find(&sha256, purpose) {
+            println!("📤 {} already uploaded as {}", path.display(), registered.file.id);
+            return Ok(registered.file.clone())
+        }
+        let filename = path.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+        let file = self.upload_file(bytes, &filename, purpose).await?;
+        registry.register(file.clone(), path, &sha256);
+        registry.save(&self.files_filepath)?;
+        Ok(file)
+    }
+
+    /// The files stored with OpenAI that match `search`. Every page of the listing is read, so none are left out.
+    pub async fn list_files(&self, search: &FileSearch) -> Result<Vec<FileObject>, Status> {
+        let mut files = vec![];
+        let mut after: Option<String> = None;
+        loop {
+            let res = self.sender().get(&files_page_path(search.purpose, after.as_deref())).await.map_err(|e| Status::Error(e.to_string()))?;
+            let list = res.json::<FileList>().await.map_err(|e| Status::Error(new_error(e).to_string()))?;
+            let last = list.data.last().map(|file| file.id.clone());
+            files.extend(list.data);
+            match (list.has_more, last) {
+                (false, _) => break,
+                (true, Some(last)) => after = Some(last),
+                (true, None) => return Err(Status::Error("OpenAI listed more files after an empty page".to_string())),
+            }
+        }
+        Ok(files.into_iter().filter(|file| search.matches(file)).collect())
+    }
+
+    pub async fn retrieve_file(&self, file_id: &str) -> Result<FileObject, Status> {
+        let res = self.sender().get(&format!("/files/{file_id}")).await.map_err(|e| Status::Error(e.to_string()))?;
+        res.json::<FileObject>().await.map_err(|e| Status::Error(new_error(e).to_string()))
+    }
+
+    /// The content of the file `file_id`
+    pub async fn file_content(&self, file_id: &str) -> Result<Vec<u8>, Status> {
+        let res = self.sender().get(&format!("/files/{file_id}/content")).await.map_err(|e| Status::Error(e.to_string()))?;
+        let bytes = res.bytes().await.map_err(|e| Status::Error(new_error(e).to_string()))?;
+        Ok(bytes.to_vec())
+    }
+
+    /// Writes the content of the file `file_id` to `path`
+    pub async fn download_file(&self, file_id: &str, path: &Path) -> Result<(), Status> {
+        let bytes = self.file_content(file_id).await?;
+        fs::write(path, &bytes).map_err(|e| Status::Error(format!("{}: {e}", path.display())))?;
+        println!("📥 Downloaded {file_id} ({} bytes) to {}", bytes.len(), path.display());
+        Ok(())
+    }
+
+    /// Deletes the file `file_id` from OpenAI, and from the `FileRegistry`
+    pub async fn delete_file(&self, file_id: &str) -> Result<(), Status> {
+        let res = self.sender().delete(&format!("/files/{file_id}")).await.map_err(|e| Status::Error(e.to_string()))?;
+        let deleted = res.json::<FileDeleted>().await.map_err(|e| Status::Error(new_error(e).to_string()))?;
+        if !deleted.deleted {
+            return Err(Status::Error(format!("OpenAI did not delete file {file_id}")))
+        }
+        let mut registry = FileRegistry::load(&self.files_filepath)?;
+        if registry.forget(file_id).is_some() {
+            registry.save(&self.files_filepath)?;
+        }
+        println!("🗑️ Deleted file {file_id}");
+        Ok(())
+    }
+
+    /// Forgets the registered files that are no longer stored with OpenAI, such as ones deleted elsewhere, so that their documents can be uploaded again. Returns what was forgotten.
+    pub async fn prune_file_registry(&self) -> Result<Vec<RegisteredFile>, Status> {
+        let stored: HashSet<String> = self.list_files(&FileSearch::new()).await?.into_iter().map(|file| file.id).collect();
+        let mut registry = FileRegistry::load(&self.files_filepath)?;
+        let gone: Vec<String> = registry.files.keys().filter(|id| !stored.contains(*id)).cloned().collect();
+        let forgotten: Vec<RegisteredFile> = gone.iter().filter_map(|id| registry.forget(id)).collect();
+        if !forgotten.is_empty() {
+            registry.save(&self.files_filepath)?;
+            println!("🗑️ Forgot {} registered files no longer stored with OpenAI", forgotten.len());
+        }
+        Ok(forgotten)
+    }
+}
@@ -0,0 +1,171 @@
+use std::collections::HashMap;
+use sea_orm::{ColumnTrait, ConnectionTrait, DatabaseBackend, EntityTrait, QueryFilter, Statement, Value};
+
+use crate::{
+    models::db::{completions::Column, prelude::Completions},
+    Query,
+    QueryKind,
+};
+use super::{core::Status, database::DbMethods};
+
+/// Ranked search over the prompts and response contents of stored queries.
+/// <br> Runs on the `search_vector` column on Postgres, and on the `completions_fts` FTS5 table on SQLite. See `models::db::migration`
+///
+/// ```
+/// use openai_rs::*;
+/// use openai_rs::models::client::full_text::FullTextSearch;
+///
+/// let search = FullTextSearch::new("statin side effects")
+///     .kind(QueryKind::Text)
+///     .document_title("Cholesterol Paradox")
+///     .limit(5);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct FullTextSearch {
+    /// Every word must appear in the prompt or the response. Postgres also matches other forms of a word, SQLite only the word itself.
+    pub text: String,
+    pub kinds: Option<Vec<QueryKind>>,
+    /// Exact match. Only text queries have a document title, so this excludes the other kinds.
+    pub document_title: Option<String>,
+    pub limit: u64,
+    /// Put around the matched words in snippets
+    pub highlight: (String, String),
+}
+
+/// A stored query that matched a `FullTextSearch`
+#[derive(Debug, Clone, PartialEq)]
+pub struct FullTextHit {
+    pub key: String,
+    pub query: Query,
+    /// Higher is more relevant. Only comparable between hits of the same search.
+    pub rank: f64,
+    /// The best matching fragment, with matched words highlighted
+    pub snippet: String,
+}
+
+impl FullTextSearch {
+    /// Defaults to 20 results, with matches highlighted by `<b>` and `</b>`
+    pub fn new(text: &str) -> Self {
+        FullTextSearch {
+            text: text.to_string(),
+            kinds: None,
+            document_title: None,
+            limit: 20,
+            highlight: ("<b>".to_string(), "</b>".to_string()),
+        }
+    }
+
+    /// Adds `kind` to the kinds to match
+    pub fn kind(mut self, kind: QueryKind) -> Self {
+        self.kinds.get_or_insert_with(Vec::new).push(kind);
+        self
+    }
+
+    pub fn document_title(mut self, document_title: &str) -> Self {
+        self.document_title = Some(document_title.to_string());
+        self
+    }
+
+    pub fn limit(mut self, limit: u64) -> Self {
+        self.limit = limit;
+        self
+    }
+
+    pub fn highlight(mut self, start: &str, stop: &str) -> Self {
+        self.highlight = (start.to_string(), stop.to_string());
+        self
+    }
+
+    /// The statement returning `rid`, `rank` and `snippet` of the best matches
+    fn statement(&self, backend: DatabaseBackend) -> Result<Statement, Status> {
+        let mut values: Vec<Value> = vec![];
+        let mut placeholder = |value: Value| {
+            values.push(value);
+            match backend {
+                DatabaseBackend::Postgres => format!("${}", values.len()),
+                _ => "?".to_string(),
+            }
+        };
+
+        let sql = match backend {
+            DatabaseBackend::Postgres => {
+                let (start, stop) = &self.highlight;
+                let options = placeholder(format!("StartSel={start}, StopSel={stop}, MaxFragments=2").into());
+                let text = placeholder(self.text.clone().into());
+                let scope = self.scope(&mut placeholder);
+                let limit = placeholder((self.limit as i64).into());
+                format!(
+                    "SELECT c.rid, ts_rank(c.search_vector, q)::float8 AS rank, \
+                     ts_headline('english', c.prompt || ' ' || coalesce(c.response->'choices'->0->'message'->>'content', ''), q, {options}) AS snippet \
+                     FROM completions c, websearch_to_tsquery('english', {text}) q \
+                     WHERE c.search_vector @@ q{scope} ORDER BY rank DESC, c.rid LIMIT {limit}"
+                )
+            },
+            DatabaseBackend::Sqlite => {
+                let start = placeholder(self.highlight.0.clone().into());
+                let stop = placeholder(self.highlight.1.clone().into());
+                let text = placeholder(fts5_query(&self.text).into());
+                let scope = self.scope(&mut placeholder);
+                let limit = placeholder((self.limit as i64).into());
+                // bm25() is lower for better matches
+                format!(
+                    "SELECT c.rid, -bm25(completions_fts) AS rank, snippet(completions_fts, -1, {start}, {stop}, '…', 24) AS snippet \
+                     FROM completions_fts JOIN completions c ON c.rid = completions_fts.rowid \
+                     WHERE completions_fts MATCH {text}{scope} ORDER BY rank DESC, c.rid LIMIT {limit}"
+                )
+            },
+            _ => return Err(Status::Error("Full-text search needs a Postgres or SQLite database".to_string())),
+        };
+        Ok(Statement::from_sql_and_values(backend, sql, values))
+    }
+
+    /// `AND ...` conditions for the kinds and document title
+    fn scope(&self, placeholder: &mut impl FnMut(Value) -> String) -> String {
+        let mut scope = String::new();
+        if let Some(kinds) = &self.kinds {
+            let kinds: Vec<String> = kinds.iter().map(|kind| placeholder(kind.as_str().into())).collect();
+            scope.push_str(&format!(" AND c.kind IN ({})", kinds.join(", ")));
+        }
+        if let Some(title) = &self.document_title {
+            scope.push_str(&format!(" AND c.document_title = {}", placeholder(title.clone().into())));
+        }
+        scope
+    }
+}
+
+/// Every word of `text` as a quoted FTS5 string, so that punctuation in it can't be read as query syntax
+fn fts5_query(text: &str) -> String {
+    text.split_whitespace()
+        .map(|word| format!("\"{}\"", word.replace('"', "\"\"")))
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+impl DbMethods {
+    /// The stored queries best matching `search`, most relevant first
+    pub async fn full_text_search(&self, search: &FullTextSearch) -> Result<Vec<FullTextHit>, Status> {
+        let conn = self.conn()?;
+        if search.text.split_whitespace().next().is_none() { return Ok(vec![]) }
+
+        let rows = conn.query_all(search.statement(conn.get_database_backend())?).await.map_err(|e| Status::Error(e.to_string()))?;
+        let mut ranked: Vec<(i32, f64, String)> = vec![];
+        for row in rows {
+            let rid: i32 = row.try_get("", "rid").map_err(|e| Status::Error(e.to_string()))?;
+            let rank: f64 = row.try_get("", "rank").map_err(|e| Status::Error(e.to_string()))?;
+            let snippet: String = row.try_get("", "snippet").map_err(|e| Status::Error(e.to_string()))?;
+            ranked.push((rid, rank, snippet));
+        }
+
+        let rids: Vec<i32> = ranked.iter().map(|(rid, _, _)| *rid).collect();
+        let mut models: HashMap<i32, _> = Completions::find().filter(Column::Rid.is_in(rids)).all(conn).await.map_err(|e| Status::Error(e.to_string()))?
+            .into_iter().map(|model| (model.rid, model)).collect();
+
+        let mut hits = vec![];
+        for (rid, rank, snippet) in ranked {
+            if let Some(model) = models.remove(&rid) {
+                hits.push(FullTextHit { key: model.query_key.clone(), query: model.to_query()?, rank, snippet });
+            }
+        }
+        Ok(hits)
+    }
+}
@@ -0,0 +1,125 @@
+use std::{
+    fs,
+    io::{self, BufRead, Write},
+    path::PathBuf,
+};
+use chrono::{DateTime, Utc};
+use serde::{Serialize, Deserialize};
+
+use crate::Query;
+
+/// Version history of cache entries. Whenever a cached query is overwritten or evicted, the outgoing version is appended here, stamped with when it was buried.
+/// <br> The file holds one `Version` per line, and is kept across restarts.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Graveyard {
+    pub filepath: PathBuf,
+}
+
+impl Default for Graveyard {
+    fn default() -> Self {
+        Graveyard { filepath: "./graveyard.json".into() }
+    }
+}
+
+/// A prior version of the query at `key`
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Version {
+    pub key: String,
+    pub buried_at: DateTime<Utc>,
+    pub query: Query,
+}
+
+/// One line of a diff between two responses
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiffLine {
+    Same(String),
+    Removed(String),
+    Added(String),
+}
+
+impl Graveyard {
+    /// Appends `query` to the history of `key`
+    pub fn bury(&self, key: &str, query: &Query) {
+        let version = Version { key: key.to_string(), buried_at: Utc::now(), query: query.clone() };
+        let mut graveyard = match fs::OpenOptions::new().create(true).append(true).open(&self.filepath) {Ok(f)=>f, Err(e)=>panic!("🪦  Could not open graveyard file at {}, due to error:  ❌  {}", self.filepath.display(), e)};
+        let line = serde_json::to_string(&version).expect("Serialization of a buried query to the graveyard");
+        writeln!(graveyard, "{line}").expect("Write to graveyard file");
+    }
+
+    /// Every buried version, oldest first. Lines that can't be read back (such as the pretty-printed graveyards of older versions of this crate) are skipped.
+    pub fn all(&self) -> Vec<Version> {
+        let file = match fs::File::open(&self.filepath) {
+            Ok(f) => f,
+            Err(_) => return vec![],
+        };
+        let mut versions = vec![];
+        let mut skipped = 0;
+        for line in io::BufReader::new(file).lines() {
+            let line = match line {
+                Ok(line) => line,
+                Err(_) => { skipped += 1; continue },
+            };
+            if line.trim().is_empty() { continue }
+            match serde_json::from_str::<Version>(&line) {
+                Ok(version) => versions.push(version),
+                Err(_) => skipped += 1,
+            }
+        }
+        if skipped > 0 {
+            println!("🪦  Skipped {skipped} unreadable lines in graveyard file {}", self.filepath.display());
+        }
+        versions
+    }
+
+    /// Prior versions of the query at `key`, oldest first. Indices into this list are what `diff()` and `Cache::restore()` take.
+    pub fn versions(&self, key: &str) -> Vec<Version> {
+        self.all().into_iter().filter(|v| v.key == key).collect()
+    }
+
+    /// Line diff of the response content of two versions of `key`, from `older` to `newer`
+    pub fn diff(&self, key: &str, older: usize, newer: usize) -> Option<Vec<DiffLine>> {
+        let versions = self.versions(key);
+        let older = versions.get(older)?.query.content().unwrap_or_default();
+        let newer = versions.get(newer)?.query.content().unwrap_or_default();
+        Some(diff_lines(&older, &newer))
+    }
+
+    /// Empties the graveyard file. History is otherwise never discarded.
+    pub fn clear(&self) {
+        let _graveyard = match fs::OpenOptions::new().create(true).truncate(true).write(true).open(&self.filepath) {Ok(f)=>f, Err(e)=>panic!("🪦  Could not clear graveyard file at {}, due to error:  ❌  {}", self.filepath.display(), e)};
+        println!("🪦  Graveyard cleared at: {}", self.filepath.display());
+    }
+}
+
+/// Line diff by longest common subsequence
+pub fn diff_lines(old: &str, new: &str) -> Vec<DiffLine> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+
+    // lcs[i][j] is the length of the longest common subsequence of old[i..] and new[j..]
+    let mut lcs = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] { lcs[i + 1][j + 1] + 1 } else { lcs[i + 1][j].max(lcs[i][j + 1]) };
+        }
+    }
+
+    let mut diff = vec![];
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() && j < new.len() {
+        if old[i] == new[j] {
+            diff.push(DiffLine::Same(old[i].to_string()));
+            i += 1;
+            j += 1;
+        } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+            diff.push(DiffLine::Removed(old[i].to_string()));
+            i += 1;
+        } else {
+            diff.push(DiffLine::Added(new[j].to_string()));
+            j += 1;
+        }
+    }
+    diff.extend(old[i..].iter().map(|line| DiffLine::Removed(line.to_string())));
+    diff.extend(new[j..].iter().map(|line| DiffLine::Added(line.to_string())));
+    diff
+}
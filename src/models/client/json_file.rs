@@ -0,0 +1,23 @@
+use std::{
+    fs,
+    io,
+    path::Path,
+};
+use serde::{de::DeserializeOwned, Serialize};
+
+use super::core::Status;
+
+/// Reads the JSON file at `filepath`, or `T::default()` if there is none. Errors if it can't be read or parsed, rather than start empty and lose what it held on the next save.
+pub(crate) fn load_json<T: DeserializeOwned + Default>(filepath: &Path) -> Result<T, Status> {
+    match fs::File::open(filepath) {
+        Ok(f) => serde_json::from_reader(io::BufReader::new(f)).map_err(|e| Status::Error(format!("{}: {e}", filepath.display()))),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(T::default()),
+        Err(e) => Err(Status::Error(format!("{}: {e}", filepath.display()))),
+    }
+}
+
+/// Writes `value` to `filepath` as pretty JSON
+pub(crate) fn save_json<T: Serialize>(value: &T, filepath: &Path) -> Result<(), Status> {
+    let json = serde_json::to_string_pretty(value).map_err(|e| Status::Error(e.to_string()))?;
+    fs::write(filepath, json).map_err(|e| Status::Error(format!("{}: {e}", filepath.display())))
+}
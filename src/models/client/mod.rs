@@ -2,4 +2,16 @@ pub mod core;
 pub mod database;
 pub mod completion;
 pub mod requests;
-pub mod graveyard;
\ No newline at end of file
+pub mod graveyard;
+pub mod semantic;
+pub mod sync;
+pub mod db_search;
+pub mod db_delete;
+pub mod directory;
+pub mod batch;
+pub mod batch_job;
+pub mod files;
+pub mod document_records;
+pub mod battery;
+pub mod full_text;
+pub mod json_file;
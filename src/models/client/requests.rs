@@ -1,42 +1,131 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
 use reqwest::Response;
 
 use crate::{
     models::{
-        client::core::OpenAIAccount, 
-        api_error::APIError, 
-        ChatCompletionRequest, 
-        ChatCompletionResponse
+        client::core::OpenAIAccount,
+        api_error::APIError,
+        ChatCompletionRequest,
+        ChatCompletionResponse,
+        embeddings::{EmbeddingRequest, EmbeddingResponse},
     },
     constants::API_URL_V1,
 };
 
+/// Most requests to send to OpenAI per minute, across every request of the client, batches included
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimit {
+    pub requests_per_minute: u32,
+}
 
-impl OpenAIAccount {
+/// Spaces requests evenly to keep under a `RateLimit`. Shared by every request in flight.
+#[derive(Debug)]
+pub(crate) struct RateLimiter {
+    interval: Duration,
+    /// When the next request may be sent
+    next: Mutex<Instant>,
+}
 
+impl RateLimiter {
+    pub(crate) fn new(limit: RateLimit) -> Self {
+        RateLimiter { interval: Duration::from_secs(60) / limit.requests_per_minute.max(1), next: Mutex::new(Instant::now()) }
+    }
+
+    /// Waits for this request's turn
+    pub(crate) async fn acquire(&self) {
+        let wait = {
+            let mut next = self.next.lock().expect("rate limiter lock");
+            let now = Instant::now();
+            let turn = (*next).max(now);
+            *next = turn + self.interval;
+            turn - now
+        };
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+/// What a request needs from the account. Cheap to clone, so that many requests can be in flight while the account itself is borrowed to record their results.
+#[derive(Debug, Clone)]
+pub(super) struct Sender {
+    api_key: String,
+    rate_limiter: Option<Arc<RateLimiter>>,
+}
+
+impl Sender {
     pub(super) async fn send_completion_request(&self, req: ChatCompletionRequest) -> Result<ChatCompletionResponse, APIError> {
         let res = self.post("/chat/completions", &req).await?;
         let r = res.json::<ChatCompletionResponse>().await;
-        match r { Ok(r) => Ok(r), Err(e) => Err(self.new_error(e)) }
+        match r { Ok(r) => Ok(r), Err(e) => Err(new_error(e)) }
     }
 
-    fn new_error(&self, err: reqwest::Error) -> APIError {
-        APIError { message: err.to_string() }
+    pub(super) async fn send_embedding_request(&self, req: EmbeddingRequest) -> Result<EmbeddingResponse, APIError> {
+        let res = self.post("/embeddings", &req).await?;
+        let r = res.json::<EmbeddingResponse>().await;
+        match r { Ok(r) => Ok(r), Err(e) => Err(new_error(e)) }
     }
 
-    pub async fn post<T: serde::ser::Serialize>(&self, path: &str, params: &T) -> Result<Response, APIError> {
-        let client = reqwest::Client::new();
-        let url = format!("{API_URL_V1}{path}");
-        let res = client
-            .post(&url)
+    async fn post<T: serde::ser::Serialize>(&self, path: &str, params: &T) -> Result<Response, APIError> {
+        let req = reqwest::Client::new()
+            .post(format!("{API_URL_V1}{path}"))
             .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .json(&params);
+        self.send(req).await
+    }
+
+    pub(super) async fn post_multipart(&self, path: &str, form: reqwest::multipart::Form) -> Result<Response, APIError> {
+        self.send(reqwest::Client::new().post(format!("{API_URL_V1}{path}")).multipart(form)).await
+    }
+
+    pub(super) async fn get(&self, path: &str) -> Result<Response, APIError> {
+        self.send(reqwest::Client::new().get(format!("{API_URL_V1}{path}"))).await
+    }
+
+    pub(super) async fn delete(&self, path: &str) -> Result<Response, APIError> {
+        self.send(reqwest::Client::new().delete(format!("{API_URL_V1}{path}"))).await
+    }
+
+    /// Authorizes and sends `req` once the rate limit allows, erroring on a non-success status
+    async fn send(&self, req: reqwest::RequestBuilder) -> Result<Response, APIError> {
+        if let Some(rate_limiter) = &self.rate_limiter {
+            rate_limiter.acquire().await;
+        }
+        let res = req
             .header(reqwest::header::AUTHORIZATION, "Bearer ".to_owned() + &self.api_key)
-            .json(&params)
             .send()
             .await;
         match res {
-            Ok(res) => match res.status().is_success() { true => Ok(res), false => Err(APIError { message: format!(  "{}: {}", res.status(), res.text().await.unwrap()   ) })  }, 
-            Err(e) => Err(self.new_error(e)),
+            Ok(res) => match res.status().is_success() { true => Ok(res), false => Err(APIError { message: format!(  "{}: {}", res.status(), res.text().await.unwrap()   ) })  },
+            Err(e) => Err(new_error(e)),
         }
     }
-    
-}
\ No newline at end of file
+}
+
+pub(super) fn new_error(err: reqwest::Error) -> APIError {
+    APIError { message: err.to_string() }
+}
+
+
+impl OpenAIAccount {
+
+    pub(super) fn sender(&self) -> Sender {
+        Sender { api_key: self.api_key.clone(), rate_limiter: self.rate_limiter.clone() }
+    }
+
+    pub(super) async fn send_completion_request(&self, req: ChatCompletionRequest) -> Result<ChatCompletionResponse, APIError> {
+        self.sender().send_completion_request(req).await
+    }
+
+    pub(super) async fn send_embedding_request(&self, req: EmbeddingRequest) -> Result<EmbeddingResponse, APIError> {
+        self.sender().send_embedding_request(req).await
+    }
+
+    pub async fn post<T: serde::ser::Serialize>(&self, path: &str, params: &T) -> Result<Response, APIError> {
+        self.sender().post(path, params).await
+    }
+
+}
@@ -0,0 +1,82 @@
+use crate::{
+    models::{
+        client::core::{OpenAIAccount, Status},
+        embeddings::EmbeddingRequest,
+    },
+    Query,
+    QueryKind,
+};
+
+/// Inputs per embeddings request when indexing the cache
+const EMBEDDING_BATCH_SIZE: usize = 100;
+
+impl OpenAIAccount {
+    /// Embeds each of `inputs`, in order. The cost is added to the bill.
+    pub async fn get_embeddings(&mut self, inputs: Vec<String>) -> Result<Vec<Vec<f32>>, Status> {
+        let res = self.send_embedding_request(EmbeddingRequest::new(inputs)).await.map_err(|e| Status::Error(e.to_string()))?;
+        self.bill.update_embeddings(&res);
+        let mut data = res.data;
+        data.sort_by_key(|embedding| embedding.index);
+        Ok(data.into_iter().map(|embedding| embedding.embedding).collect())
+    }
+
+    /// Looks for a cached query with a near-duplicate of `prompt`, when `Opts::semantic` is set: first by normalized text, then by embedding similarity.
+    /// <br> Returns the hit with its similarity, and the embedding of `prompt` if one was requested, to be stored with a fresh completion on a miss.
+    /// <br> A failed embeddings request is reported and treated as a miss, as is a hit that `accept` rejects. See `Cache::get_if()`
+    pub(super) async fn semantic_lookup(&mut self, prompt: &str, document_title: Option<&str>, accept: impl Fn(&Query) -> bool) -> (Option<(Query, f32)>, Option<Vec<f32>>) {
+        let index = match &self.semantic { Some(index) => index, None => return (None, None) };
+
+        if let Some(key) = index.normalized_match(&self.cache, prompt, document_title) {
+            println!("--[Normalized prompt matched cache key: \"{key}\"]--");
+            return (self.cache.get_if(&key, &accept).map(|query| (query, 1.0)), None)
+        }
+        if !index.opts.use_embeddings { return (None, None) }
+
+        let embedding = match self.get_embeddings(vec![prompt.to_string()]).await {
+            Ok(mut embeddings) if !embeddings.is_empty() => embeddings.remove(0),
+            Ok(_) => return (None, None),
+            Err(e) => {
+                println!("🧭 Semantic lookup skipped, embedding failed:  ❌  {e:?}");
+                return (None, None)
+            },
+        };
+
+        let nearest = self.semantic.as_ref().and_then(|index| index.nearest(&self.cache, &embedding, document_title));
+        match nearest {
+            Some((key, similarity)) => {
+                println!("--[Similar prompt ({similarity:.3}) matched cache key: \"{key}\"]--");
+                (self.cache.get_if(&key, &accept).map(|query| (query, similarity)), Some(embedding))
+            },
+            None => (None, Some(embedding)),
+        }
+    }
+
+    /// Stores the prompt embedding of a freshly cached query, so later near-duplicates can match it
+    pub(super) fn semantic_remember(&mut self, cache_key: String, embedding: Option<Vec<f32>>) {
+        if let (Some(index), Some(embedding)) = (self.semantic.as_mut(), embedding) {
+            index.insert(cache_key, embedding);
+        }
+    }
+
+    /// Embeds the prompts of cached chat and text queries that have no embedding yet, so that the semantic cache can match them. Returns how many were added.
+    pub async fn index_cache_embeddings(&mut self) -> Result<usize, Status> {
+        let index = match &self.semantic { Some(index) => index, None => return Ok(0) };
+
+        let missing: Vec<(String, String)> = self.cache.entries.iter()
+            .filter(|(key, query)| matches!(query.kind(), QueryKind::Chat | QueryKind::Text) && !index.embeddings.contains_key(*key))
+            .map(|(key, query)| (key.clone(), query.prompt().to_string()))
+            .collect();
+
+        println!("🧭 Embedding {} cached prompts...", missing.len());
+        for batch in missing.chunks(EMBEDDING_BATCH_SIZE) {
+            let embeddings = self.get_embeddings(batch.iter().map(|(_, prompt)| prompt.clone()).collect()).await?;
+            if let Some(index) = self.semantic.as_mut() {
+                for ((key, _), embedding) in batch.iter().zip(embeddings) {
+                    index.embeddings.insert(key.clone(), embedding);
+                }
+                index.save();
+            }
+        }
+        Ok(missing.len())
+    }
+}
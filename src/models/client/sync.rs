@@ -0,0 +1,174 @@
+use std::{
+    collections::HashMap,
+    path::Path,
+};
+use chrono::{DateTime, Utc};
+use sea_orm::{EntityTrait, QueryFilter, ColumnTrait};
+use serde::{Serialize, Deserialize};
+
+use crate::{
+    models::{
+        archive::ConflictPolicy,
+        cache::Cache,
+        db::{completions::Column, prelude::Completions},
+    },
+    Query,
+};
+use super::{
+    core::{OpenAIAccount, Status},
+    database::{same_row, DbMethods, DEFAULT_UPSERT_CHUNK_SIZE},
+    json_file::{load_json, save_json},
+};
+
+/// When a cache was last synced with the database. Kept in its own file next to the cache, as it belongs to that one cache.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+pub struct SyncWatermark {
+    /// `None` if never synced, in which case the next sync moves everything
+    pub last_synced_at: Option<DateTime<Utc>>,
+    /// The `timestamp` of the rows the last sync pushed, if it pushed any. They are saved after `last_synced_at`, but are what the cache already holds, so the next sync doesn't pull them back.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pushed_at: Option<DateTime<Utc>>,
+}
+
+impl SyncWatermark {
+    /// Reads the watermark file, or starts from never synced if it is absent. See `load_json()`
+    pub fn load(filepath: &Path) -> Result<SyncWatermark, Status> {
+        load_json(filepath)
+    }
+
+    pub fn save(&self, filepath: &Path) -> Result<(), Status> {
+        save_json(self, filepath)
+    }
+}
+
+/// Which side won a conflict
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyncResolution {
+    /// The cache entry replaced the row, which went to the graveyard
+    Pushed,
+    /// The row replaced the cache entry, which went to the graveyard
+    Pulled,
+}
+
+/// A key that changed both in the cache and in the database since the last sync, to different rows
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyncConflict {
+    pub key: String,
+    pub local_updated_at: DateTime<Utc>,
+    pub remote_updated_at: DateTime<Utc>,
+    pub resolution: SyncResolution,
+}
+
+/// What moved during a sync
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct SyncReport {
+    /// The watermark the sync started from
+    pub since: Option<DateTime<Utc>>,
+    /// The new watermark
+    pub synced_at: DateTime<Utc>,
+    /// Keys written to the database, conflicts won by the cache included. Entries the database already held as they are were not written, and are left out.
+    pub pushed: Vec<String>,
+    /// Keys written to the cache, conflicts won by the database included
+    pub pulled: Vec<String>,
+    pub conflicts: Vec<SyncConflict>,
+}
+
+impl SyncReport {
+    pub fn print(&self) {
+        println!("\n");
+        println!("🗄️  Sync since {}", self.since.map(|since| since.to_rfc3339()).unwrap_or("the beginning".to_string()));
+        println!("Pushed: {}", self.pushed.len());
+        println!("Pulled: {}", self.pulled.len());
+        println!("Conflicts: {}", self.conflicts.len());
+        for conflict in &self.conflicts {
+            println!("  {:?} \"{}\"", conflict.resolution, conflict.key);
+        }
+        println!("\n");
+    }
+}
+
+impl DbMethods {
+    /// Pushes cache entries updated since the watermark stored at `watermark_filepath`, and pulls rows saved since then, then moves the watermark up.
+    /// <br> Keys changed on both sides to different rows are resolved by `policy`: `KeepMine` and `Graveyard` push the cache entry, `TakeTheirs` pulls the row, and `KeepNewest` takes whichever was edited later, going by `updated_at`. The losing version goes to the cache's graveyard.
+    /// <br> Only the changed rows are read, by the indexed `timestamp` column, so regular syncs stay cheap on large tables.
+    pub async fn sync(&self, cache: &mut Cache, watermark_filepath: &Path, policy: ConflictPolicy) -> Result<SyncReport, Status> {
+        println!("🗄️  Syncing cache with database...");
+        let SyncWatermark { last_synced_at: since, pushed_at } = SyncWatermark::load(watermark_filepath)?;
+        // Taken before reading either side, so that nothing saved while syncing is skipped next time
+        let synced_at = Utc::now();
+
+        let local: Vec<(&String, &Query)> = cache.entries.iter()
+            .filter(|(_, query)| since.is_none_or(|since| query.updated_at() > since))
+            .collect();
+
+        let mut find = Completions::find();
+        if let Some(since) = since {
+            find = find.filter(Column::Timestamp.gt(since.naive_utc()));
+        }
+        if let Some(pushed_at) = pushed_at {
+            find = find.filter(Column::Timestamp.ne(pushed_at.naive_utc()));
+        }
+        let mut remote: HashMap<String, Query> = HashMap::new();
+        for model in find.all(self.conn()?).await.map_err(|e| Status::Error(e.to_string()))? {
+            remote.insert(model.query_key.clone(), model.to_query()?);
+        }
+
+        let mut report = SyncReport { since, synced_at, ..Default::default() };
+        let mut to_push: Vec<(&String, &Query)> = vec![];
+        let mut to_pull: Vec<(String, Query)> = vec![];
+
+        for (key, mine) in local {
+            let theirs = match remote.remove(key) {
+                None => { to_push.push((key, mine)); continue },
+                Some(theirs) => theirs,
+            };
+            if same_row(key, &theirs, mine) { continue }
+
+            let resolution = match policy {
+                ConflictPolicy::KeepMine | ConflictPolicy::Graveyard => SyncResolution::Pushed,
+                ConflictPolicy::TakeTheirs => SyncResolution::Pulled,
+                ConflictPolicy::KeepNewest => if theirs.updated_at() > mine.updated_at() { SyncResolution::Pulled } else { SyncResolution::Pushed },
+            };
+            report.conflicts.push(SyncConflict {
+                key: key.clone(),
+                local_updated_at: mine.updated_at(),
+                remote_updated_at: theirs.updated_at(),
+                resolution,
+            });
+            match resolution {
+                SyncResolution::Pushed => to_push.push((key, mine)),
+                SyncResolution::Pulled => to_pull.push((key.clone(), theirs)),
+            }
+        }
+        // Rows changed only in the database
+        to_pull.extend(remote);
+
+        let upserted = self.upsert_entries(to_push, &cache.graveyard, DEFAULT_UPSERT_CHUNK_SIZE).await?;
+        let pushed_at = (!upserted.written.is_empty()).then_some(upserted.written_at);
+        report.pushed = upserted.written;
+
+        for (key, theirs) in to_pull {
+            // Such as a row another cache saved as this one already holds it
+            if cache.entries.get(&key).is_some_and(|mine| same_row(&key, &theirs, mine)) { continue }
+            if let Some(mine) = cache.entries.insert(key.clone(), theirs) {
+                cache.graveyard.bury(&key, &mine);
+            }
+            report.pulled.push(key);
+        }
+        if !report.pulled.is_empty() {
+            cache.evict_entries();
+            cache.save();
+        }
+
+        SyncWatermark { last_synced_at: Some(synced_at), pushed_at }.save(watermark_filepath)?;
+        println!("🗄️  Sync done. Pushed: {}, pulled: {}, conflicts: {}", report.pushed.len(), report.pulled.len(), report.conflicts.len());
+        Ok(report)
+    }
+}
+
+impl OpenAIAccount {
+    /// `DbMethods::sync()` of this client's cache, with the watermark at `Opts::sync_filepath`
+    pub async fn sync(&mut self, policy: ConflictPolicy) -> Result<SyncReport, Status> {
+        self.db.sync(&mut self.cache, &self.sync_filepath, policy).await
+    }
+}
@@ -1,21 +1,24 @@
-//! `SeaORM` Entity. Generated by sea-orm-codegen 0.12.4
+//! `SeaORM` Entity for the `completions` table, which holds queries of every kind. See `QueryKind::as_str()` for the values of `kind`
 
 use sea_orm::entity::prelude::*;
 use serde::{Deserialize, Serialize};
 
 #[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
-#[sea_orm(table_name = "text_completions")]
+#[sea_orm(table_name = "completions")]
 pub struct Model {
     #[sea_orm(primary_key)]
     pub rid: i32,
+    pub kind: String,
+    /// When the row was last written, in UTC
     pub timestamp: DateTime,
     pub model: String,
     #[sea_orm(column_type = "Double")]
     pub temperature: f64,
     #[sea_orm(column_type = "Text")]
     pub prompt: String,
-    #[sea_orm(column_type = "Text")]
-    pub document_title: String,
+    /// Only set for text queries
+    #[sea_orm(column_type = "Text", nullable)]
+    pub document_title: Option<String>,
     #[sea_orm(column_type = "Text")]
     pub query_key: String,
     #[sea_orm(unique)]
@@ -24,8 +27,14 @@ pub struct Model {
     pub completion_tokens: i32,
     pub total_tokens: i32,
     pub process_time: i32,
-    #[sea_orm(column_type = "JsonBinary")]
+    #[sea_orm(column_type = "Json")]
     pub response: Json,
+    /// The messages that were sent, when they were kept
+    #[sea_orm(column_type = "Json", nullable)]
+    pub request: Option<Json>,
+    /// Query-level details that have no column of their own, such as when the query was created
+    #[sea_orm(column_type = "Json", nullable)]
+    pub metadata: Option<Json>,
     #[sea_orm(column_type = "Double")]
     pub cost: f64,
 }
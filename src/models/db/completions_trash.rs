@@ -0,0 +1,24 @@
+//! `SeaORM` Entity for the `completions_trash` table, which holds soft-deleted rows of `completions` until they are restored or purged
+
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "completions_trash")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub rid: i32,
+    pub kind: String,
+    #[sea_orm(column_type = "Text")]
+    pub query_key: String,
+    pub query_key_hash: String,
+    pub deleted_at: DateTime,
+    /// The trashed `completions::Model`
+    #[sea_orm(column_type = "Json")]
+    pub row: Json,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -1,46 +1,118 @@
 use super::*;
-use crate::models::queries::*;
-use crate::GptModel;
+use crate::models::{queries::*, client::core::Status, hash::query_key_hash, labels::Labels, documents::{Citation, PdfOpts}};
+use crate::{GptModel, Query, QueryKind};
+use chrono::{DateTime, Utc};
+use sea_orm::ActiveValue;
+use serde::{Serialize, Deserialize};
 
-impl chat_completions::Model {
-    pub fn to_query(self) -> ChatQuery {
-        ChatQuery { 
-            prompt: self.prompt.clone(), 
-            cost: self.cost as f32, 
-            response: serde_json::from_value(self.response).unwrap(), 
-            process_time: self.process_time as u64, 
-            model: GptModel::from_string(&self.model), 
-            temperature: self.temperature as f32,
-            from_cache: true, 
-        }
-    }
+/// What is kept in the `metadata` column of a completion
+#[derive(Debug, Serialize, Deserialize, Default)]
+struct Metadata {
+    /// When the query was first cached. The `timestamp` column is when it was last saved to the database.
+    #[serde(default)]
+    created_at: Option<DateTime<Utc>>,
+    /// When the query last changed in the cache it was saved from. Unlike `timestamp`, this is carried across syncs, so that the newer of two edits can be told apart.
+    #[serde(default)]
+    updated_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Labels::is_empty")]
+    labels: Labels,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    citations: Vec<Citation>,
+    #[serde(default, skip_serializing_if = "PdfOpts::is_default")]
+    pdf: PdfOpts,
+    #[serde(default)]
+    tokens_saved: usize,
 }
 
-impl text_completions::Model {
-    pub fn to_query(self) -> TextQuery {
-        TextQuery { 
-            prompt: self.prompt,
-            document_title: self.document_title,
-            response: serde_json::from_value(self.response).unwrap(), 
-            process_time: self.process_time as u64, 
-            model: GptModel::from_string(&self.model), 
-            temperature: self.temperature as f32,
-            cost: self.cost as f32, 
-            from_cache: true, 
-        }
+impl completions::Model {
+    /// Errors if `kind` is not one of `QueryKind::as_str()`, or the stored response can't be read
+    pub fn to_query(self) -> Result<Query, Status> {
+        let kind = QueryKind::from_string(&self.kind).ok_or(Status::Error(format!("Unknown query kind '{}' in row {}", self.kind, self.rid)))?;
+        let response = serde_json::from_value(self.response).map_err(|e| Status::Error(e.to_string()))?;
+        let messages = self.request.and_then(|request| serde_json::from_value(request).ok()).unwrap_or_default();
+        let metadata: Metadata = self.metadata.and_then(|metadata| serde_json::from_value(metadata).ok()).unwrap_or_default();
+        let created_at = metadata.created_at.unwrap_or(self.timestamp.and_utc());
+        let updated_at = metadata.updated_at.unwrap_or(self.timestamp.and_utc());
+
+        let query = match kind {
+            QueryKind::Chat => Query::ChatQuery(ChatQuery {
+                prompt: self.prompt,
+                cost: self.cost as f32,
+                response,
+                process_time: self.process_time as u64,
+                model: GptModel::from_string(&self.model),
+                temperature: self.temperature as f32,
+                from_cache: true,
+                similarity: None,
+                created_at,
+                last_accessed: Utc::now(),
+                updated_at,
+                messages,
+                labels: metadata.labels,
+            }),
+            QueryKind::Text => Query::TextQuery(TextQuery {
+                prompt: self.prompt,
+                document_title: self.document_title.unwrap_or_default(),
+                response,
+                process_time: self.process_time as u64,
+                model: GptModel::from_string(&self.model),
+                temperature: self.temperature as f32,
+                cost: self.cost as f32,
+                from_cache: true,
+                similarity: None,
+                created_at,
+                last_accessed: Utc::now(),
+                updated_at,
+                messages,
+                labels: metadata.labels,
+                citations: metadata.citations,
+                pdf: metadata.pdf,
+                tokens_saved: metadata.tokens_saved,
+            }),
+            QueryKind::Meta => Query::MetaQuery(MetaQuery {
+                prompt: self.prompt,
+                cost: self.cost as f32,
+                response,
+                process_time: self.process_time as u64,
+                model: GptModel::from_string(&self.model),
+                temperature: self.temperature as f32,
+                from_cache: true,
+                similarity: None,
+                created_at,
+                last_accessed: Utc::now(),
+                updated_at,
+                messages,
+                labels: metadata.labels,
+            }),
+        };
+        Ok(query)
     }
 }
 
-impl meta_completions::Model {
-    pub fn to_query(self) -> MetaQuery {
-        MetaQuery { 
-            prompt: self.prompt, 
-            cost: self.cost as f32, 
-            response: serde_json::from_value(self.response).unwrap(), 
-            process_time: self.process_time as u64, 
-            model: GptModel::from_string(&self.model), 
-            temperature: self.temperature as f32,
-            from_cache: true, 
+impl completions::ActiveModel {
+    /// A new row for `query`, stored under `cache_key`
+    pub fn from_query(cache_key: &str, query: &Query) -> completions::ActiveModel {
+        let response = query.response_ref();
+        let messages = query.messages();
+        let metadata = Metadata { created_at: Some(query.created_at()), updated_at: Some(query.updated_at()), labels: query.labels().clone(), citations: query.citations().to_vec(), pdf: query.pdf(), tokens_saved: query.tokens_saved() };
+        completions::ActiveModel {
+            rid: ActiveValue::NotSet,
+            kind: ActiveValue::Set(query.kind().as_str().to_string()),
+            timestamp: ActiveValue::Set(Utc::now().naive_utc()),
+            model: ActiveValue::Set(query.model().to_string()),
+            temperature: ActiveValue::Set(query.temperature() as f64),
+            prompt: ActiveValue::Set(query.prompt().to_string()),
+            document_title: ActiveValue::Set(query.document_title().map(str::to_string)),
+            query_key: ActiveValue::Set(cache_key.to_string()),
+            query_key_hash: ActiveValue::Set(query_key_hash(cache_key)),
+            prompt_tokens: ActiveValue::Set(response.usage.prompt_tokens),
+            completion_tokens: ActiveValue::Set(response.usage.completion_tokens),
+            total_tokens: ActiveValue::Set(response.usage.total_tokens),
+            process_time: ActiveValue::Set(query.process_time() as i32),
+            response: ActiveValue::Set(serde_json::to_value(response).expect("conversion to JSON value of query.response")),
+            request: ActiveValue::Set(if messages.is_empty() { None } else { Some(serde_json::to_value(messages).expect("conversion to JSON value of query.messages")) }),
+            metadata: ActiveValue::Set(Some(serde_json::to_value(metadata).expect("conversion to JSON value of query metadata"))),
+            cost: ActiveValue::Set(query.cost() as f64),
         }
     }
-}
\ No newline at end of file
+}
@@ -1,9 +1,13 @@
-CREATE TABLE chat_completions (
+/* Reference only. The schema is created and upgraded by the migrations in `migration/`, see `Opts::run_migrations` */
+
+CREATE TABLE completions (
     rid serial PRIMARY KEY,
+    kind varchar(16) NOT NULL,
     timestamp TIMESTAMP NOT NULL,
     model varchar(45) NOT NULL,
     temperature float NOT NULL,
     prompt TEXT NOT NULL,
+    document_title TEXT,
     query_key text NOT NULL,
     query_key_hash char(64) NOT NULL,
     prompt_tokens int NOT NULL,
@@ -11,6 +15,8 @@ CREATE TABLE chat_completions (
     total_tokens int NOT NULL,
     process_time int NOT NULL,
     response jsonb NOT NULL,
+    request jsonb,
+    metadata jsonb,
     cost float NOT NULL,
     CONSTRAINT query_key_hash_unique UNIQUE (query_key_hash)
 );
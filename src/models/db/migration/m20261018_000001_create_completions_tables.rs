@@ -0,0 +1,50 @@
+use sea_orm_migration::prelude::*;
+
+use sea_orm_migration::sea_orm::DatabaseBackend;
+
+use super::{json_column, ChatCompletions, CompletionsColumn, MetaCompletions, TextCompletions};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+/// `if_not_exists`, so that databases made by hand from `db.sql` are adopted rather than failing
+pub(super) fn completions_table<T: IntoIden + 'static>(backend: DatabaseBackend, table: T, with_document_title: bool) -> TableCreateStatement {
+    let mut statement = Table::create();
+    statement
+        .table(table)
+        .if_not_exists()
+        .col(ColumnDef::new(CompletionsColumn::Rid).integer().not_null().auto_increment().primary_key())
+        .col(ColumnDef::new(CompletionsColumn::Timestamp).timestamp().not_null())
+        .col(ColumnDef::new(CompletionsColumn::Model).string_len(45).not_null())
+        .col(ColumnDef::new(CompletionsColumn::Temperature).double().not_null())
+        .col(ColumnDef::new(CompletionsColumn::Prompt).text().not_null());
+    if with_document_title {
+        statement.col(ColumnDef::new(CompletionsColumn::DocumentTitle).text().not_null());
+    }
+    statement
+        .col(ColumnDef::new(CompletionsColumn::QueryKey).text().not_null())
+        .col(ColumnDef::new(CompletionsColumn::QueryKeyHash).string_len(64).not_null().unique_key())
+        .col(ColumnDef::new(CompletionsColumn::PromptTokens).integer().not_null())
+        .col(ColumnDef::new(CompletionsColumn::CompletionTokens).integer().not_null())
+        .col(ColumnDef::new(CompletionsColumn::TotalTokens).integer().not_null())
+        .col(ColumnDef::new(CompletionsColumn::ProcessTime).integer().not_null())
+        .col(json_column(backend, CompletionsColumn::Response).not_null())
+        .col(ColumnDef::new(CompletionsColumn::Cost).double().not_null())
+        .to_owned()
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let backend = manager.get_database_backend();
+        manager.create_table(completions_table(backend, ChatCompletions::Table, false)).await?;
+        manager.create_table(completions_table(backend, TextCompletions::Table, true)).await?;
+        manager.create_table(completions_table(backend, MetaCompletions::Table, false)).await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager.drop_table(Table::drop().table(ChatCompletions::Table).if_exists().to_owned()).await?;
+        manager.drop_table(Table::drop().table(TextCompletions::Table).if_exists().to_owned()).await?;
+        manager.drop_table(Table::drop().table(MetaCompletions::Table).if_exists().to_owned()).await
+    }
+}
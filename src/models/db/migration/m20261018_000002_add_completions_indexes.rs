@@ -0,0 +1,23 @@
+use sea_orm_migration::prelude::*;
+
+use super::{ChatCompletions, CompletionsColumn, MetaCompletions, TextCompletions};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager.create_index(Index::create().if_not_exists().name("idx_chat_completions_timestamp").table(ChatCompletions::Table).col(CompletionsColumn::Timestamp).to_owned()).await?;
+        manager.create_index(Index::create().if_not_exists().name("idx_text_completions_timestamp").table(TextCompletions::Table).col(CompletionsColumn::Timestamp).to_owned()).await?;
+        manager.create_index(Index::create().if_not_exists().name("idx_meta_completions_timestamp").table(MetaCompletions::Table).col(CompletionsColumn::Timestamp).to_owned()).await?;
+        manager.create_index(Index::create().if_not_exists().name("idx_text_completions_document_title").table(TextCompletions::Table).col(CompletionsColumn::DocumentTitle).to_owned()).await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager.drop_index(Index::drop().if_exists().name("idx_chat_completions_timestamp").table(ChatCompletions::Table).to_owned()).await?;
+        manager.drop_index(Index::drop().if_exists().name("idx_text_completions_timestamp").table(TextCompletions::Table).to_owned()).await?;
+        manager.drop_index(Index::drop().if_exists().name("idx_meta_completions_timestamp").table(MetaCompletions::Table).to_owned()).await?;
+        manager.drop_index(Index::drop().if_exists().name("idx_text_completions_document_title").table(TextCompletions::Table).to_owned()).await
+    }
+}
@@ -0,0 +1,119 @@
+use sea_orm_migration::prelude::*;
+
+use super::{json_column, m20261018_000001_create_completions_tables::completions_table, m20261018_000002_add_completions_indexes, ChatCompletions, Completions, CompletionsColumn, MetaCompletions, TextCompletions};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+/// Columns that every per-kind table shares with `completions`, in the order they are copied
+fn shared_columns() -> Vec<CompletionsColumn> {
+    vec![
+        CompletionsColumn::Timestamp,
+        CompletionsColumn::Model,
+        CompletionsColumn::Temperature,
+        CompletionsColumn::Prompt,
+        CompletionsColumn::QueryKey,
+        CompletionsColumn::QueryKeyHash,
+        CompletionsColumn::PromptTokens,
+        CompletionsColumn::CompletionTokens,
+        CompletionsColumn::TotalTokens,
+        CompletionsColumn::ProcessTime,
+        CompletionsColumn::Response,
+        CompletionsColumn::Cost,
+    ]
+}
+
+/// `INSERT INTO completions (...) SELECT ..., '<kind>' FROM <table>`
+fn copy_into_completions<T: IntoIden + 'static>(table: T, kind: &str, with_document_title: bool) -> InsertStatement {
+    let mut columns = shared_columns();
+    if with_document_title { columns.push(CompletionsColumn::DocumentTitle) }
+    let select = Query::select()
+        .columns(shared_columns())
+        .columns(if with_document_title { vec![CompletionsColumn::DocumentTitle] } else { vec![] })
+        // Inlined rather than bound, since Postgres can't infer the type of a bound parameter in a select list
+        .expr(SimpleExpr::Constant(kind.into()))
+        .from(table)
+        .to_owned();
+    columns.push(CompletionsColumn::Kind);
+    Query::insert()
+        .into_table(Completions::Table)
+        .columns(columns)
+        .select_from(select)
+        .expect("as many selected as inserted columns")
+        .to_owned()
+}
+
+/// `INSERT INTO <table> (...) SELECT ... FROM completions WHERE kind = '<kind>'`
+fn copy_out_of_completions<T: IntoIden + 'static>(table: T, kind: &str, with_document_title: bool) -> InsertStatement {
+    let mut columns = shared_columns();
+    if with_document_title { columns.push(CompletionsColumn::DocumentTitle) }
+    let select = Query::select()
+        .columns(shared_columns())
+        .columns(if with_document_title { vec![CompletionsColumn::DocumentTitle] } else { vec![] })
+        .from(Completions::Table)
+        .and_where(Expr::col(CompletionsColumn::Kind).eq(kind))
+        .to_owned();
+    Query::insert()
+        .into_table(table)
+        .columns(columns)
+        .select_from(select)
+        .expect("as many selected as inserted columns")
+        .to_owned()
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    /// Creates `completions`, moves every row of the per-kind tables into it, then drops them
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let backend = manager.get_database_backend();
+        manager.create_table(
+            Table::create()
+                .table(Completions::Table)
+                .if_not_exists()
+                .col(ColumnDef::new(CompletionsColumn::Rid).integer().not_null().auto_increment().primary_key())
+                .col(ColumnDef::new(CompletionsColumn::Kind).string_len(16).not_null())
+                .col(ColumnDef::new(CompletionsColumn::Timestamp).timestamp().not_null())
+                .col(ColumnDef::new(CompletionsColumn::Model).string_len(45).not_null())
+                .col(ColumnDef::new(CompletionsColumn::Temperature).double().not_null())
+                .col(ColumnDef::new(CompletionsColumn::Prompt).text().not_null())
+                .col(ColumnDef::new(CompletionsColumn::DocumentTitle).text().null())
+                .col(ColumnDef::new(CompletionsColumn::QueryKey).text().not_null())
+                .col(ColumnDef::new(CompletionsColumn::QueryKeyHash).string_len(64).not_null().unique_key())
+                .col(ColumnDef::new(CompletionsColumn::PromptTokens).integer().not_null())
+                .col(ColumnDef::new(CompletionsColumn::CompletionTokens).integer().not_null())
+                .col(ColumnDef::new(CompletionsColumn::TotalTokens).integer().not_null())
+                .col(ColumnDef::new(CompletionsColumn::ProcessTime).integer().not_null())
+                .col(json_column(backend, CompletionsColumn::Response).not_null())
+                .col(json_column(backend, CompletionsColumn::Request).null())
+                .col(json_column(backend, CompletionsColumn::Metadata).null())
+                .col(ColumnDef::new(CompletionsColumn::Cost).double().not_null())
+                .to_owned()
+        ).await?;
+        manager.create_index(Index::create().if_not_exists().name("idx_completions_kind").table(Completions::Table).col(CompletionsColumn::Kind).to_owned()).await?;
+        manager.create_index(Index::create().if_not_exists().name("idx_completions_timestamp").table(Completions::Table).col(CompletionsColumn::Timestamp).to_owned()).await?;
+        manager.create_index(Index::create().if_not_exists().name("idx_completions_document_title").table(Completions::Table).col(CompletionsColumn::DocumentTitle).to_owned()).await?;
+
+        manager.exec_stmt(copy_into_completions(ChatCompletions::Table, "chat", false)).await?;
+        manager.exec_stmt(copy_into_completions(TextCompletions::Table, "text", true)).await?;
+        manager.exec_stmt(copy_into_completions(MetaCompletions::Table, "meta", false)).await?;
+
+        manager.drop_table(Table::drop().table(ChatCompletions::Table).to_owned()).await?;
+        manager.drop_table(Table::drop().table(TextCompletions::Table).to_owned()).await?;
+        manager.drop_table(Table::drop().table(MetaCompletions::Table).to_owned()).await
+    }
+
+    /// Splits `completions` back into the per-kind tables. `request` and `metadata` are lost.
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let backend = manager.get_database_backend();
+        manager.create_table(completions_table(backend, ChatCompletions::Table, false)).await?;
+        manager.create_table(completions_table(backend, TextCompletions::Table, true)).await?;
+        manager.create_table(completions_table(backend, MetaCompletions::Table, false)).await?;
+        m20261018_000002_add_completions_indexes::Migration.up(manager).await?;
+
+        manager.exec_stmt(copy_out_of_completions(ChatCompletions::Table, "chat", false)).await?;
+        manager.exec_stmt(copy_out_of_completions(TextCompletions::Table, "text", true)).await?;
+        manager.exec_stmt(copy_out_of_completions(MetaCompletions::Table, "meta", false)).await?;
+
+        manager.drop_table(Table::drop().table(Completions::Table).to_owned()).await
+    }
+}
@@ -0,0 +1,63 @@
+use sea_orm_migration::prelude::*;
+use sea_orm_migration::sea_orm::{ConnectionTrait, DatabaseBackend};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+/// A `search_vector` column generated from the prompt and response content, with a GIN index
+const POSTGRES_UP: &[&str] = &[
+    "ALTER TABLE completions ADD COLUMN search_vector tsvector GENERATED ALWAYS AS (
+        to_tsvector('english', prompt || ' ' || coalesce(response->'choices'->0->'message'->>'content', ''))
+    ) STORED",
+    "CREATE INDEX idx_completions_search_vector ON completions USING GIN (search_vector)",
+];
+const POSTGRES_DOWN: &[&str] = &[
+    "DROP INDEX IF EXISTS idx_completions_search_vector",
+    "ALTER TABLE completions DROP COLUMN IF EXISTS search_vector",
+];
+
+/// An FTS5 table over the prompt and response content, whose rowid is the `rid` of the completion, kept up to date by triggers
+const SQLITE_UP: &[&str] = &[
+    "CREATE VIRTUAL TABLE completions_fts USING fts5(prompt, content)",
+    "CREATE TRIGGER completions_fts_insert AFTER INSERT ON completions BEGIN
+        INSERT INTO completions_fts (rowid, prompt, content) VALUES (new.rid, new.prompt, json_extract(new.response, '$.choices[0].message.content'));
+    END",
+    "CREATE TRIGGER completions_fts_update AFTER UPDATE ON completions BEGIN
+        DELETE FROM completions_fts WHERE rowid = old.rid;
+        INSERT INTO completions_fts (rowid, prompt, content) VALUES (new.rid, new.prompt, json_extract(new.response, '$.choices[0].message.content'));
+    END",
+    "CREATE TRIGGER completions_fts_delete AFTER DELETE ON completions BEGIN
+        DELETE FROM completions_fts WHERE rowid = old.rid;
+    END",
+    "INSERT INTO completions_fts (rowid, prompt, content) SELECT rid, prompt, json_extract(response, '$.choices[0].message.content') FROM completions",
+];
+const SQLITE_DOWN: &[&str] = &[
+    "DROP TRIGGER IF EXISTS completions_fts_insert",
+    "DROP TRIGGER IF EXISTS completions_fts_update",
+    "DROP TRIGGER IF EXISTS completions_fts_delete",
+    "DROP TABLE IF EXISTS completions_fts",
+];
+
+async fn run(manager: &SchemaManager<'_>, postgres: &[&str], sqlite: &[&str]) -> Result<(), DbErr> {
+    let statements = match manager.get_database_backend() {
+        DatabaseBackend::Postgres => postgres,
+        DatabaseBackend::Sqlite => sqlite,
+        // Full-text search is only offered on the backends above
+        _ => &[],
+    };
+    for statement in statements {
+        manager.get_connection().execute_unprepared(statement).await?;
+    }
+    Ok(())
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        run(manager, POSTGRES_UP, SQLITE_UP).await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        run(manager, POSTGRES_DOWN, SQLITE_DOWN).await
+    }
+}
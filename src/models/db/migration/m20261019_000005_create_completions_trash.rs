@@ -0,0 +1,31 @@
+use sea_orm_migration::prelude::*;
+
+use super::{json_column, CompletionsColumn, CompletionsTrash, TrashColumn};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    /// Creates `completions_trash`, where soft-deleted rows of `completions` are kept whole until restored or purged
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager.create_table(
+            Table::create()
+                .table(CompletionsTrash::Table)
+                .if_not_exists()
+                .col(ColumnDef::new(CompletionsColumn::Rid).integer().not_null().auto_increment().primary_key())
+                .col(ColumnDef::new(CompletionsColumn::Kind).string_len(16).not_null())
+                .col(ColumnDef::new(CompletionsColumn::QueryKey).text().not_null())
+                // Not unique, as the same key can be trashed more than once
+                .col(ColumnDef::new(CompletionsColumn::QueryKeyHash).string_len(64).not_null())
+                .col(ColumnDef::new(TrashColumn::DeletedAt).timestamp().not_null())
+                .col(json_column(manager.get_database_backend(), TrashColumn::Row).not_null())
+                .to_owned()
+        ).await?;
+        manager.create_index(Index::create().if_not_exists().name("idx_completions_trash_deleted_at").table(CompletionsTrash::Table).col(TrashColumn::DeletedAt).to_owned()).await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager.drop_table(Table::drop().table(CompletionsTrash::Table).if_exists().to_owned()).await
+    }
+}
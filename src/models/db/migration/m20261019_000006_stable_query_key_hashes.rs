@@ -0,0 +1,43 @@
+use sea_orm_migration::prelude::*;
+use sea_orm_migration::sea_orm::ConnectionTrait;
+
+use crate::models::hash::{calculate_hash, query_key_hash};
+use super::{Completions, CompletionsColumn, CompletionsTrash};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+/// Rewrites the `query_key_hash` of every row of `table` as `hash` of its `query_key`
+async fn rehash(manager: &SchemaManager<'_>, table: DynIden, hash: impl Fn(&str) -> String) -> Result<(), DbErr> {
+    let db = manager.get_connection();
+    let backend = manager.get_database_backend();
+    let select = Query::select()
+        .columns([CompletionsColumn::Rid, CompletionsColumn::QueryKey])
+        .from(table.clone())
+        .to_owned();
+    for row in db.query_all(backend.build(&select)).await? {
+        let rid: i32 = row.try_get("", "rid")?;
+        let query_key: String = row.try_get("", "query_key")?;
+        let update = Query::update()
+            .table(table.clone())
+            .value(CompletionsColumn::QueryKeyHash, hash(&query_key))
+            .and_where(Expr::col(CompletionsColumn::Rid).eq(rid))
+            .to_owned();
+        db.execute(backend.build(&update)).await?;
+    }
+    Ok(())
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    /// Rehashes every `query_key_hash` by SHA-256, which unlike the `DefaultHasher` it was made with doesn't change with the Rust version, so that the same key keeps matching the same row
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        rehash(manager, Completions::Table.into_iden(), query_key_hash).await?;
+        rehash(manager, CompletionsTrash::Table.into_iden(), query_key_hash).await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        rehash(manager, Completions::Table.into_iden(), |query_key| calculate_hash(&query_key)).await?;
+        rehash(manager, CompletionsTrash::Table.into_iden(), |query_key| calculate_hash(&query_key)).await
+    }
+}
@@ -0,0 +1,93 @@
+//! Schema for the `*_completions` tables, as sea-orm-migration migrations bundled into the crate.
+//! <br> Run at startup with `Opts::run_migrations`, or with `DbMethods::migrate()`. Applied migrations are recorded in the `seaql_migrations` table.
+
+pub use sea_orm_migration::prelude::*;
+use sea_orm_migration::sea_orm::DatabaseBackend;
+
+mod m20261018_000001_create_completions_tables;
+mod m20261018_000002_add_completions_indexes;
+mod m20261019_000003_unify_completions_tables;
+mod m20261019_000004_add_completions_full_text;
+mod m20261019_000005_create_completions_trash;
+mod m20261019_000006_stable_query_key_hashes;
+
+pub struct Migrator;
+
+#[async_trait::async_trait]
+impl MigratorTrait for Migrator {
+    fn migrations() -> Vec<Box<dyn MigrationTrait>> {
+        vec![
+            Box::new(m20261018_000001_create_completions_tables::Migration),
+            Box::new(m20261018_000002_add_completions_indexes::Migration),
+            Box::new(m20261019_000003_unify_completions_tables::Migration),
+            Box::new(m20261019_000004_add_completions_full_text::Migration),
+            Box::new(m20261019_000005_create_completions_trash::Migration),
+            Box::new(m20261019_000006_stable_query_key_hashes::Migration),
+        ]
+    }
+}
+
+/// A JSON column: `jsonb` on Postgres, where it is stored parsed, and plain JSON on the other backends, which have no binary JSON type
+pub(crate) fn json_column<T: IntoIden>(backend: DatabaseBackend, column: T) -> ColumnDef {
+    let mut column = ColumnDef::new(column);
+    match backend {
+        DatabaseBackend::Postgres => column.json_binary(),
+        _ => column.json(),
+    };
+    column
+}
+
+#[derive(DeriveIden)]
+pub(crate) enum Completions {
+    Table,
+}
+
+#[derive(DeriveIden)]
+pub(crate) enum CompletionsTrash {
+    Table,
+}
+
+#[derive(DeriveIden)]
+pub(crate) enum ChatCompletions {
+    Table,
+}
+
+#[derive(DeriveIden)]
+pub(crate) enum TextCompletions {
+    Table,
+}
+
+#[derive(DeriveIden)]
+pub(crate) enum MetaCompletions {
+    Table,
+}
+
+/// Columns of the completions tables. Of the per-kind tables, only `text_completions` has `document_title`, and none have `kind`, `request` or `metadata`.
+#[derive(DeriveIden)]
+pub(crate) enum CompletionsColumn {
+    Rid,
+    Kind,
+    Timestamp,
+    Model,
+    Temperature,
+    Prompt,
+    DocumentTitle,
+    QueryKey,
+    QueryKeyHash,
+    PromptTokens,
+    CompletionTokens,
+    TotalTokens,
+    ProcessTime,
+    Response,
+    Request,
+    Metadata,
+    Cost,
+}
+
+/// Columns of `completions_trash` that `completions` lacks
+#[derive(DeriveIden)]
+pub(crate) enum TrashColumn {
+    DeletedAt,
+    /// The whole trashed row of `completions`, as JSON
+    Row,
+}
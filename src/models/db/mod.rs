@@ -2,8 +2,8 @@
 
 pub mod prelude;
 
-pub mod chat_completions;
-pub mod meta_completions;
-pub mod text_completions;
+pub mod completions;
+pub mod completions_trash;
 
-pub mod db;
\ No newline at end of file
+pub mod db;
+pub mod migration;
@@ -0,0 +1,117 @@
+use regex::Regex;
+use serde::{Serialize, Deserialize};
+
+/// One entry of a document's reference list. Fields that could not be told apart in the entry are left empty; `raw` always holds the whole entry.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Reference {
+    /// As numbered in the list, if it is
+    pub number: Option<u32>,
+    pub raw: String,
+    pub authors: Vec<String>,
+    pub year: Option<u16>,
+    pub title: Option<String>,
+    pub journal: Option<String>,
+    pub doi: Option<String>,
+}
+
+/// The first DOI in `text`, without trailing punctuation
+pub fn find_doi(text: &str) -> Option<String> {
+    let doi = Regex::new(r#"\b10\.\d{4,9}/[^\s"<>\]]+"#).expect("valid regex");
+    doi.find(text).map(|doi| doi.as_str().trim_end_matches(['.', ',', ';', ')']).to_string())
+}
+
+/// Where the reference list of `text` begins: at its last "References" or "Bibliography" heading on a line of its own, or else at its last such heading run into a list starting at `1.` or `[1]`
+pub fn reference_section_start(text: &str) -> Option<usize> {
+    let heading_line = Regex::new(r"(?im)^[ \t]*(?:\d+\.?[ \t]*)?(?:references|bibliography|works cited|literature cited)[ \t]*:?[ \t]*$").expect("valid regex");
+    let heading_inline = Regex::new(r"(?i)\b(?:references|bibliography|works cited|literature cited)\s*:?\s+(?:1\.|\[1\])\s").expect("valid regex");
+    heading_line.find_iter(text).last()
+        .or_else(|| heading_inline.find_iter(text).last())
+        .map(|heading| heading.start())
+}
+
+/// The entries of the reference list of `text`, if it has one. See `reference_section_start()`
+pub fn parse_references(text: &str) -> Vec<Reference> {
+    let Some(start) = reference_section_start(text) else { return vec![] };
+    let heading = Regex::new(r"(?i)^\s*(?:\d+\.?\s*)?(?:references|bibliography|works cited|literature cited)\s*:?").expect("valid regex");
+    let section = &text[start..];
+    let section = &section[heading.find(section).map_or(0, |heading| heading.end())..];
+    split_entries(section).into_iter().map(|(number, raw)| parse_reference(number, &raw)).collect()
+}
+
+/// Numbered entries, `1.` or `[1]`, where the numbers count up from one so that numbers within an entry are not mistaken for the next. Else one entry per paragraph, or per line if there are no blank lines.
+fn split_entries(section: &str) -> Vec<(Option<u32>, String)> {
+    let marker = Regex::new(r"(?:^|\s)(?:\[(\d{1,3})\]|(\d{1,3})\.)\s+").expect("valid regex");
+    let mut starts = vec![];
+    for captures in marker.captures_iter(section) {
+        let number: u32 = captures.get(1).or(captures.get(2)).and_then(|number| number.as_str().parse().ok()).unwrap_or_default();
+        if number as usize == starts.len() + 1 {
+            let whole = captures.get(0).expect("whole match");
+            starts.push((number, whole.start(), whole.end()));
+        }
+    }
+    if !starts.is_empty() {
+        return starts.iter().enumerate().map(|(i, &(number, _, text_start))| {
+            let end = starts.get(i + 1).map_or(section.len(), |next| next.1);
+            (Some(number), collapse(&section[text_start..end]))
+        }).collect()
+    }
+
+    let paragraphs = Regex::new(r"\n\s*\n").expect("valid regex");
+    let entries: Vec<&str> = match paragraphs.is_match(section.trim()) {
+        true => paragraphs.split(section).collect(),
+        false => section.lines().collect(),
+    };
+    entries.into_iter().map(collapse).filter(|entry| !entry.is_empty()).map(|entry| (None, entry)).collect()
+}
+
+/// One line, with words broken across lines joined
+fn collapse(text: &str) -> String {
+    let broken_words = Regex::new(r"(\w)-\s*\n\s*(\p{Ll})").expect("valid regex");
+    broken_words.replace_all(text, "$1$2").split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Reads the authors, title and journal of an entry in APA (`Author, A. (2016). Title. Journal, 6.`), semicolon-separated (`Author, A.; Other, B. Title. Journal 2016, 6.`) or Vancouver style (`Author A, Other B. Title. Journal. 2016;6.`)
+fn parse_reference(number: Option<u32>, raw: &str) -> Reference {
+    let year_pattern = Regex::new(r"\b(1[89]\d{2}|20\d{2})\b").expect("valid regex");
+    let apa_year = Regex::new(r"\((1[89]\d{2}|20\d{2})[a-z]?\)\.?").expect("valid regex");
+    let semicolon_authors = Regex::new(r"^((?:[^;.]+?,\s*(?:\p{Lu}\.\s*-?)+;\s*)*[^;.]+?,\s*(?:\p{Lu}\.\s*-?)+(?:;?\s*et al\.)?)\s").expect("valid regex");
+
+    let doi = find_doi(raw);
+    let year = year_pattern.captures(raw).and_then(|captures| captures[1].parse().ok());
+    let (authors, rest): (Vec<String>, &str) = if let Some(at) = apa_year.find(raw) {
+        (split_authors(&raw[..at.start()], ','), &raw[at.end()..])
+    } else if let Some(captures) = semicolon_authors.captures(raw) {
+        let list = captures.get(1).expect("author list");
+        (split_authors(list.as_str(), ';'), &raw[list.end()..])
+    } else {
+        match raw.split_once(". ") {
+            Some((list, rest)) => (split_authors(list, ','), rest),
+            None => (vec![], raw),
+        }
+    };
+
+    let (title, rest) = match rest.trim().split_once(". ") {
+        Some((title, rest)) => (Some(title.trim().to_string()), rest),
+        None => (None, rest),
+    };
+    let journal = rest.split(|c: char| c.is_ascii_digit() || c == '(' || c == '[').next()
+        .map(|journal| journal.trim().trim_end_matches([',', ';', '.', ' ']).trim().to_string())
+        .filter(|journal| !journal.is_empty() && !journal.to_lowercase().starts_with("doi") && !journal.starts_with("http"));
+
+    Reference { number, raw: raw.to_string(), authors, year, title, journal, doi }
+}
+
+fn split_authors(list: &str, separator: char) -> Vec<String> {
+    let list = list.replace(" & ", &format!("{separator} ")).replace(" and ", &format!("{separator} "));
+    // In `Author, A., Other, B.` the commas also split names from initials, so names are rejoined with the initials that follow
+    let parts: Vec<&str> = list.split(separator).map(|part| part.trim().trim_end_matches('.')).filter(|part| !part.is_empty()).collect();
+    let initials = Regex::new(r"^(?:\p{Lu}\.?\s*-?)+$").expect("valid regex");
+    let mut authors: Vec<String> = vec![];
+    for part in parts {
+        match authors.last_mut() {
+            Some(last) if separator == ',' && initials.is_match(part) && !last.contains(' ') => { last.push_str(", "); last.push_str(part); },
+            _ => authors.push(part.to_string()),
+        }
+    }
+    authors.into_iter().filter(|author| author != "et al").collect()
+}
@@ -0,0 +1,58 @@
+use regex::Regex;
+use serde::{Serialize, Deserialize};
+
+use crate::models::documents::pdf::PdfPage;
+
+/// Sent with a document whose pages are marked, when `PdfOpts::cite_pages` is on
+pub const CITATION_INSTRUCTIONS: &str = "Each page of the document begins with a marker such as [Page 3]. Support every claim in your answer with a citation of the page it comes from, in the form [p. 3: \"exact words from that page\"]. Quote the page word for word, and keep quotes short.";
+
+/// A page the model cited for its answer, and the words it quoted from it
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Citation {
+    /// 1-based, as marked in the text sent
+    pub page: u32,
+    pub quote: String,
+    /// Whether `quote` was found on `page` of the source. A citation that is not verified is flagged: the page is wrong, or the quote was paraphrased or made up.
+    pub verified: bool,
+}
+
+/// The text of `pages`, each after a `[Page N]` marker
+pub fn page_marked_text(pages: &[PdfPage]) -> String {
+    pages.iter().map(|page| format!("[Page {}]\n{}\n", page.number, page.text.trim_end())).collect()
+}
+
+/// The citations in `answer` of the form asked for by `CITATION_INSTRUCTIONS`, each checked against `pages`
+pub fn parse_citations(answer: &str, pages: &[PdfPage]) -> Vec<Citation> {
+    let citation = Regex::new(r#"(?i)\[\s*(?:p\.?|page)\s*(\d+)\s*:\s*["“]([^"”]+)["”]\s*\]"#).expect("valid regex");
+    citation.captures_iter(answer).filter_map(|captures| {
+        let page: u32 = captures[1].parse().ok()?;
+        let quote = captures[2].trim().to_string();
+        let verified = pages.iter().find(|source| source.number == page).is_some_and(|source| quote_matches(&quote, &source.text));
+        Some(Citation { page, quote, verified })
+    }).collect()
+}
+
+/// Whether `quote` appears in `text`, ignoring case, spacing, line-break hyphens and typographic quotes and dashes. Parts of a quote elided with `...` must appear in order.
+fn quote_matches(quote: &str, text: &str) -> bool {
+    let text = comparable(text);
+    let mut from = 0;
+    for part in quote.split(['…']).flat_map(|part| part.split("...")).map(comparable).filter(|part| !part.is_empty()) {
+        match text[from..].find(&part) {
+            Some(at) => from += at + part.len(),
+            None => return false,
+        }
+    }
+    from > 0
+}
+
+fn comparable(text: &str) -> String {
+    let line_break_hyphens = Regex::new(r"(\w)-\s*\n\s*(\w)").expect("valid regex");
+    let text = line_break_hyphens.replace_all(text, "$1$2");
+    let text: String = text.chars().map(|c| match c {
+        '“' | '”' | '„' => '"',
+        '‘' | '’' => '\'',
+        '–' | '—' => '-',
+        c => c,
+    }).collect();
+    text.to_lowercase().split_whitespace().collect::<Vec<_>>().join(" ")
+}
@@ -0,0 +1,182 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::Path,
+    sync::{Mutex, OnceLock, PoisonError},
+};
+use regex::Regex;
+use serde::{Serialize, Deserialize};
+
+use crate::{
+    constants::CHARS_PER_TOKEN,
+    models::{
+        client::core::Status,
+        documents::{
+            pdf::{PdfLoader, PdfOpts, PdfPage},
+            text::{TextLoader, MarkdownLoader, HtmlLoader},
+            office::{DocxLoader, EpubLoader, zip_entry},
+        },
+    },
+};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DocumentFormat {
+    Pdf,
+    Text,
+    Markdown,
+    Html,
+    Docx,
+    Epub,
+}
+
+impl DocumentFormat {
+    /// The format of files named `*.{extension}`, ignoring case
+    pub fn from_extension(extension: &str) -> Option<DocumentFormat> {
+        match extension.to_lowercase().as_str() {
+            "pdf" => Some(DocumentFormat::Pdf),
+            "txt" | "text" => Some(DocumentFormat::Text),
+            "md" | "markdown" => Some(DocumentFormat::Markdown),
+            "html" | "htm" | "xhtml" => Some(DocumentFormat::Html),
+            "docx" => Some(DocumentFormat::Docx),
+            "epub" => Some(DocumentFormat::Epub),
+            _ => None,
+        }
+    }
+
+    /// The format `bytes` look like. Markdown can't be told from plain text, so reads as `Text`.
+    pub fn sniff(bytes: &[u8]) -> Option<DocumentFormat> {
+        if bytes.starts_with(b"%PDF-") {
+            return Some(DocumentFormat::Pdf)
+        }
+        if bytes.starts_with(b"PK\x03\x04") {
+            if zip_entry(bytes, "mimetype").is_ok_and(|mimetype| mimetype.trim_ascii() == b"application/epub+zip") {
+                return Some(DocumentFormat::Epub)
+            }
+            if zip_entry(bytes, "word/document.xml").is_ok() {
+                return Some(DocumentFormat::Docx)
+            }
+            return None
+        }
+        let text = std::str::from_utf8(bytes).ok()?;
+        let start = text.trim_start_matches('\u{feff}').trim_start().chars().take(64).collect::<String>().to_lowercase();
+        if start.starts_with("<!doctype html") || start.starts_with("<html") {
+            Some(DocumentFormat::Html)
+        } else {
+            Some(DocumentFormat::Text)
+        }
+    }
+
+    /// By the extension of `path` if it is known, else by sniffing `bytes`
+    pub fn detect(path: &Path, bytes: &[u8]) -> Option<DocumentFormat> {
+        path.extension()
+            .and_then(|extension| DocumentFormat::from_extension(&extension.to_string_lossy()))
+            .or_else(|| DocumentFormat::sniff(bytes))
+    }
+}
+
+/// What a loader could tell about a document, beside its text. Fields a format doesn't carry are `None`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct DocumentMetadata {
+    pub format: DocumentFormat,
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub page_count: Option<usize>,
+    /// Often the abstract, for PDFs
+    #[serde(default)]
+    pub subject: Option<String>,
+    #[serde(default)]
+    pub keywords: Vec<String>,
+    /// Of the creation date the file carries, which for a paper is usually, but not always, its publication year
+    #[serde(default)]
+    pub year: Option<u16>,
+}
+
+impl DocumentMetadata {
+    pub fn new(format: DocumentFormat) -> Self {
+        DocumentMetadata { format, title: None, author: None, page_count: None, subject: None, keywords: vec![], year: None }
+    }
+}
+
+/// Keywords listed with semicolons, or else commas
+pub(crate) fn split_keywords(keywords: &str) -> Vec<String> {
+    let separator = if keywords.contains(';') { ';' } else { ',' };
+    keywords.split(separator).map(str::trim).filter(|keyword| !keyword.is_empty()).map(str::to_string).collect()
+}
+
+/// The year a date such as `2016-06-12` or PDF's `D:20160612...` starts with
+pub(crate) fn date_year(date: &str) -> Option<u16> {
+    let date = date.trim().trim_start_matches("D:");
+    date.get(..4).and_then(|year| year.parse().ok())
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoadedDocument {
+    /// What is sent to the model
+    pub text: String,
+    pub metadata: DocumentMetadata,
+    /// The text split by page, for formats that have pages. Empty for the others.
+    pub pages: Vec<PdfPage>,
+    /// Estimated tokens dropped from the text by `PdfOpts::cleanup`. 0 for other formats, or without cleanup.
+    pub tokens_saved: usize,
+}
+
+/// Reads the text of documents of one format
+pub trait DocumentLoader {
+    fn format(&self) -> DocumentFormat;
+
+    /// The text of the document held in `bytes`, normalized, with what metadata the format carries
+    fn load(&self, bytes: &[u8]) -> Result<LoadedDocument, Status>;
+}
+
+pub fn loader_for(format: DocumentFormat) -> Box<dyn DocumentLoader> {
+    match format {
+        DocumentFormat::Pdf => Box::new(PdfLoader::default()),
+        DocumentFormat::Text => Box::new(TextLoader),
+        DocumentFormat::Markdown => Box::new(MarkdownLoader),
+        DocumentFormat::Html => Box::new(HtmlLoader),
+        DocumentFormat::Docx => Box::new(DocxLoader),
+        DocumentFormat::Epub => Box::new(EpubLoader),
+    }
+}
+
+/// Loads the document at `path` with the loader of its format, reading a PDF as `pdf` says. See `DocumentFormat::detect()`
+pub fn load_document(path: &Path, pdf: &PdfOpts) -> Result<LoadedDocument, Status> {
+    let bytes = fs::read(path).map_err(|e| Status::Error(format!("{}: {e}", path.display())))?;
+    load_document_bytes(path, &bytes, pdf)
+}
+
+/// `load_document()` of the content `bytes` of the file at `path`, already read
+pub fn load_document_bytes(path: &Path, bytes: &[u8], pdf: &PdfOpts) -> Result<LoadedDocument, Status> {
+    match DocumentFormat::detect(path, bytes) {
+        Some(DocumentFormat::Pdf) => PdfLoader { opts: pdf.clone() }.load(bytes),
+        Some(format) => loader_for(format).load(bytes),
+        None => Err(Status::UnsupportedDocument(path.display().to_string())),
+    }
+}
+
+/// Rough count of the tokens `text` takes up, at `CHARS_PER_TOKEN`
+pub fn estimate_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(CHARS_PER_TOKEN)
+}
+
+/// Unix line endings, no trailing spaces, and at most one blank line in a row
+pub(crate) fn normalize_text(text: &str) -> String {
+    let text = text.trim_start_matches('\u{feff}').replace("\r\n", "\n").replace('\r', "\n");
+    let lines: Vec<&str> = text.lines().map(str::trim_end).collect();
+    static BLANK_RUNS: OnceLock<Regex> = OnceLock::new();
+    let blank_runs = cached_regex(&BLANK_RUNS, r"\n{3,}");
+    blank_runs.replace_all(&lines.join("\n"), "\n\n").trim().to_string()
+}
+
+/// `pattern`, compiled into `cell` on first use and kept there for every later call
+pub(crate) fn cached_regex(cell: &'static OnceLock<Regex>, pattern: &str) -> &'static Regex {
+    cell.get_or_init(|| Regex::new(pattern).expect("valid regex"))
+}
+
+/// The regex `pattern(name)`, compiled into `cell` on the first use of each `name`, such as an element or attribute name, and kept there for every later call
+pub(crate) fn cached_named_regex(cell: &'static OnceLock<Mutex<HashMap<String, Regex>>>, name: &str, pattern: impl FnOnce(&str) -> String) -> Regex {
+    let mut compiled = cell.get_or_init(Default::default).lock().unwrap_or_else(PoisonError::into_inner);
+    compiled.entry(name.to_string())
+        .or_insert_with(|| Regex::new(&pattern(name)).expect("escaped name is a valid regex"))
+        .clone()
+}
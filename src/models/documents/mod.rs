@@ -0,0 +1,13 @@
+pub mod loader;
+pub mod pdf;
+pub mod text;
+pub mod office;
+pub mod citations;
+pub mod bibliography;
+pub mod store;
+
+pub use loader::{DocumentFormat, DocumentLoader, DocumentMetadata, LoadedDocument, load_document, load_document_bytes, loader_for, estimate_tokens};
+pub use pdf::{PdfCleanup, PdfOpts};
+pub use citations::Citation;
+pub use bibliography::Reference;
+pub use store::{DocumentStore, StoredDocument};
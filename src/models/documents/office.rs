@@ -0,0 +1,135 @@
+use std::{
+    collections::HashMap,
+    io::{Cursor, Read},
+    sync::{Mutex, OnceLock},
+};
+use regex::Regex;
+
+use crate::models::{
+    client::core::Status,
+    documents::{
+        loader::{cached_named_regex, cached_regex, date_year, normalize_text, split_keywords, DocumentFormat, DocumentLoader, DocumentMetadata, LoadedDocument},
+        text::{attribute, decode_entities, html_to_text},
+    },
+};
+
+/// The paragraphs of the body, one per line. The title and author come from `docProps/core.xml`, and the page count as last saved by Word from `docProps/app.xml`.
+pub struct DocxLoader;
+
+impl DocumentLoader for DocxLoader {
+    fn format(&self) -> DocumentFormat {
+        DocumentFormat::Docx
+    }
+
+    fn load(&self, bytes: &[u8]) -> Result<LoadedDocument, Status> {
+        static PARAGRAPH_ENDS: OnceLock<Regex> = OnceLock::new();
+        static TABS: OnceLock<Regex> = OnceLock::new();
+        static TAGS: OnceLock<Regex> = OnceLock::new();
+        let document = String::from_utf8_lossy(&zip_entry(bytes, "word/document.xml")?).to_string();
+        // Empty paragraphs are self-closing, with or without attributes such as `w:rsidR`
+        let paragraph_ends = cached_regex(&PARAGRAPH_ENDS, r"</w:p>|<w:p\b[^>]*/>|<w:br\b[^>]*/>|<w:cr/>");
+        let tabs = cached_regex(&TABS, r"<w:tab/>");
+        let tags = cached_regex(&TAGS, r"(?s)<[^>]*>");
+        let text = paragraph_ends.replace_all(&document, "\n");
+        let text = tabs.replace_all(&text, "\t");
+        let text = tags.replace_all(&text, "");
+
+        let mut metadata = DocumentMetadata::new(DocumentFormat::Docx);
+        if let Ok(core) = zip_entry(bytes, "docProps/core.xml") {
+            let core = String::from_utf8_lossy(&core);
+            metadata.title = element_text(&core, "dc:title");
+            metadata.author = element_text(&core, "dc:creator");
+            metadata.subject = element_text(&core, "dc:subject").or_else(|| element_text(&core, "dc:description"));
+            metadata.keywords = element_text(&core, "cp:keywords").map(|keywords| split_keywords(&keywords)).unwrap_or_default();
+            metadata.year = element_text(&core, "dcterms:created").and_then(|date| date_year(&date));
+        }
+        if let Ok(app) = zip_entry(bytes, "docProps/app.xml") {
+            metadata.page_count = element_text(&String::from_utf8_lossy(&app), "Pages").and_then(|pages| pages.parse().ok());
+        }
+        Ok(LoadedDocument { text: normalize_text(&decode_entities(&text)), metadata, pages: vec![], tokens_saved: 0 })
+    }
+}
+
+/// The text of every chapter, in reading order. The title and author come from the package document.
+pub struct EpubLoader;
+
+impl DocumentLoader for EpubLoader {
+    fn format(&self) -> DocumentFormat {
+        DocumentFormat::Epub
+    }
+
+    fn load(&self, bytes: &[u8]) -> Result<LoadedDocument, Status> {
+        static ROOTFILE: OnceLock<Regex> = OnceLock::new();
+        static ITEM: OnceLock<Regex> = OnceLock::new();
+        static ITEMREF: OnceLock<Regex> = OnceLock::new();
+        static SUBJECT: OnceLock<Regex> = OnceLock::new();
+        let container = String::from_utf8_lossy(&zip_entry(bytes, "META-INF/container.xml")?).to_string();
+        let package_path = cached_regex(&ROOTFILE, r"(?is)<rootfile\b[^>]*>")
+            .find(&container)
+            .and_then(|tag| attribute(tag.as_str(), "full-path"))
+            .ok_or_else(|| Status::Error("EPUB container names no package document".to_string()))?;
+        let package = String::from_utf8_lossy(&zip_entry(bytes, &package_path)?).to_string();
+
+        let manifest: HashMap<String, String> = cached_regex(&ITEM, r"(?is)<item\b[^>]*>")
+            .find_iter(&package)
+            .filter_map(|tag| Some((attribute(tag.as_str(), "id")?, attribute(tag.as_str(), "href")?)))
+            .collect();
+        let spine: Vec<String> = cached_regex(&ITEMREF, r"(?is)<itemref\b[^>]*>")
+            .find_iter(&package)
+            .filter_map(|tag| attribute(tag.as_str(), "idref"))
+            .collect();
+
+        let mut chapters = vec![];
+        for idref in spine {
+            let href = manifest.get(&idref).ok_or_else(|| Status::Error(format!("EPUB spine item {idref} is not in the manifest")))?;
+            let chapter = zip_entry(bytes, &resolve_href(&package_path, href))?;
+            chapters.push(html_to_text(&String::from_utf8_lossy(&chapter)));
+        }
+
+        let metadata = DocumentMetadata {
+            title: element_text(&package, "dc:title"),
+            author: element_text(&package, "dc:creator"),
+            subject: element_text(&package, "dc:description"),
+            keywords: cached_regex(&SUBJECT, r"(?is)<dc:subject\b[^>]*>(.*?)</dc:subject>")
+                .captures_iter(&package)
+                .map(|captures| decode_entities(captures[1].trim()))
+                .collect(),
+            year: element_text(&package, "dc:date").and_then(|date| date_year(&date)),
+            ..DocumentMetadata::new(DocumentFormat::Epub)
+        };
+        Ok(LoadedDocument { text: normalize_text(&chapters.join("\n\n")), metadata, pages: vec![], tokens_saved: 0 })
+    }
+}
+
+/// The entry `name` of the zip archive in `bytes`
+pub(crate) fn zip_entry(bytes: &[u8], name: &str) -> Result<Vec<u8>, Status> {
+    let mut archive = zip::ZipArchive::new(Cursor::new(bytes)).map_err(|e| Status::Error(e.to_string()))?;
+    let mut entry = archive.by_name(name).map_err(|e| Status::Error(format!("{name}: {e}")))?;
+    let mut content = vec![];
+    entry.read_to_end(&mut content).map_err(|e| Status::Error(format!("{name}: {e}")))?;
+    Ok(content)
+}
+
+/// The decoded, non-empty text of the first `<element>` of `xml`
+fn element_text(xml: &str, element: &str) -> Option<String> {
+    static ELEMENTS: OnceLock<Mutex<HashMap<String, Regex>>> = OnceLock::new();
+    let pattern = cached_named_regex(&ELEMENTS, element, |element| format!(r"(?is)<{0}\b[^>]*>(.*?)</{0}>", regex::escape(element)));
+    let captures = pattern.captures(xml)?;
+    let text = decode_entities(captures[1].trim());
+    (!text.is_empty()).then_some(text)
+}
+
+/// The archive path of `href`, relative to the package document at `package_path`
+fn resolve_href(package_path: &str, href: &str) -> String {
+    let href = href.split('#').next().unwrap_or_default().replace("%20", " ");
+    let mut segments: Vec<&str> = package_path.split('/').collect();
+    segments.pop();
+    for segment in href.split('/') {
+        match segment {
+            ".." => { segments.pop(); },
+            "." | "" => (),
+            segment => segments.push(segment),
+        }
+    }
+    segments.join("/")
+}
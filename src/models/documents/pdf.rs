@@ -0,0 +1,262 @@
+use std::{collections::HashMap, ops::RangeInclusive};
+use lopdf::{Document, Object};
+use regex::Regex;
+use serde::{Serialize, Deserialize};
+
+use crate::models::{
+    client::core::Status,
+    documents::{
+        bibliography::reference_section_start,
+        loader::{date_year, estimate_tokens, normalize_text, split_keywords, DocumentFormat, DocumentLoader, DocumentMetadata, LoadedDocument},
+    },
+};
+
+/// Which pages of a PDF are read, and how their text is cleaned up. The default reads every page and leaves the text as `lopdf` extracts it.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct PdfOpts {
+    /// 1-based, inclusive. Every page when `None`. See `parse_page_ranges()`
+    pub pages: Option<Vec<RangeInclusive<u32>>>,
+    pub cleanup: PdfCleanup,
+    /// Marks each page in the text sent, asks the model to cite the pages it draws on, and keeps the citations on the query, checked against the pages. See `Citation`
+    /// <br> Only for `OpenAIAccount::apply_prompt_to_pdf()` and `apply_prompt_to_document()`.
+    /// <br> Kept on `TextQuery::pdf` like `pages` and `cleanup`, so that an answer cached without citations is not served to a call that cites pages, nor the other way around.
+    pub cite_pages: bool,
+}
+
+/// Passes over the extracted text that drop what costs tokens without informing the model. Each is off by default.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PdfCleanup {
+    /// Joins words broken across lines, such as `choles-\nterol`
+    pub dehyphenate: bool,
+    /// Drops the lines repeated at the top or bottom of most pages, such as running titles, and page numbers
+    pub strip_headers_footers: bool,
+    /// Collapses runs of spaces, trailing spaces and blank lines
+    pub collapse_whitespace: bool,
+    /// Drops everything from the last "References" or "Bibliography" heading on
+    pub strip_references: bool,
+}
+
+impl PdfOpts {
+    pub fn is_default(&self) -> bool {
+        *self == PdfOpts::default()
+    }
+}
+
+impl PdfCleanup {
+    pub fn all() -> Self {
+        PdfCleanup { dehyphenate: true, strip_headers_footers: true, collapse_whitespace: true, strip_references: true }
+    }
+
+    pub fn is_none(&self) -> bool {
+        *self == PdfCleanup::default()
+    }
+
+    /// Runs the passes that are on over `pages`, in order
+    pub fn apply(&self, pages: &mut Vec<PdfPage>) {
+        if self.strip_headers_footers {
+            strip_headers_footers(pages);
+        }
+        if self.dehyphenate {
+            let broken_words = Regex::new(r"(\w)-[ \t]*\r?\n[ \t]*(\p{Ll})").expect("valid regex");
+            for page in pages.iter_mut() {
+                page.text = broken_words.replace_all(&page.text, "$1$2").to_string();
+            }
+        }
+        if self.strip_references {
+            strip_references(pages);
+        }
+        if self.collapse_whitespace {
+            let spaces = Regex::new(r"[ \t]+").expect("valid regex");
+            for page in pages.iter_mut() {
+                // Pages are joined end to end, so each keeps a line break after its text
+                page.text = format!("{}\n", normalize_text(&spaces.replace_all(&page.text, " ")));
+            }
+        }
+    }
+}
+
+/// Parses page ranges such as `"1-3, 7, 10-"`, where an open end runs to the last page
+pub fn parse_page_ranges(ranges: &str) -> Result<Vec<RangeInclusive<u32>>, Status> {
+    let page = |number: &str| number.trim().parse::<u32>().ok().filter(|&page| page > 0)
+        .ok_or_else(|| Status::Error(format!("Invalid page number \"{}\" in page ranges \"{ranges}\"", number.trim())));
+    ranges.split(',').map(|range| match range.split_once('-') {
+        Some((start, end)) if end.trim().is_empty() => Ok(page(start)?..=u32::MAX),
+        Some((start, end)) => Ok(page(start)?..=page(end)?),
+        None => { let page = page(range)?; Ok(page..=page) },
+    }).collect()
+}
+
+/// The text of one page, after cleanup
+#[derive(Debug, Clone, PartialEq)]
+pub struct PdfPage {
+    /// 1-based
+    pub number: u32,
+    pub text: String,
+}
+
+/// What `PdfLoader::extract()` read
+#[derive(Debug, Clone, PartialEq)]
+pub struct PdfExtraction {
+    /// The pages read, in order. Pages after a stripped reference section are left out.
+    pub pages: Vec<PdfPage>,
+    /// Pages `lopdf` could not read, with why. The others are read regardless.
+    pub failed_pages: Vec<(u32, String)>,
+    /// Of the whole document, whichever pages were read
+    pub page_count: usize,
+    /// Estimated tokens of the selected pages before cleanup
+    pub raw_tokens: usize,
+    /// Estimated tokens of `text()`
+    pub tokens: usize,
+}
+
+impl PdfExtraction {
+    /// The pages, one after the other
+    pub fn text(&self) -> String {
+        self.pages.iter().map(|page| page.text.as_str()).collect()
+    }
+
+    pub fn tokens_saved(&self) -> usize {
+        self.raw_tokens.saturating_sub(self.tokens)
+    }
+}
+
+/// Reads the text of the pages selected by `opts`, in order, and cleans it up. A page that fails to parse is skipped with a warning.
+/// <br> With the default `PdfOpts`, the text is left as `lopdf` extracts it, so that what is sent for a PDF is what it always was.
+#[derive(Debug, Clone, Default)]
+pub struct PdfLoader {
+    pub opts: PdfOpts,
+}
+
+impl PdfLoader {
+    pub fn extract(&self, pdf: &Document) -> Result<PdfExtraction, Status> {
+        let page_count = pdf.get_pages().len();
+        let selected: Vec<u32> = (1..=page_count as u32)
+            .filter(|page| self.opts.pages.as_ref().is_none_or(|ranges| ranges.iter().any(|range| range.contains(page))))
+            .collect();
+        if selected.is_empty() {
+            return Err(Status::Error(format!("No page of the {page_count} in the PDF is in {:?}", self.opts.pages)))
+        }
+
+        let mut pages = vec![];
+        let mut failed_pages = vec![];
+        for number in selected {
+            match pdf.extract_text(&[number]) {
+                Ok(text) => pages.push(PdfPage { number, text }),
+                Err(e) => {
+                    println!("⚠️ Skipping page {number} of {page_count}, which could not be read: {e}");
+                    failed_pages.push((number, e.to_string()));
+                },
+            }
+        }
+        if pages.is_empty() {
+            return Err(Status::Error(format!("None of the selected pages could be read: {failed_pages:?}")))
+        }
+
+        let raw_tokens = estimate_tokens(&pages.iter().map(|page| page.text.as_str()).collect::<String>());
+        self.opts.cleanup.apply(&mut pages);
+        let mut extraction = PdfExtraction { pages, failed_pages, page_count, raw_tokens, tokens: 0 };
+        extraction.tokens = estimate_tokens(&extraction.text());
+        Ok(extraction)
+    }
+}
+
+impl DocumentLoader for PdfLoader {
+    fn format(&self) -> DocumentFormat {
+        DocumentFormat::Pdf
+    }
+
+    fn load(&self, bytes: &[u8]) -> Result<LoadedDocument, Status> {
+        let pdf = Document::load_mem(bytes).map_err(|e| Status::Error(e.to_string()))?;
+        let extraction = self.extract(&pdf)?;
+        if !self.opts.cleanup.is_none() {
+            println!("🧹 Cleanup saved ~{} of ~{} tokens", extraction.tokens_saved(), extraction.raw_tokens);
+        }
+        let metadata = DocumentMetadata {
+            title: info_string(&pdf, b"Title"),
+            author: info_string(&pdf, b"Author"),
+            page_count: Some(extraction.page_count),
+            subject: info_string(&pdf, b"Subject"),
+            keywords: info_string(&pdf, b"Keywords").map(|keywords| split_keywords(&keywords)).unwrap_or_default(),
+            year: info_string(&pdf, b"CreationDate").and_then(|date| date_year(&date)),
+            ..DocumentMetadata::new(DocumentFormat::Pdf)
+        };
+        Ok(LoadedDocument { text: extraction.text(), metadata, tokens_saved: extraction.tokens_saved(), pages: extraction.pages })
+    }
+}
+
+/// How many lines at each end of a page can be a header or footer
+const MARGIN_LINES: usize = 2;
+
+/// Drops lines at the top or bottom of a page that, with digits ignored, are at the top or bottom of at least half the pages, and at least three. Lone page numbers there are dropped on any page.
+fn strip_headers_footers(pages: &mut [PdfPage]) {
+    let page_number = Regex::new(r"(?i)^(?:page\s*)?\d+(?:\s*(?:of|/)\s*\d+)?$").expect("valid regex");
+    let digits = Regex::new(r"\d+").expect("valid regex");
+    let shape = |line: &str| digits.replace_all(line.trim(), "#").to_string();
+
+    // Indices of the non-empty lines near either end of each page
+    let margins: Vec<Vec<usize>> = pages.iter().map(|page| {
+        let filled: Vec<usize> = page.text.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()).map(|(i, _)| i).collect();
+        let mut margin: Vec<usize> = filled.iter().take(MARGIN_LINES).chain(filled.iter().rev().take(MARGIN_LINES)).copied().collect();
+        margin.sort();
+        margin.dedup();
+        margin
+    }).collect();
+
+    let mut pages_with: HashMap<String, usize> = HashMap::new();
+    for (page, margin) in pages.iter().zip(&margins) {
+        let lines: Vec<&str> = page.text.lines().collect();
+        let mut shapes: Vec<String> = margin.iter().map(|&i| shape(lines[i])).collect();
+        shapes.sort();
+        shapes.dedup();
+        for shape in shapes {
+            *pages_with.entry(shape).or_default() += 1;
+        }
+    }
+    let threshold = 3.max(pages.len().div_ceil(2));
+
+    for (page, margin) in pages.iter_mut().zip(&margins) {
+        let kept: Vec<&str> = page.text.lines().enumerate()
+            .filter(|(i, line)| !(margin.contains(i) && (page_number.is_match(line.trim()) || pages_with[&shape(line)] >= threshold)))
+            .map(|(_, line)| line)
+            .collect();
+        page.text = format!("{}\n", kept.join("\n"));
+    }
+}
+
+/// Drops everything from the last reference heading on, unless it is in the first half of the pages, where it is more likely a mention than the section. See `reference_section_start()`
+fn strip_references(pages: &mut Vec<PdfPage>) {
+    let found = pages.iter().enumerate().rev()
+        .find_map(|(i, page)| reference_section_start(&page.text).map(|start| (i, start)));
+    if let Some((i, start)) = found {
+        if i >= pages.len() / 2 {
+            pages[i].text.truncate(start);
+            pages.truncate(i + 1);
+        }
+    }
+}
+
+/// The non-empty text entry `key` of the document's info dictionary
+fn info_string(pdf: &Document, key: &[u8]) -> Option<String> {
+    let info = match pdf.trailer.get(b"Info").ok()? {
+        Object::Reference(id) => pdf.get_object(*id).ok()?,
+        object => object,
+    };
+    let value = info.as_dict().ok()?.get(key).ok()?;
+    let text = match value {
+        Object::String(bytes, _) => decode_pdf_string(bytes),
+        _ => return None,
+    };
+    let text = text.trim();
+    (!text.is_empty()).then(|| text.to_string())
+}
+
+/// PDF text strings are UTF-16BE when they start with its byte order mark, else PDFDocEncoding, which is Latin-1 for the characters that matter here
+fn decode_pdf_string(bytes: &[u8]) -> String {
+    match bytes {
+        [0xFE, 0xFF, rest @ ..] => {
+            let units: Vec<u16> = rest.chunks_exact(2).map(|pair| u16::from_be_bytes([pair[0], pair[1]])).collect();
+            String::from_utf16_lossy(&units)
+        },
+        _ => bytes.iter().map(|&byte| byte as char).collect(),
+    }
+}
@@ -0,0 +1,219 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    io,
+    path::{Path, PathBuf},
+};
+use chrono::{DateTime, Utc};
+use serde::{Serialize, Deserialize};
+
+use crate::models::{
+    client::{core::Status, json_file::{load_json, save_json}},
+    documents::{
+        loader::{estimate_tokens, load_document_bytes, DocumentFormat, DocumentMetadata},
+        pdf::{PdfOpts, PdfPage},
+    },
+    hash::sha256_hex,
+    ChatCompletionMessage,
+    MessageRole,
+};
+
+/// Where the text of a page starts in `StoredDocument::text`
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub struct PageOffset {
+    /// 1-based
+    pub number: u32,
+    /// In bytes
+    pub start: usize,
+    /// Estimated tokens of the page
+    pub tokens: usize,
+}
+
+/// The text extracted from one version of a file, as it is sent to the model
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct StoredDocument {
+    /// Hex SHA-256 of the file
+    pub sha256: String,
+    /// Where the file was when extracted
+    pub source: PathBuf,
+    /// The pages and cleanup a PDF was read with. `cite_pages` is left off, since it doesn't change the text.
+    pub pdf: PdfOpts,
+    pub metadata: DocumentMetadata,
+    pub text: String,
+    /// One per page, in order, for formats that have pages. Empty for the others.
+    pub page_offsets: Vec<PageOffset>,
+    /// Estimated tokens of `text`
+    pub tokens: usize,
+    /// Estimated tokens `PdfOpts::cleanup` dropped from `text`
+    #[serde(default)]
+    pub tokens_saved: usize,
+    pub stored_at: DateTime<Utc>,
+}
+
+impl StoredDocument {
+    /// Extracts the text of the file at `path`, whose content is `bytes`, reading a PDF as `pdf` says
+    pub fn extract(path: &Path, bytes: &[u8], pdf: &PdfOpts) -> Result<StoredDocument, Status> {
+        let doc = load_document_bytes(path, bytes, pdf)?;
+        let mut start = 0;
+        let page_offsets = doc.pages.iter().map(|page| {
+            let offset = PageOffset { number: page.number, start, tokens: estimate_tokens(&page.text) };
+            start += page.text.len();
+            offset
+        }).collect();
+        Ok(StoredDocument {
+            sha256: sha256_hex(bytes),
+            source: path.to_path_buf(),
+            pdf: StoredDocument::extraction_opts(doc.metadata.format, pdf),
+            tokens: estimate_tokens(&doc.text),
+            tokens_saved: doc.tokens_saved,
+            metadata: doc.metadata,
+            text: doc.text,
+            page_offsets,
+            stored_at: Utc::now(),
+        })
+    }
+
+    /// The text split by page, as the loader read it. Errors if `page_offsets` don't fall on character boundaries of `text`, in order, as in an entry edited or corrupted on disk.
+    pub fn pages(&self) -> Result<Vec<PdfPage>, Status> {
+        self.page_offsets.iter().enumerate().map(|(i, offset)| {
+            let end = self.page_offsets.get(i + 1).map_or(self.text.len(), |next| next.start);
+            let text = self.text.get(offset.start..end).ok_or(Status::Error(format!("Page {} of the stored text of {} is out of bounds", offset.number, self.source.display())))?;
+            Ok(PdfPage { number: offset.number, text: text.to_string() })
+        }).collect()
+    }
+
+    /// Stands in for `text` in the messages kept on a query: the hash under which, with the query's `TextQuery::pdf`, `DocumentStore::get()` finds it
+    pub fn reference(&self) -> String {
+        format!("[document sha256:{}]", self.sha256)
+    }
+
+    /// `messages`, sent to apply a prompt to this document, with the document replaced by `reference()`, so that the cache, graveyard, archives and batch files don't each hold a copy of its text.
+    /// <br> The document is the last system message, just before the prompt.
+    pub fn referenced_in(&self, messages: &[ChatCompletionMessage]) -> Vec<ChatCompletionMessage> {
+        let mut messages = messages.to_vec();
+        if let Some(message) = messages.iter_mut().rev().find(|message| matches!(message.role, MessageRole::system)) {
+            message.content = Some(self.reference());
+        }
+        messages
+    }
+
+    /// The options a prompt applied to the file at `path` under `pdf` is answered with: those of `pdf` for a PDF, `cite_pages` included, and none for other formats. Its format is told by extension, or by content if the extension is unknown.
+    /// <br> A `TextQuery` keeps these, so that an answer to a document read with other pages, cleanup or citations is not served from the cache.
+    pub fn read_opts(path: &Path, pdf: &PdfOpts) -> PdfOpts {
+        let format = path.extension()
+            .and_then(|extension| DocumentFormat::from_extension(&extension.to_string_lossy()))
+            .or_else(|| fs::read(path).ok().and_then(|bytes| DocumentFormat::sniff(&bytes)));
+        match format {
+            Some(DocumentFormat::Pdf) => pdf.clone(),
+            _ => PdfOpts::default(),
+        }
+    }
+
+    /// The options that shape the text of a document of `format`: those of `pdf` that select and clean up pages, for a PDF, and none for other formats
+    fn extraction_opts(format: DocumentFormat, pdf: &PdfOpts) -> PdfOpts {
+        match format {
+            DocumentFormat::Pdf => PdfOpts { cite_pages: false, ..pdf.clone() },
+            _ => PdfOpts::default(),
+        }
+    }
+}
+
+/// Extracted text kept on disk by file content hash and the `PdfOpts` it was read with, one JSON file per version of a document and way of reading it, so that each is parsed once however many prompts are run over it.
+/// <br> Kept at `Opts::document_store_dir`. The entries of a file are dropped when it changes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DocumentStore {
+    pub dir: PathBuf,
+}
+
+impl DocumentStore {
+    pub fn new(dir: &Path) -> Self {
+        DocumentStore { dir: dir.to_path_buf() }
+    }
+
+    /// The text of the file at `path`, from the store if this content was extracted before with the same options, else extracted and stored
+    pub fn read(&self, path: &Path, pdf: &PdfOpts) -> Result<StoredDocument, Status> {
+        let bytes = fs::read(path).map_err(|e| Status::Error(format!("{}: {e}", path.display())))?;
+        let sha256 = sha256_hex(&bytes);
+        let read_as = DocumentFormat::detect(path, &bytes).map_or(PdfOpts::default(), |format| StoredDocument::extraction_opts(format, pdf));
+        if let Some(doc) = self.get(&sha256, &read_as) {
+            self.track(path, &sha256)?;
+            return Ok(doc)
+        }
+        let doc = StoredDocument::extract(path, &bytes, pdf)?;
+        self.put(&doc)?;
+        println!("🗃️ Stored the text of {}: ~{} tokens{}", path.display(), doc.tokens, match doc.page_offsets.len() {
+            0 => String::new(),
+            pages => format!(" over {pages} pages"),
+        });
+        Ok(doc)
+    }
+
+    /// The entry for the file content hashed to `sha256`, read with `pdf`, if any. `cite_pages` is ignored, as on `StoredDocument::pdf`
+    pub fn get(&self, sha256: &str, pdf: &PdfOpts) -> Option<StoredDocument> {
+        let f = fs::File::open(self.entry_path(sha256, pdf)).ok()?;
+        serde_json::from_reader(io::BufReader::new(f)).ok()
+    }
+
+    /// Keeps `doc`, alongside the entries of its content read other ways, and in place of those of an earlier version of its file
+    pub fn put(&self, doc: &StoredDocument) -> Result<(), Status> {
+        fs::create_dir_all(&self.dir).map_err(|e| Status::Error(e.to_string()))?;
+        let json = serde_json::to_string(doc).map_err(|e| Status::Error(e.to_string()))?;
+        fs::write(self.entry_path(&doc.sha256, &doc.pdf), json).map_err(|e| Status::Error(e.to_string()))?;
+        self.track(&doc.source, &doc.sha256)
+    }
+
+    /// Records that the file at `source` now hashes to `sha256`, and drops the entries of what it held before unless another file still holds that
+    fn track(&self, source: &Path, sha256: &str) -> Result<(), Status> {
+        let mut index = self.index()?;
+        let Some(previous) = index.insert(source.to_path_buf(), sha256.to_string()) else { return self.save_index(&index) };
+        if previous == sha256 {
+            return Ok(())
+        }
+        if !index.values().any(|held| *held == previous) {
+            let dropped = self.drop_entries(&previous)?;
+            if dropped > 0 {println!("🗑️ Dropped the stored text of the earlier version of {}", source.display())};
+        }
+        self.save_index(&index)
+    }
+
+    /// Removes every entry of the content hashed to `sha256`, however it was read. Returns how many there were.
+    fn drop_entries(&self, sha256: &str) -> Result<usize, Status> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(0),
+            Err(e) => return Err(Status::Error(e.to_string())),
+        };
+        let mut dropped = 0;
+        for entry in entries {
+            let entry = entry.map_err(|e| Status::Error(e.to_string()))?;
+            let name = entry.file_name().to_string_lossy().to_string();
+            if name == format!("{sha256}.json") || name.starts_with(&format!("{sha256}-")) {
+                fs::remove_file(entry.path()).map_err(|e| Status::Error(e.to_string()))?;
+                dropped += 1;
+            }
+        }
+        Ok(dropped)
+    }
+
+    /// The content hash last seen of each file read. See `load_json()`
+    fn index(&self) -> Result<BTreeMap<PathBuf, String>, Status> {
+        load_json(&self.dir.join("index.json"))
+    }
+
+    fn save_index(&self, index: &BTreeMap<PathBuf, String>) -> Result<(), Status> {
+        fs::create_dir_all(&self.dir).map_err(|e| Status::Error(e.to_string()))?;
+        save_json(index, &self.dir.join("index.json"))
+    }
+
+    /// `{sha256}.json` for content read whole and as extracted, else suffixed with the hash of the options it was read with
+    fn entry_path(&self, sha256: &str, pdf: &PdfOpts) -> PathBuf {
+        let pdf = PdfOpts { cite_pages: false, ..pdf.clone() };
+        match pdf.is_default() {
+            true => self.dir.join(format!("{sha256}.json")),
+            false => {
+                let opts = serde_json::to_string(&pdf).expect("conversion to JSON of PdfOpts");
+                self.dir.join(format!("{sha256}-{}.json", &sha256_hex(opts.as_bytes())[..16]))
+            },
+        }
+    }
+}
@@ -0,0 +1,134 @@
+use std::{
+    collections::HashMap,
+    sync::{Mutex, OnceLock},
+};
+use regex::Regex;
+
+use crate::models::{
+    client::core::Status,
+    documents::loader::{cached_named_regex, cached_regex, normalize_text, DocumentFormat, DocumentLoader, DocumentMetadata, LoadedDocument},
+};
+
+pub struct TextLoader;
+
+impl DocumentLoader for TextLoader {
+    fn format(&self) -> DocumentFormat {
+        DocumentFormat::Text
+    }
+
+    fn load(&self, bytes: &[u8]) -> Result<LoadedDocument, Status> {
+        Ok(LoadedDocument { text: normalize_text(&String::from_utf8_lossy(bytes)), metadata: DocumentMetadata::new(DocumentFormat::Text), pages: vec![], tokens_saved: 0 })
+    }
+}
+
+/// Keeps the markdown as is, since models read it well. The title and author come from YAML front matter, which is dropped, else the title from the first `# ` heading.
+pub struct MarkdownLoader;
+
+impl DocumentLoader for MarkdownLoader {
+    fn format(&self) -> DocumentFormat {
+        DocumentFormat::Markdown
+    }
+
+    fn load(&self, bytes: &[u8]) -> Result<LoadedDocument, Status> {
+        let text = normalize_text(&String::from_utf8_lossy(bytes));
+        let mut metadata = DocumentMetadata::new(DocumentFormat::Markdown);
+
+        let body = match text.strip_prefix("---\n").and_then(|rest| rest.split_once("\n---")) {
+            Some((front_matter, body)) => {
+                for line in front_matter.lines() {
+                    match line.split_once(':') {
+                        Some(("title", value)) => metadata.title = unquote(value),
+                        Some(("author", value)) => metadata.author = unquote(value),
+                        _ => (),
+                    }
+                }
+                body.trim_start_matches('-').trim().to_string()
+            },
+            None => text,
+        };
+        if metadata.title.is_none() {
+            metadata.title = body.lines().find_map(|line| line.strip_prefix("# ")).and_then(unquote);
+        }
+        Ok(LoadedDocument { text: body, metadata, pages: vec![], tokens_saved: 0 })
+    }
+}
+
+fn unquote(value: &str) -> Option<String> {
+    let value = value.trim().trim_matches(|c| c == '"' || c == '\'').trim();
+    (!value.is_empty()).then(|| value.to_string())
+}
+
+/// The visible text of the page. The title comes from `<title>`, and the author from `<meta name="author">`.
+pub struct HtmlLoader;
+
+impl DocumentLoader for HtmlLoader {
+    fn format(&self) -> DocumentFormat {
+        DocumentFormat::Html
+    }
+
+    fn load(&self, bytes: &[u8]) -> Result<LoadedDocument, Status> {
+        static TITLE: OnceLock<Regex> = OnceLock::new();
+        static AUTHOR: OnceLock<Regex> = OnceLock::new();
+        let html = String::from_utf8_lossy(bytes);
+        let title = cached_regex(&TITLE, r"(?is)<title[^>]*>(.*?)</title>")
+            .captures(&html)
+            .and_then(|captures| unquote(&decode_entities(&captures[1])));
+        let author = cached_regex(&AUTHOR, r#"(?is)<meta\b[^>]*\bname\s*=\s*["']author["'][^>]*>"#)
+            .find(&html)
+            .and_then(|tag| attribute(tag.as_str(), "content"))
+            .and_then(|author| unquote(&decode_entities(&author)));
+        let metadata = DocumentMetadata { title, author, ..DocumentMetadata::new(DocumentFormat::Html) };
+        Ok(LoadedDocument { text: html_to_text(&html), metadata, pages: vec![], tokens_saved: 0 })
+    }
+}
+
+/// The value of the attribute `name` of the tag `tag`
+pub(crate) fn attribute(tag: &str, name: &str) -> Option<String> {
+    static ATTRIBUTES: OnceLock<Mutex<HashMap<String, Regex>>> = OnceLock::new();
+    let pattern = cached_named_regex(&ATTRIBUTES, name, |name| format!(r#"(?is)\b{}\s*=\s*(?:"([^"]*)"|'([^']*)')"#, regex::escape(name)));
+    let captures = pattern.captures(tag)?;
+    captures.get(1).or(captures.get(2)).map(|value| value.as_str().to_string())
+}
+
+/// The visible text of HTML or XHTML, one line per block element
+pub(crate) fn html_to_text(html: &str) -> String {
+    static HIDDEN: OnceLock<Regex> = OnceLock::new();
+    static LINE_BREAKS: OnceLock<Regex> = OnceLock::new();
+    static TAGS: OnceLock<Regex> = OnceLock::new();
+    static SPACES: OnceLock<Regex> = OnceLock::new();
+    let hidden = cached_regex(&HIDDEN, r"(?is)<!--.*?-->|<head\b.*?</head>|<script\b.*?</script>|<style\b.*?</style>");
+    let line_breaks = cached_regex(&LINE_BREAKS, r"(?i)<br\s*/?>|</?(?:p|div|h[1-6]|li|ul|ol|tr|table|section|article|blockquote|pre|header|footer|aside|nav)\b[^>]*>");
+    let tags = cached_regex(&TAGS, r"(?s)<[^>]*>");
+    let spaces = cached_regex(&SPACES, r"[ \t\u{a0}]+");
+
+    let text = hidden.replace_all(html, "");
+    let text = line_breaks.replace_all(&text, "\n");
+    let text = tags.replace_all(&text, "");
+    let text = decode_entities(&text);
+    let lines: Vec<String> = text.lines().map(|line| spaces.replace_all(line, " ").trim().to_string()).collect();
+    normalize_text(&lines.join("\n"))
+}
+
+/// Decodes the character references of XML, and the most common named ones of HTML
+pub(crate) fn decode_entities(text: &str) -> String {
+    static ENTITIES: OnceLock<Regex> = OnceLock::new();
+    let entities = cached_regex(&ENTITIES, r"&(#[0-9]+|#[xX][0-9a-fA-F]+|[a-zA-Z]+);");
+    entities.replace_all(text, |captures: &regex::Captures| {
+        let entity = &captures[1];
+        let decoded = match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            "nbsp" => Some(' '),
+            "ndash" => Some('–'),
+            "mdash" => Some('—'),
+            "hellip" => Some('…'),
+            _ if entity.starts_with("#x") || entity.starts_with("#X") => u32::from_str_radix(&entity[2..], 16).ok().and_then(char::from_u32),
+            _ if entity.starts_with('#') => entity[1..].parse().ok().and_then(char::from_u32),
+            _ => None,
+        };
+        decoded.map(String::from).unwrap_or_else(|| captures[0].to_string())
+    }).to_string()
+}
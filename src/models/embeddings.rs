@@ -0,0 +1,43 @@
+use serde::{Serialize, Deserialize};
+
+use crate::constants::model_strings::TEXT_EMBEDDING_ADA_002;
+
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct EmbeddingRequest {
+    pub model: String,
+    pub input: Vec<String>,
+}
+
+impl EmbeddingRequest {
+    pub fn new(input: Vec<String>) -> Self {
+        EmbeddingRequest { model: TEXT_EMBEDDING_ADA_002.to_string(), input }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct EmbeddingResponse {
+    pub object: String,
+    pub data: Vec<Embedding>,
+    pub model: String,
+    pub usage: EmbeddingUsage,
+}
+
+impl EmbeddingResponse {
+    /// Return cost in CENTS
+    pub fn cost(&self) -> f32 {
+        crate::constants::cost_factors::compute_embedding_cost(&self.usage) as f32 * 100.0
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct Embedding {
+    pub object: String,
+    pub embedding: Vec<f32>,
+    pub index: usize,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+pub struct EmbeddingUsage {
+    pub prompt_tokens: i32,
+    pub total_tokens: i32,
+}
@@ -1,9 +1,20 @@
 use std::collections::hash_map::DefaultHasher;
 use std::hash::{Hash, Hasher};
+use sha2::{Sha256, Digest};
 
 
 pub fn calculate_hash<T: Hash>(t: &T) -> String {
     let mut s = DefaultHasher::new();
     t.hash(&mut s);
     s.finish().to_string()
-}
\ No newline at end of file
+}
+
+/// Hex SHA-256 of `bytes`. Unlike `calculate_hash()`, this is stable across machines and Rust versions, so it is what gets written to files that are shared.
+pub fn sha256_hex(bytes: &[u8]) -> String {
+    Sha256::digest(bytes).iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+/// The `query_key_hash` column of a stored query, on which saving and syncing match rows. By `sha256_hex()`, so that it stays the same across machines and Rust versions.
+pub fn query_key_hash(query_key: &str) -> String {
+    sha256_hex(query_key.as_bytes())
+}
@@ -0,0 +1,46 @@
+use serde::{Serialize, Deserialize};
+
+/// Which project, experiment and run a query was made for. Stamped on every new query from `OpenAIAccount::labels`, plus any given per call.
+/// <br> Stored with the query in the cache and database, and filterable with `CacheSearch`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Default)]
+pub struct Labels {
+    pub project: Option<String>,
+    pub tags: Vec<String>,
+    /// Identifies one run of a script or experiment, to tell apart queries of different runs on the same project
+    pub run_id: Option<String>,
+}
+
+impl Labels {
+    pub fn project(project: &str) -> Self {
+        Labels { project: Some(project.to_string()), ..Default::default() }
+    }
+
+    pub fn with_tag(mut self, tag: &str) -> Self {
+        if !self.tags.iter().any(|t| t == tag) {
+            self.tags.push(tag.to_string());
+        }
+        self
+    }
+
+    pub fn with_run_id(mut self, run_id: &str) -> Self {
+        self.run_id = Some(run_id.to_string());
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.project.is_none() && self.tags.is_empty() && self.run_id.is_none()
+    }
+
+    /// These labels with `other` laid over them: its project and run id win where set, and the tags of both are kept
+    pub fn merged(&self, other: &Labels) -> Labels {
+        let mut merged = Labels {
+            project: other.project.clone().or(self.project.clone()),
+            tags: self.tags.clone(),
+            run_id: other.run_id.clone().or(self.run_id.clone()),
+        };
+        for tag in &other.tags {
+            merged = merged.with_tag(tag);
+        }
+        merged
+    }
+}
@@ -10,6 +10,12 @@ pub mod hash;
 pub mod queries;
 pub mod client;
 pub mod cache;
+pub mod search;
+pub mod archive;
+pub mod embeddings;
+pub mod semantic;
+pub mod labels;
+pub mod documents;
 
 // Hoist up these structs into the "::models::{}" scope, out from their individual files (they are still available there too)
 pub use req_and_res::ChatCompletionMessage;
@@ -22,5 +28,5 @@ pub use queries::{
     text_query::TextQuery, 
     chat_query::ChatQuery
 };
-pub use query::Query;
+pub use query::{Query, QueryKind};
 pub use gpt_models::GptModel;
\ No newline at end of file
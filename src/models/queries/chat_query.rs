@@ -1,29 +1,47 @@
 use serde::{Serialize, Deserialize};
+use chrono::{DateTime, Utc};
 
-use crate::{GptModel, models::ChatCompletionResponse};
+use crate::{GptModel, models::{ChatCompletionResponse, ChatCompletionMessage, labels::Labels}};
 
 pub trait Cacheable {
     fn key(&self) -> String;
 }
 
 
-/// An individual Query, representing a prompt-completion event, and its metadata <br>
-/// A query can hold a Summary event for a pdf, in which case its query_type will be PdfCompletion, and its prompt field will be a stamp of the corresponding battery used to generate the summary. See `Battery`
+/// An individual Query, representing a prompt-completion event, and its metadata
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
 pub struct ChatQuery {
-    /// The prompt that was sent for chat completion if QueryType is chat completion, else this field is field with a stamp corresponding to the question battery that was used
+    /// The prompt that was sent for chat completion, which is also its key in the cache
     pub prompt: String,
     /// The cost of the request-response interaction (both prompt and completion tokens)
     pub cost: f32,
     /// The response given to this query's prompt field. `response` holds the metrics which are tracked in a running total in this OpenAIAccount's `bill`
     pub response: ChatCompletionResponse,
-    /// The time it took from sending this Query's prompt, to receiving this Query's response.
+    /// The time it took from sending this Query's prompt, to receiving this Query's response, in milliseconds.
+    /// <br> Compatibility: chat queries cached before the unit was milliseconds hold whole seconds here. They are read as they are, not converted, since nothing stored with them tells the two apart.
     pub process_time: u64,
     /// Model used to generate the response
     pub model: GptModel,
-    /// The key in the cache for a prompt completion is the prompt, whereas the key for a PdfCompletion is the pdf's filename, which should always match its storage name on disc, plus a stamp corresponding to the battery used upon the pdf for the completion.
     pub temperature: f32,
     pub from_cache: bool,
+    /// Set when served from the cache for a near-duplicate prompt: `1.0` for a normalized-text match, else the cosine similarity of the prompt embeddings. See `SemanticOpts`
+    #[serde(default)]
+    pub similarity: Option<f32>,
+    /// When the completion was received and first cached. Entries cached before this was tracked are stamped on load.
+    #[serde(default = "Utc::now")]
+    pub created_at: DateTime<Utc>,
+    /// When the query was last served from the cache. Used for least-recently-used eviction, see `EvictionPolicy`
+    #[serde(default = "Utc::now")]
+    pub last_accessed: DateTime<Utc>,
+    /// When the entry last changed, in the cache or in the database it was pulled from. Used to sync incrementally, see `DbMethods::sync()`
+    #[serde(default = "Utc::now")]
+    pub updated_at: DateTime<Utc>,
+    /// The messages that were sent for this completion, system messages and any document included. Empty for entries cached before these were kept.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub messages: Vec<ChatCompletionMessage>,
+    /// Project, tags and run the query was made for. See `Labels`
+    #[serde(default, skip_serializing_if = "Labels::is_empty")]
+    pub labels: Labels,
 }
 
 impl ChatQuery {
@@ -1,26 +1,42 @@
 use std::path::PathBuf;
 use serde::{Serialize, Deserialize};
-use crate::{models::ChatCompletionResponse, GptModel, chat_query::Cacheable};
+use chrono::{DateTime, Utc};
+use crate::{models::{ChatCompletionResponse, ChatCompletionMessage, labels::Labels}, GptModel, chat_query::Cacheable};
 
 
-/// Query intended for running on the outputs of previous queries
-
-/// A query can hold a Summary event for a pdf, in which case its query_type will be PdfCompletion, and its prompt field will be a stamp of the corresponding battery used to generate the summary. See `Battery`
+/// Query intended for running on the outputs of previous queries, such as the answers of a `BatteryMatrix`. See `OpenAIAccount::meta_complete_battery()`
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
 pub struct MetaQuery {
-    /// The prompt that was sent for chat completion if QueryType is chat completion, else this field is field with a stamp corresponding to the question battery that was used
+    /// The prompt applied to the outputs
     pub prompt: String,
     /// The cost of the request-response interaction (both prompt and completion tokens)
     pub cost: f32,
     /// The response given to this query's prompt field. `response` holds the metrics which are tracked in a running total in this OpenAIAccount's `bill`
     pub response: ChatCompletionResponse,
-    /// The time it took from sending this Query's prompt, to receiving this Query's response.
+    /// The time it took from sending this Query's prompt, to receiving this Query's response, in milliseconds
     pub process_time: u64,
     /// Model used to generate the response
     pub model: GptModel,
-    /// The key in the cache for a prompt completion is the prompt, whereas the key for a PdfCompletion is the pdf's filename, which should always match its storage name on disc, plus a stamp corresponding to the battery used upon the pdf for the completion.
     pub temperature: f32,
     pub from_cache: bool,
+    /// Set when served from the cache for a near-duplicate prompt: `1.0` for a normalized-text match, else the cosine similarity of the prompt embeddings. See `SemanticOpts`
+    #[serde(default)]
+    pub similarity: Option<f32>,
+    /// When the completion was received and first cached. Entries cached before this was tracked are stamped on load.
+    #[serde(default = "Utc::now")]
+    pub created_at: DateTime<Utc>,
+    /// When the query was last served from the cache. Used for least-recently-used eviction, see `EvictionPolicy`
+    #[serde(default = "Utc::now")]
+    pub last_accessed: DateTime<Utc>,
+    /// When the entry last changed, in the cache or in the database it was pulled from. Used to sync incrementally, see `DbMethods::sync()`
+    #[serde(default = "Utc::now")]
+    pub updated_at: DateTime<Utc>,
+    /// The messages that were sent for this completion, system messages and any document included. Empty for entries cached before these were kept.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub messages: Vec<ChatCompletionMessage>,
+    /// Project, tags and run the query was made for. See `Labels`
+    #[serde(default, skip_serializing_if = "Labels::is_empty")]
+    pub labels: Labels,
 }
 
 impl MetaQuery {
@@ -1,33 +1,66 @@
 use serde::{Serialize, Deserialize};
+use chrono::{DateTime, Utc};
 
-use crate::{models::{ChatCompletionResponse}, GptModel};
+use crate::{models::{ChatCompletionResponse, ChatCompletionMessage, labels::Labels, documents::{Citation, PdfOpts}}, GptModel};
 
 use super::chat_query::Cacheable;
 
-/// A query can hold a Summary event for a pdf, in which case its query_type will be PdfCompletion, and its prompt field will be a stamp of the corresponding battery used to generate the summary. See `Battery`
+/// A prompt applied to a document, and its metadata. Queries made by running a `Battery` carry its stamp among their label tags. See `Battery::stamp()`
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
 pub struct TextQuery {
-    /// The prompt that was sent for chat completion if QueryType is chat completion, else this field is field with a stamp corresponding to the question battery that was used
+    /// The prompt applied to the document
     pub prompt: String,
     /// The cost of the request-response interaction (both prompt and completion tokens)
     pub cost: f32,
     /// The response given to this query's prompt field. `response` holds the metrics which are tracked in a running total in this OpenAIAccount's `bill`
     pub response: ChatCompletionResponse,
-    /// The time it took from sending this Query's prompt, to receiving this Query's response.
+    /// The time it took from sending this Query's prompt, to receiving this Query's response, in milliseconds
     pub process_time: u64,
     /// Model used to generate the response
     pub model: GptModel,
-    /// The key in the cache for a prompt completion is the prompt, whereas the key for a PdfCompletion is the pdf's filename, which should always match its storage name on disc, plus a stamp corresponding to the battery used upon the pdf for the completion.
-    /// 
+    /// The filename of the document without extension. With the prompt, it makes the key in the cache, see `TextQuery::key()`
     pub document_title: String,
     pub temperature: f32,
     pub from_cache: bool,
+    /// Set when served from the cache for a near-duplicate prompt: `1.0` for a normalized-text match, else the cosine similarity of the prompt embeddings. See `SemanticOpts`
+    #[serde(default)]
+    pub similarity: Option<f32>,
+    /// When the completion was received and first cached. Entries cached before this was tracked are stamped on load.
+    #[serde(default = "Utc::now")]
+    pub created_at: DateTime<Utc>,
+    /// When the query was last served from the cache. Used for least-recently-used eviction, see `EvictionPolicy`
+    #[serde(default = "Utc::now")]
+    pub last_accessed: DateTime<Utc>,
+    /// When the entry last changed, in the cache or in the database it was pulled from. Used to sync incrementally, see `DbMethods::sync()`
+    #[serde(default = "Utc::now")]
+    pub updated_at: DateTime<Utc>,
+    /// The messages that were sent for this completion, with the document replaced by its `StoredDocument::reference()`. Empty for entries cached before these were kept.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub messages: Vec<ChatCompletionMessage>,
+    /// Project, tags and run the query was made for. See `Labels`
+    #[serde(default, skip_serializing_if = "Labels::is_empty")]
+    pub labels: Labels,
+    /// Pages of the document the answer cites, when asked for with `PdfOpts::cite_pages`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub citations: Vec<Citation>,
+    /// The pages and cleanup the document was read with, and whether its pages were cited. The default for a whole, uncited PDF, and for other formats. See `StoredDocument::read_opts()`
+    /// <br> The key is the same whatever these are, but a cached answer is only served to a call that reads the document the same way. Otherwise it is asked again, and the new answer replaces it.
+    #[serde(default, skip_serializing_if = "PdfOpts::is_default")]
+    pub pdf: PdfOpts,
+    /// Estimated tokens `PdfOpts::cleanup` dropped from the document before it was sent. See `StoredDocument::tokens_saved`
+    #[serde(default)]
+    pub tokens_saved: usize,
 }
 
 impl TextQuery {
     pub fn key(prompt: &str, document_title: &str) -> String {
         format!("{document_title}: {prompt}")
     }
+
+    /// The citations whose quote was not found on the page cited
+    pub fn flagged_citations(&self) -> Vec<&Citation> {
+        self.citations.iter().filter(|citation| !citation.verified).collect()
+    }
 }
 
 impl Cacheable for TextQuery {
@@ -1,12 +1,14 @@
 use serde::{Serialize, Deserialize};
+use chrono::{DateTime, Utc};
 
-use crate::{
-    models::db::*,
-    GptModel
-};
+use crate::GptModel;
 use super::{
     queries::chat_query::Cacheable, 
     ChatCompletionResponse,
+    ChatCompletionMessage,
+    labels::Labels,
+    documents::{Citation, PdfOpts},
+    response::FinishReason,
     ChatQuery, 
     TextQuery, 
     MetaQuery, 
@@ -21,6 +23,34 @@ pub enum Query {
     MetaQuery(MetaQuery),
 }
 
+/// The variant of a `Query`, without its contents. Used where settings or filters apply per kind of query.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub enum QueryKind {
+    Chat,
+    Text,
+    Meta,
+}
+
+impl QueryKind {
+    /// How the kind is stored in the `kind` column of the `completions` table
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            QueryKind::Chat => "chat",
+            QueryKind::Text => "text",
+            QueryKind::Meta => "meta",
+        }
+    }
+
+    pub fn from_string(kind: &str) -> Option<QueryKind> {
+        match kind {
+            "chat" => Some(QueryKind::Chat),
+            "text" => Some(QueryKind::Text),
+            "meta" => Some(QueryKind::Meta),
+            _ => None,
+        }
+    }
+}
+
 impl Query {
     pub fn response(self) -> ChatCompletionResponse {
         match self {
@@ -30,7 +60,7 @@ impl Query {
         }
     }
 
-    pub fn model(self) -> GptModel {
+    pub fn model(&self) -> GptModel {
         match self {
             Query::ChatQuery(q) => q.model,
             Query::TextQuery(q) => q.model,
@@ -38,6 +68,165 @@ impl Query {
         }
     }
 
+    pub(crate) fn response_ref(&self) -> &ChatCompletionResponse {
+        match self {
+            Query::ChatQuery(q) => &q.response,
+            Query::TextQuery(q) => &q.response,
+            Query::MetaQuery(q) => &q.response,
+        }
+    }
+
+    /// The text of the first choice of the response, if it has any
+    pub fn content(&self) -> Option<String> {
+        self.response_ref().choices.first().and_then(|choice| choice.message.content.clone())
+    }
+
+    pub fn finish_reason(&self) -> Option<&FinishReason> {
+        self.response_ref().choices.first().map(|choice| &choice.finish_reason)
+    }
+
+    pub fn prompt(&self) -> &str {
+        match self {
+            Query::ChatQuery(q) => &q.prompt,
+            Query::TextQuery(q) => &q.prompt,
+            Query::MetaQuery(q) => &q.prompt,
+        }
+    }
+
+    /// Only a `TextQuery` is run against a document
+    pub fn document_title(&self) -> Option<&str> {
+        match self {
+            Query::TextQuery(q) => Some(&q.document_title),
+            _ => None,
+        }
+    }
+
+    /// Cost in CENTS
+    pub fn cost(&self) -> f32 {
+        match self {
+            Query::ChatQuery(q) => q.cost,
+            Query::TextQuery(q) => q.cost,
+            Query::MetaQuery(q) => q.cost,
+        }
+    }
+
+    pub fn temperature(&self) -> f32 {
+        match self {
+            Query::ChatQuery(q) => q.temperature,
+            Query::TextQuery(q) => q.temperature,
+            Query::MetaQuery(q) => q.temperature,
+        }
+    }
+
+    /// The messages that were sent for the completion, if they were kept
+    pub fn messages(&self) -> &[ChatCompletionMessage] {
+        match self {
+            Query::ChatQuery(q) => &q.messages,
+            Query::TextQuery(q) => &q.messages,
+            Query::MetaQuery(q) => &q.messages,
+        }
+    }
+
+    pub fn labels(&self) -> &Labels {
+        match self {
+            Query::ChatQuery(q) => &q.labels,
+            Query::TextQuery(q) => &q.labels,
+            Query::MetaQuery(q) => &q.labels,
+        }
+    }
+
+    pub(crate) fn labels_mut(&mut self) -> &mut Labels {
+        match self {
+            Query::ChatQuery(q) => &mut q.labels,
+            Query::TextQuery(q) => &mut q.labels,
+            Query::MetaQuery(q) => &mut q.labels,
+        }
+    }
+
+    /// Empty but for a `TextQuery` asked to cite its pages
+    pub fn citations(&self) -> &[Citation] {
+        match self {
+            Query::TextQuery(q) => &q.citations,
+            _ => &[],
+        }
+    }
+
+    /// Default but for a `TextQuery` on a PDF read with some pages or cleanup. See `TextQuery::pdf`
+    pub fn pdf(&self) -> PdfOpts {
+        match self {
+            Query::TextQuery(q) => q.pdf.clone(),
+            _ => PdfOpts::default(),
+        }
+    }
+
+    /// 0 but for a `TextQuery` on a PDF read with cleanup. See `TextQuery::tokens_saved`
+    pub fn tokens_saved(&self) -> usize {
+        match self {
+            Query::TextQuery(q) => q.tokens_saved,
+            _ => 0,
+        }
+    }
+
+    pub fn process_time(&self) -> u64 {
+        match self {
+            Query::ChatQuery(q) => q.process_time,
+            Query::TextQuery(q) => q.process_time,
+            Query::MetaQuery(q) => q.process_time,
+        }
+    }
+
+    pub fn kind(&self) -> QueryKind {
+        match self {
+            Query::ChatQuery(_) => QueryKind::Chat,
+            Query::TextQuery(_) => QueryKind::Text,
+            Query::MetaQuery(_) => QueryKind::Meta,
+        }
+    }
+
+    pub fn created_at(&self) -> DateTime<Utc> {
+        match self {
+            Query::ChatQuery(q) => q.created_at,
+            Query::TextQuery(q) => q.created_at,
+            Query::MetaQuery(q) => q.created_at,
+        }
+    }
+
+    pub fn last_accessed(&self) -> DateTime<Utc> {
+        match self {
+            Query::ChatQuery(q) => q.last_accessed,
+            Query::TextQuery(q) => q.last_accessed,
+            Query::MetaQuery(q) => q.last_accessed,
+        }
+    }
+
+    pub fn updated_at(&self) -> DateTime<Utc> {
+        match self {
+            Query::ChatQuery(q) => q.updated_at,
+            Query::TextQuery(q) => q.updated_at,
+            Query::MetaQuery(q) => q.updated_at,
+        }
+    }
+
+    /// Marks the query as changed just now, so that the next sync pushes it
+    pub(crate) fn mark_updated(&mut self) {
+        let now = Utc::now();
+        match self {
+            Query::ChatQuery(q) => q.updated_at = now,
+            Query::TextQuery(q) => q.updated_at = now,
+            Query::MetaQuery(q) => q.updated_at = now,
+        }
+    }
+
+    /// Marks the query as served just now
+    pub(crate) fn touch(&mut self) {
+        let now = Utc::now();
+        match self {
+            Query::ChatQuery(q) => q.last_accessed = now,
+            Query::TextQuery(q) => q.last_accessed = now,
+            Query::MetaQuery(q) => q.last_accessed = now,
+        }
+    }
+
     pub fn expect_as_chat(self) -> ChatQuery {
         if let Query::ChatQuery(query) = self {query} else {panic!("Expected to be a ChatQuery {self:#?}")}
     }
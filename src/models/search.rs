@@ -0,0 +1,305 @@
+use std::collections::BTreeMap;
+use chrono::{DateTime, Utc};
+use regex::Regex;
+
+use crate::{
+    models::{cache::Cache, client::core::Status, response::FinishReason},
+    GptModel,
+    Query,
+    QueryKind,
+};
+
+/// Filters, sorting and a limit over the queries in a `Cache`. Every filter left as `None` matches everything.
+/// <br> Build with the chained setters, then `.run(&cache)`. The queries in the results can be handed to `OpenAIAccount::meta_complete_queries()`, and their keys to `Cache::remove_many()`.
+///
+/// ```
+/// use openai_rs::*;
+/// use openai_rs::models::search::{CacheSearch, SortBy};
+///
+/// # fn main() -> Result<(), openai_rs::models::client::core::Status> {
+/// let search = CacheSearch::new()
+///     .kind(QueryKind::Text)
+///     .document_title("Cholesterol Paradox")
+///     .response_regex(r"(?i)ldl")?
+///     .sort_by(SortBy::Cost, true)
+///     .limit(10);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct CacheSearch {
+    pub kinds: Option<Vec<QueryKind>>,
+    pub models: Option<Vec<GptModel>>,
+    /// Exact match. Only `TextQuery`s have a document title, so this excludes the other kinds.
+    pub document_title: Option<String>,
+    /// Inclusive lower bound on `created_at`
+    pub created_after: Option<DateTime<Utc>>,
+    /// Exclusive upper bound on `created_at`
+    pub created_before: Option<DateTime<Utc>>,
+    /// Inclusive, in CENTS
+    pub min_cost: Option<f32>,
+    /// Inclusive, in CENTS
+    pub max_cost: Option<f32>,
+    pub finish_reason: Option<FinishReason>,
+    pub prompt_contains: Option<String>,
+    pub response_contains: Option<String>,
+    pub prompt_regex: Option<Regex>,
+    pub response_regex: Option<Regex>,
+    /// Exact match on `Labels::project`
+    pub project: Option<String>,
+    /// Matches queries carrying this tag among their `Labels::tags`
+    pub tag: Option<String>,
+    /// Exact match on `Labels::run_id`
+    pub run_id: Option<String>,
+    /// Field to sort by, and whether to sort descending. Unsorted results come in no particular order.
+    pub sort: Option<(SortBy, bool)>,
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortBy {
+    Key,
+    CreatedAt,
+    LastAccessed,
+    Cost,
+    ProcessTime,
+}
+
+impl CacheSearch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds `kind` to the kinds to match
+    pub fn kind(mut self, kind: QueryKind) -> Self {
+        self.kinds.get_or_insert_with(Vec::new).push(kind);
+        self
+    }
+
+    /// Adds `model` to the models to match
+    pub fn model(mut self, model: GptModel) -> Self {
+        self.models.get_or_insert_with(Vec::new).push(model);
+        self
+    }
+
+    pub fn document_title(mut self, document_title: &str) -> Self {
+        self.document_title = Some(document_title.to_string());
+        self
+    }
+
+    pub fn created_between(mut self, after: Option<DateTime<Utc>>, before: Option<DateTime<Utc>>) -> Self {
+        self.created_after = after;
+        self.created_before = before;
+        self
+    }
+
+    /// Bounds in CENTS
+    pub fn cost_between(mut self, min: Option<f32>, max: Option<f32>) -> Self {
+        self.min_cost = min;
+        self.max_cost = max;
+        self
+    }
+
+    pub fn finish_reason(mut self, finish_reason: FinishReason) -> Self {
+        self.finish_reason = Some(finish_reason);
+        self
+    }
+
+    pub fn prompt_contains(mut self, text: &str) -> Self {
+        self.prompt_contains = Some(text.to_string());
+        self
+    }
+
+    pub fn response_contains(mut self, text: &str) -> Self {
+        self.response_contains = Some(text.to_string());
+        self
+    }
+
+    /// Returns a `Status::Error` if `pattern` is not a valid regex
+    pub fn prompt_regex(mut self, pattern: &str) -> Result<Self, Status> {
+        self.prompt_regex = Some(Regex::new(pattern).map_err(|e| Status::Error(format!("Invalid prompt regex \"{pattern}\": {e}")))?);
+        Ok(self)
+    }
+
+    /// Returns a `Status::Error` if `pattern` is not a valid regex
+    pub fn response_regex(mut self, pattern: &str) -> Result<Self, Status> {
+        self.response_regex = Some(Regex::new(pattern).map_err(|e| Status::Error(format!("Invalid response regex \"{pattern}\": {e}")))?);
+        Ok(self)
+    }
+
+    pub fn project(mut self, project: &str) -> Self {
+        self.project = Some(project.to_string());
+        self
+    }
+
+    pub fn tag(mut self, tag: &str) -> Self {
+        self.tag = Some(tag.to_string());
+        self
+    }
+
+    pub fn run_id(mut self, run_id: &str) -> Self {
+        self.run_id = Some(run_id.to_string());
+        self
+    }
+
+    pub fn sort_by(mut self, sort_by: SortBy, descending: bool) -> Self {
+        self.sort = Some((sort_by, descending));
+        self
+    }
+
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// Whether a single query passes every filter
+    pub fn matches(&self, query: &Query) -> bool {
+        if let Some(kinds) = &self.kinds {
+            if !kinds.contains(&query.kind()) { return false }
+        }
+        if let Some(models) = &self.models {
+            if !models.contains(&query.model()) { return false }
+        }
+        if let Some(title) = &self.document_title {
+            if query.document_title() != Some(title.as_str()) { return false }
+        }
+        if let Some(after) = self.created_after {
+            if query.created_at() < after { return false }
+        }
+        if let Some(before) = self.created_before {
+            if query.created_at() >= before { return false }
+        }
+        if let Some(min) = self.min_cost {
+            if query.cost() < min { return false }
+        }
+        if let Some(max) = self.max_cost {
+            if query.cost() > max { return false }
+        }
+        if let Some(finish_reason) = &self.finish_reason {
+            if query.finish_reason() != Some(finish_reason) { return false }
+        }
+        if let Some(text) = &self.prompt_contains {
+            if !query.prompt().contains(text.as_str()) { return false }
+        }
+        if let Some(regex) = &self.prompt_regex {
+            if !regex.is_match(query.prompt()) { return false }
+        }
+        if let Some(project) = &self.project {
+            if query.labels().project.as_ref() != Some(project) { return false }
+        }
+        if let Some(tag) = &self.tag {
+            if !query.labels().tags.contains(tag) { return false }
+        }
+        if let Some(run_id) = &self.run_id {
+            if query.labels().run_id.as_ref() != Some(run_id) { return false }
+        }
+        if self.response_contains.is_some() || self.response_regex.is_some() {
+            let content = query.content().unwrap_or_default();
+            if let Some(text) = &self.response_contains {
+                if !content.contains(text.as_str()) { return false }
+            }
+            if let Some(regex) = &self.response_regex {
+                if !regex.is_match(&content) { return false }
+            }
+        }
+        true
+    }
+
+    /// Copies of the matching cache entries, as `(cache_key, query)`, sorted and limited
+    pub fn run(&self, cache: &Cache) -> Vec<(String, Query)> {
+        let mut results: Vec<(String, Query)> = cache.entries.iter()
+            .filter(|(_, query)| self.matches(query))
+            .map(|(key, query)| (key.clone(), query.clone()))
+            .collect();
+
+        if let Some((sort_by, descending)) = self.sort {
+            results.sort_by(|(a_key, a), (b_key, b)| {
+                let ordering = match sort_by {
+                    SortBy::Key => a_key.cmp(b_key),
+                    SortBy::CreatedAt => a.created_at().cmp(&b.created_at()),
+                    SortBy::LastAccessed => a.last_accessed().cmp(&b.last_accessed()),
+                    SortBy::Cost => a.cost().total_cmp(&b.cost()),
+                    SortBy::ProcessTime => a.process_time().cmp(&b.process_time()),
+                };
+                if descending { ordering.reverse() } else { ordering }
+            });
+        }
+
+        if let Some(limit) = self.limit {
+            results.truncate(limit);
+        }
+        results
+    }
+
+    /// Cost and token totals of the matching queries, broken down by project, run and tag. Sorting and the limit are applied first.
+    pub fn cost_report(&self, cache: &Cache) -> CostReport {
+        let mut report = CostReport::default();
+        for (_, query) in self.run(cache) {
+            let cost = query.cost();
+            let total_tokens = query.response_ref().usage.total_tokens;
+            report.queries += 1;
+            report.cost += cost;
+            report.total_tokens += total_tokens;
+
+            let labels = query.labels();
+            if let Some(project) = &labels.project {
+                report.by_project.entry(project.clone()).or_default().add(cost, total_tokens);
+            }
+            if let Some(run_id) = &labels.run_id {
+                report.by_run.entry(run_id.clone()).or_default().add(cost, total_tokens);
+            }
+            for tag in &labels.tags {
+                report.by_tag.entry(tag.clone()).or_default().add(cost, total_tokens);
+            }
+        }
+        report
+    }
+}
+
+/// Totals over the queries of a `CacheSearch`. See `CacheSearch::cost_report()`
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct CostReport {
+    pub queries: usize,
+    /// In CENTS
+    pub cost: f32,
+    pub total_tokens: i32,
+    /// Queries without a project are only counted in the overall totals
+    pub by_project: BTreeMap<String, CostTotals>,
+    pub by_run: BTreeMap<String, CostTotals>,
+    /// A query with several tags counts towards each of them
+    pub by_tag: BTreeMap<String, CostTotals>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct CostTotals {
+    pub queries: usize,
+    /// In CENTS
+    pub cost: f32,
+    pub total_tokens: i32,
+}
+
+impl CostTotals {
+    fn add(&mut self, cost: f32, total_tokens: i32) {
+        self.queries += 1;
+        self.cost += cost;
+        self.total_tokens += total_tokens;
+    }
+}
+
+impl CostReport {
+    pub fn print(&self) {
+        println!("\n");
+        println!("🧾 Cost Report");
+        println!("Queries: {}", self.queries);
+        println!("Total Tokens: {}", self.total_tokens);
+        println!("Cost: ${:.2}", self.cost / 100.0);
+        for (heading, totals) in [("Projects", &self.by_project), ("Runs", &self.by_run), ("Tags", &self.by_tag)] {
+            if totals.is_empty() { continue }
+            println!("{heading}:");
+            for (label, total) in totals {
+                println!("  {label}: {} queries, {} tokens, ${:.2}", total.queries, total.total_tokens, total.cost / 100.0);
+            }
+        }
+        println!("\n");
+    }
+}
@@ -0,0 +1,120 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::PathBuf,
+};
+use serde::{Serialize, Deserialize};
+
+use crate::{
+    models::{cache::Cache, client::{core::Status, json_file::load_json}},
+    Query,
+    QueryKind,
+};
+
+/// Settings for matching prompts that are near-duplicates of cached ones. Off unless set on `Opts::semantic`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct SemanticOpts {
+    /// Minimum cosine similarity between prompt embeddings for a cached query to be served instead. `0.0 - 1.0`
+    pub threshold: f32,
+    /// When `false`, only the normalized-text match is tried, and no embeddings are requested
+    pub use_embeddings: bool,
+    /// Where prompt embeddings are stored, keyed by cache key. Created if absent.
+    pub embeddings_filepath: PathBuf,
+}
+
+impl Default for SemanticOpts {
+    /// Defaults:
+    /// ```
+    /// use openai_rs::models::semantic::SemanticOpts;
+    ///
+    /// SemanticOpts {
+    ///     threshold: 0.95,
+    ///     use_embeddings: true,
+    ///     embeddings_filepath: "./embeddings.json".into(),
+    /// };
+    /// ```
+    fn default() -> Self {
+        SemanticOpts {
+            threshold: 0.95,
+            use_embeddings: true,
+            embeddings_filepath: "./embeddings.json".into(),
+        }
+    }
+}
+
+/// Prompt embeddings of cached queries, used to find near-duplicate prompts
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct SemanticIndex {
+    pub opts: SemanticOpts,
+    pub embeddings: HashMap<String, Vec<f32>>,
+}
+
+impl SemanticIndex {
+    /// Reads the embeddings file, or starts empty if it is absent. See `load_json()`
+    pub fn load(opts: SemanticOpts) -> Result<SemanticIndex, Status> {
+        let embeddings = load_json(&opts.embeddings_filepath)?;
+        Ok(SemanticIndex { opts, embeddings })
+    }
+
+    pub fn insert(&mut self, cache_key: String, embedding: Vec<f32>) {
+        self.embeddings.insert(cache_key, embedding);
+        self.save();
+    }
+
+    pub(crate) fn save(&self) {
+        let file = match fs::OpenOptions::new().create(true).truncate(true).write(true).open(&self.opts.embeddings_filepath) {Ok(f)=>f, Err(e)=>panic!("🧭 Could not save embeddings at {}, due to error:  ❌  {}", self.opts.embeddings_filepath.display(), e)};
+        serde_json::to_writer(&file, &self.embeddings).expect("Serialization of embeddings to embeddings file");
+    }
+
+    /// Key of a cached query whose prompt is the same as `prompt` once normalized. See `normalize_prompt()`
+    /// <br> With a `document_title`, only text queries on that document are considered, otherwise only chat queries.
+    pub fn normalized_match(&self, cache: &Cache, prompt: &str, document_title: Option<&str>) -> Option<String> {
+        let normalized = normalize_prompt(prompt);
+        cache.entries.iter()
+            .filter(|(_, query)| is_candidate(query, document_title))
+            .find(|(_, query)| normalize_prompt(query.prompt()) == normalized)
+            .map(|(key, _)| key.clone())
+    }
+
+    /// Key and similarity of the cached query whose prompt embedding is closest to `embedding`, if it reaches the threshold.
+    /// <br> Candidates are filtered as in `normalized_match()`. Cached queries without an embedding are skipped.
+    pub fn nearest(&self, cache: &Cache, embedding: &[f32], document_title: Option<&str>) -> Option<(String, f32)> {
+        cache.entries.iter()
+            .filter(|(_, query)| is_candidate(query, document_title))
+            .filter_map(|(key, _)| {
+                let other = self.embeddings.get(key)?;
+                Some((key.clone(), cosine_similarity(embedding, other)))
+            })
+            .filter(|(_, similarity)| *similarity >= self.opts.threshold)
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+    }
+}
+
+fn is_candidate(query: &Query, document_title: Option<&str>) -> bool {
+    match document_title {
+        None => query.kind() == QueryKind::Chat,
+        Some(title) => query.document_title() == Some(title),
+    }
+}
+
+/// Lowercases, drops punctuation, and collapses whitespace, so that trivially different prompts compare equal
+pub fn normalize_prompt(prompt: &str) -> String {
+    prompt
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { ' ' })
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<&str>>()
+        .join(" ")
+}
+
+/// `0.0` if either vector is all zeroes or they differ in length
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() { return 0.0 }
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm_a = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norm_b = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm_a == 0.0 || norm_b == 0.0 { return 0.0 }
+    dot / (norm_a * norm_b)
+}
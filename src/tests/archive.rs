@@ -0,0 +1,106 @@
+use std::{io::{Cursor, Write}, path::PathBuf};
+use chrono::{Duration, Utc};
+use zip::{write::FileOptions, ZipWriter};
+
+use crate::{*, models::archive::Resolution};
+use super::{answered, cache_with};
+
+fn archive_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("{name}.zip"))
+}
+
+/// An archive holding `files`, as a hand edit of one written by `export()` would be
+fn write_archive(path: &PathBuf, files: &[(&str, &str)]) {
+    let mut zip = ZipWriter::new(Cursor::new(vec![]));
+    for (name, content) in files {
+        zip.start_file(*name, FileOptions::default()).unwrap();
+        zip.write_all(content.as_bytes()).unwrap();
+    }
+    std::fs::write(path, zip.finish().unwrap().into_inner()).unwrap();
+}
+
+fn read_archive(path: &PathBuf, name: &str) -> String {
+    let mut archive = zip::ZipArchive::new(std::fs::File::open(path).unwrap()).unwrap();
+    std::io::read_to_string(archive.by_name(name).unwrap()).unwrap()
+}
+
+#[test]
+fn export_and_import_round_trip() {
+    let mut mine = cache_with("openai_rs_archive_mine.json", vec![]);
+    mine.insert(&answered("shared", "same", Utc::now() - Duration::days(2)));
+    mine.insert(&answered("conflict", "my answer", Utc::now() - Duration::days(1)));
+
+    let mut theirs = cache_with("openai_rs_archive_theirs.json", vec![]);
+    theirs.insert(&mine.entries[&ChatQuery::key("shared")].clone());
+    theirs.insert(&answered("conflict", "their answer", Utc::now()));
+    theirs.insert(&answered("new", "only theirs", Utc::now()));
+    theirs.insert(&answered("filtered out", "not exported", Utc::now()));
+
+    let path = archive_path("openai_rs_archive_round_trip");
+    let search = CacheSearch::new().prompt_regex("^(shared|conflict|new)$").unwrap();
+    let manifest = theirs.export(&path, Some(&search)).expect("export");
+    assert_eq!(manifest.entry_count, 3);
+
+    let dry = mine.import(&path, ConflictPolicy::KeepNewest, true).expect("dry run");
+    assert_eq!(dry.added, vec![ChatQuery::key("new")]);
+    assert_eq!(dry.unchanged, vec![ChatQuery::key("shared")]);
+    assert_eq!(dry.conflicts.len(), 1);
+    assert_eq!(dry.conflicts[0].resolution, Resolution::TookTheirs);
+    assert_eq!(mine.entries.len(), 2, "a dry run changes nothing");
+
+    let report = mine.import(&path, ConflictPolicy::KeepNewest, false).expect("import");
+    assert_eq!(report.conflicts, dry.conflicts);
+    assert_eq!(mine.entries.len(), 3);
+    assert_eq!(mine.entries[&ChatQuery::key("conflict")].content().unwrap(), "their answer");
+    assert_eq!(mine.graveyard.versions(&ChatQuery::key("conflict"))[0].query.content().unwrap(), "my answer");
+}
+
+#[test]
+fn conflicts_can_go_to_the_graveyard() {
+    let mut mine = cache_with("openai_rs_archive_bury_mine.json", vec![]);
+    mine.insert(&answered("conflict", "my answer", Utc::now()));
+    let mut theirs = cache_with("openai_rs_archive_bury_theirs.json", vec![]);
+    theirs.insert(&answered("conflict", "their answer", Utc::now()));
+
+    let path = archive_path("openai_rs_archive_bury");
+    theirs.export(&path, None).expect("export");
+    mine.import(&path, ConflictPolicy::Graveyard, false).expect("import");
+
+    let key = ChatQuery::key("conflict");
+    assert_eq!(mine.entries[&key].content().unwrap(), "my answer");
+    assert_eq!(mine.graveyard.versions(&key)[0].query.content().unwrap(), "their answer");
+}
+
+#[test]
+fn tampered_archives_are_rejected() {
+    let mut theirs = cache_with("openai_rs_archive_tampered.json", vec![]);
+    theirs.insert(&answered("question", "answer", Utc::now()));
+
+    let path = archive_path("openai_rs_archive_tampered");
+    theirs.export(&path, None).expect("export");
+    let manifest = read_archive(&path, "manifest.json");
+    write_archive(&path, &[("queries.jsonl", ""), ("manifest.json", &manifest)]);
+
+    let mut mine = cache_with("openai_rs_archive_tampered_mine.json", vec![]);
+    assert!(mine.import(&path, ConflictPolicy::KeepMine, false).is_err());
+}
+
+#[test]
+fn entries_stored_under_another_key_are_rejected() {
+    let mut theirs = cache_with("openai_rs_archive_rekeyed.json", vec![]);
+    theirs.insert(&answered("question", "answer", Utc::now()));
+    theirs.insert(&answered("other", "other answer", Utc::now()));
+
+    let path = archive_path("openai_rs_archive_rekeyed");
+    theirs.export(&path, None).expect("export");
+    let jsonl = read_archive(&path, "queries.jsonl").replace(&format!("\"key\":\"{}\"", ChatQuery::key("other")), "\"key\":\"Chat: renamed\"");
+    let mut manifest: serde_json::Value = serde_json::from_str(&read_archive(&path, "manifest.json")).unwrap();
+    manifest["checksum"] = crate::models::hash::sha256_hex(jsonl.as_bytes()).into();
+    write_archive(&path, &[("queries.jsonl", &jsonl), ("manifest.json", &manifest.to_string())]);
+
+    let mut mine = cache_with("openai_rs_archive_rekeyed_mine.json", vec![]);
+    let report = mine.import(&path, ConflictPolicy::KeepMine, false).expect("import");
+    assert_eq!(report.added, vec![ChatQuery::key("question")]);
+    assert_eq!(report.rejected, vec!["Chat: renamed".to_string()]);
+    assert_eq!(mine.entries.len(), 1);
+}
@@ -0,0 +1,125 @@
+use std::{sync::{Arc, Mutex}, time::{Duration, Instant}};
+use chrono::Utc;
+
+use crate::{
+    *,
+    constants::cost_factors::BATCH_DISCOUNT,
+    models::{
+        api_error::APIError,
+        client::{batch::BatchOpts, batch_job::{batch_input_file, batch_results, BatchEntry, BatchItem, PendingBatches}, requests::{RateLimit, RateLimiter}},
+        Bill,
+        ChatCompletionRequest,
+    },
+};
+use super::{cache_with, chat_query};
+
+#[tokio::test]
+async fn rate_limits_space_out_concurrent_requests() {
+    let limiter = Arc::new(RateLimiter::new(RateLimit { requests_per_minute: 1200 }));
+    let start = Instant::now();
+
+    // Four requests at once take three intervals of 50ms between them
+    let requests = (0..4).map(|_| {
+        let limiter = limiter.clone();
+        async move { limiter.acquire().await }
+    });
+    futures::future::join_all(requests).await;
+
+    let elapsed = start.elapsed();
+    assert!(elapsed >= Duration::from_millis(150), "took {elapsed:?}");
+    assert!(elapsed < Duration::from_millis(1000), "took {elapsed:?}");
+}
+
+#[tokio::test]
+async fn batches_answer_in_order_keep_what_succeeds_and_send_repeats_once() {
+    let mut account = OpenAIAccount::default();
+    account.cache = cache_with("openai_rs_batch_run.json", vec![]);
+    account.bill = Bill { filepath: std::env::temp_dir().join("openai_rs_batch_run_bill.json"), ..Default::default() };
+
+    let sent = Mutex::new(vec![]);
+    let prompts = ["first", "fails", "second", "first"];
+    let results = account.get_completions_over(&prompts, &BatchOpts { concurrency: 3 }, |req| {
+        let prompt = req.messages[0].content.clone().unwrap_or_default();
+        sent.lock().unwrap().push(prompt.clone());
+        async move {
+            // The first prompt is answered last, so that the order of the results is up to the batch
+            tokio::time::sleep(Duration::from_millis(if prompt == "first" { 50 } else { 5 })).await;
+            match prompt.as_str() {
+                "fails" => Err(APIError { message: "Server error".to_string() }),
+                _ => Ok(chat_query(&prompt, Utc::now()).response),
+            }
+        }
+    }).await;
+
+    let mut sent = sent.into_inner().unwrap();
+    sent.sort();
+    assert_eq!(sent, vec!["fails", "first", "second"]);
+
+    let prompts_answered: Vec<Option<&str>> = results.iter().map(|result| result.as_ref().ok().map(|query| query.prompt.as_str())).collect();
+    assert_eq!(prompts_answered, vec![Some("first"), None, Some("second"), Some("first")]);
+    assert!(results[3].as_ref().unwrap().from_cache);
+    assert_eq!(account.bill.query_count, 2);
+    assert!(account.cache.entries.contains_key(&ChatQuery::key("second")));
+    assert!(!account.cache.entries.contains_key(&ChatQuery::key("fails")));
+}
+
+#[test]
+fn batch_files_round_trip_into_discounted_queries() {
+    let entry = |prompt: &str, document_title: Option<&str>| BatchEntry {
+        prompt: prompt.to_string(),
+        document_title: document_title.map(str::to_string),
+        model: GptModel::Gpt35Turbo,
+        temperature: 0.5,
+        messages: vec![],
+        labels: Labels::project("cholesterol"),
+        pdf: PdfOpts::default(),
+        tokens_saved: 0,
+    };
+    let items = vec![
+        BatchItem { entry: entry("answered", None), request: ChatCompletionRequest::default() },
+        BatchItem { entry: entry("summarize", Some("Paper")), request: ChatCompletionRequest::default() },
+        BatchItem { entry: entry("refused", None), request: ChatCompletionRequest::default() },
+        BatchItem { entry: entry("lost", None), request: ChatCompletionRequest::default() },
+    ];
+    let (input, entries) = batch_input_file(&items).unwrap();
+    let input = String::from_utf8(input).unwrap();
+    assert_eq!(input.lines().count(), 4);
+    assert!(input.lines().next().unwrap().contains(r#""custom_id":"item-0","method":"POST","url":"/v1/chat/completions""#));
+
+    let response = serde_json::to_value(chat_query("answered", Utc::now()).response).unwrap();
+    let output = [
+        serde_json::json!({ "custom_id": "item-0", "response": { "status_code": 200, "body": response } }),
+        serde_json::json!({ "custom_id": "item-1", "response": { "status_code": 200, "body": response } }),
+        serde_json::json!({ "custom_id": "item-2", "response": null, "error": { "code": "invalid_request", "message": "Too long" } }),
+    ].iter().map(|line| line.to_string()).collect::<Vec<String>>().join("\n");
+
+    let mut results = batch_results(&entries, output.as_bytes()).unwrap();
+    assert_eq!(results.queries.len(), 2);
+    let text = results.queries.iter().find(|query| query.kind() == QueryKind::Text).expect("text query");
+    assert_eq!(text.key(), "Paper: summarize");
+    assert_eq!(text.labels().project.as_deref(), Some("cholesterol"));
+    let full_price = text.response_ref().cost(&GptModel::Gpt35Turbo);
+    assert!((text.cost() - full_price * BATCH_DISCOUNT).abs() < 1e-9);
+
+    results.failed.sort();
+    assert_eq!(results.failed, vec![
+        ("Chat: lost".to_string(), "Missing from the batch output".to_string()),
+        ("Chat: refused".to_string(), "Too long".to_string()),
+    ]);
+}
+
+#[test]
+fn malformed_jobs_files_are_not_read_as_empty() {
+    let filepath = std::env::temp_dir().join("openai_rs_batch_jobs.json");
+    let _ = std::fs::remove_file(&filepath);
+    assert_eq!(PendingBatches::load(&filepath).unwrap(), PendingBatches::default());
+
+    let mut pending = PendingBatches::default();
+    pending.jobs.insert("batch_1".to_string(), Default::default());
+    pending.save(&filepath).unwrap();
+    assert_eq!(PendingBatches::load(&filepath).unwrap(), pending);
+
+    std::fs::write(&filepath, "{\"jobs\": {\"batch_1\"").unwrap();
+    assert!(PendingBatches::load(&filepath).is_err());
+    let _ = std::fs::remove_file(&filepath);
+}
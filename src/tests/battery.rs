@@ -0,0 +1,76 @@
+use std::path::PathBuf;
+use chrono::Utc;
+
+use crate::{*, models::client::battery::BatteryRow};
+use super::text_query;
+
+const TOML: &str = r#"
+name = "lipids"
+version = "2"
+description = "What each paper found"
+
+[[prompts]]
+id = "population"
+prompt = "Which population was studied?"
+
+[[prompts]]
+id = "finding"
+prompt = "What was found, in one sentence?"
+"#;
+
+const YAML: &str = "
+name: lipids
+version: '2'
+prompts:
+  - id: population
+    prompt: Which population was studied?
+  - id: finding
+    prompt: What was found, in one sentence?
+";
+
+#[test]
+fn batteries_load_from_toml_and_yaml() {
+    let battery = Battery::from_toml(TOML).unwrap();
+    assert_eq!(battery.stamp(), "battery:lipids@2");
+    assert_eq!(battery.prompts.iter().map(|prompt| prompt.id.as_str()).collect::<Vec<_>>(), vec!["population", "finding"]);
+    assert_eq!(battery, Battery { description: Some("What each paper found".to_string()), ..Battery::from_yaml(YAML).unwrap() });
+
+    let path = std::env::temp_dir().join(format!("openai_rs_battery_{}.yml", std::process::id()));
+    std::fs::write(&path, YAML).unwrap();
+    assert_eq!(Battery::load(&path).unwrap(), Battery::from_yaml(YAML).unwrap());
+    std::fs::remove_file(&path).unwrap();
+
+    assert!(Battery::from_toml("name = \"lipids\"\nversion = \"2\"\nprompts = []").is_err());
+    assert!(Battery::from_yaml(&format!("{YAML}  - id: finding\n    prompt: Again?\n")).is_err());
+    assert!(Battery::load(&PathBuf::from("battery.json")).is_err());
+}
+
+#[test]
+fn matrices_export_to_csv() {
+    let battery = Battery::from_toml(TOML).unwrap();
+    let now = Utc::now();
+    let mut matrix = BatteryMatrix::new(&battery);
+    matrix.rows.push(BatteryRow {
+        path: PathBuf::from("./pdfs/Cholesterol Paradox.pdf"),
+        document_title: "Cholesterol Paradox".to_string(),
+        cells: battery.prompts.iter().map(|prompt| Ok(text_query(&prompt.prompt, "Cholesterol Paradox", now))).collect(),
+    });
+    let mut cached = text_query("Which population was studied?", "Is Higher Better", now);
+    cached.from_cache = true;
+    matrix.rows.push(BatteryRow {
+        path: PathBuf::from("./pdfs/Is Higher Better.pdf"),
+        document_title: "Is Higher Better".to_string(),
+        cells: vec![Ok(cached), Err("Timeout".to_string())],
+    });
+
+    assert_eq!(matrix.answer("Cholesterol Paradox", "finding").as_deref(), Some("An answer to: What was found, in one sentence?"));
+    assert_eq!(matrix.answer("Is Higher Better", "finding"), None);
+    assert_eq!(matrix.failed(), vec![("Is Higher Better", "finding", "Timeout")]);
+    assert_eq!(matrix.queries().len(), 3);
+    assert!((matrix.cost() - 0.02).abs() < 1e-6);
+    assert_eq!(matrix.to_csv(), concat!(
+        "document,population,finding\n",
+        "Cholesterol Paradox,An answer to: Which population was studied?,\"An answer to: What was found, in one sentence?\"\n",
+        "Is Higher Better,An answer to: Which population was studied?,\n",
+    ));
+}
@@ -0,0 +1,407 @@
+use sea_orm::DatabaseBackend;
+
+use crate::models::{
+    client::{core::Status, database::DbMethods},
+    db::migration::{json_column, CompletionsColumn, Migrator, MigratorTrait, PostgresQueryBuilder, SqliteQueryBuilder, Table},
+};
+
+#[test]
+fn migrations_are_uniquely_named_in_order() {
+    let names: Vec<String> = Migrator::migrations().iter().map(|m| m.name().to_string()).collect();
+    let mut sorted = names.clone();
+    sorted.sort();
+    sorted.dedup();
+    assert_eq!(names, sorted);
+}
+
+#[test]
+fn json_columns_are_binary_only_on_postgres() {
+    let table = |backend| Table::create().table(CompletionsColumn::Response).col(json_column(backend, CompletionsColumn::Response).not_null()).to_owned();
+    assert!(table(DatabaseBackend::Postgres).to_string(PostgresQueryBuilder).contains("\"response\" jsonb"));
+    let sqlite = table(DatabaseBackend::Sqlite).to_string(SqliteQueryBuilder);
+    assert!(!sqlite.contains("jsonb") && !sqlite.contains("json_binary"), "{sqlite}");
+}
+
+#[cfg(feature = "sqlite")]
+mod sqlite {
+    use chrono::Utc;
+
+    use futures::StreamExt;
+    use sea_orm::{ConnectionTrait, EntityTrait};
+
+    use crate::{*, models::{client::{core::Status, database::DbMethods, db_delete::DeleteMode, full_text::FullTextSearch, sync::SyncResolution}, db::{completions, migration::{Migrator, MigratorTrait}}}};
+    use crate::tests::{cache_with, chat_query, text_query};
+
+    pub(crate) async fn migrated_db() -> DbMethods {
+        DbMethods::connect("sqlite::memory:", &PoolOpts::default(), true).await.expect("in-memory sqlite")
+    }
+
+    #[tokio::test]
+    async fn migrations_apply_to_sqlite() {
+        let db = migrated_db().await;
+        let status = db.migration_status().await.unwrap();
+        assert!(status.pending.is_empty());
+        assert_eq!(status.version.as_deref(), Some("m20261019_000006_stable_query_key_hashes"));
+
+    }
+
+    #[tokio::test]
+    async fn queries_round_trip_through_sqlite() {
+        let db = migrated_db().await;
+        let mut query = chat_query("stored", Utc::now());
+        query.labels = Labels::project("cholesterol").with_tag("review").with_run_id("run-1");
+        let cache = cache_with("openai_rs_db_round_trip.json", vec![Query::ChatQuery(query.clone())]);
+
+        let id = db.insert_query(query.key(), &cache).await.expect("insert");
+        let stored = db.get_query(&query.key()).await.unwrap().expect("stored query").expect_as_chat();
+        assert_eq!(stored.prompt, query.prompt);
+        assert_eq!(stored.response, query.response);
+        assert_eq!(stored.labels, query.labels);
+        assert_eq!(db.read_all().await.unwrap().len(), 1);
+
+        db.delete_query_by_id(id).await.unwrap();
+        assert!(db.read_all().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn rows_of_the_per_kind_tables_move_into_completions() {
+        let db = DbMethods::connect("sqlite::memory:", &PoolOpts::default(), false).await.unwrap();
+        let conn = db.conn().unwrap();
+        Migrator::up(conn, Some(2)).await.unwrap();
+
+        let response = serde_json::to_string(&chat_query("old", Utc::now()).response).unwrap();
+        for (table, title_column, title_value) in [("chat_completions", "", ""), ("text_completions", "document_title, ", "'Paper', ")] {
+            conn.execute_unprepared(&format!(
+                "INSERT INTO {table} (timestamp, model, temperature, prompt, {title_column}query_key, query_key_hash, prompt_tokens, completion_tokens, total_tokens, process_time, response, cost) \
+                 VALUES ('2026-01-01 00:00:00', 'gpt-3.5-turbo', 0.5, 'old', {title_value}'{table}: old', '{table}', 10, 20, 30, 1, '{response}', 0.01)"
+            )).await.unwrap();
+        }
+
+        db.migrate().await.unwrap();
+        let stored = db.read_all().await.unwrap();
+        assert_eq!(stored.len(), 2);
+        assert_eq!(stored["chat_completions: old"].kind(), QueryKind::Chat);
+        assert_eq!(stored["text_completions: old"].document_title(), Some("Paper"));
+        assert_eq!(db.get_queries(QueryKind::Text).await.unwrap().len(), 1);
+
+        Migrator::down(conn, Some(4)).await.unwrap();
+        let rows = conn.query_all(sea_orm::Statement::from_string(conn.get_database_backend(), "SELECT document_title FROM text_completions")).await.unwrap();
+        assert_eq!(rows[0].try_get::<String>("", "document_title").unwrap(), "Paper");
+    }
+
+    #[tokio::test]
+    async fn rows_saved_with_the_old_key_hashes_still_match_their_key() {
+        let db = DbMethods::connect("sqlite::memory:", &PoolOpts::default(), false).await.unwrap();
+        let conn = db.conn().unwrap();
+        Migrator::up(conn, Some(5)).await.unwrap();
+
+        let query = Query::ChatQuery(chat_query("rehashed", Utc::now()));
+        let mut cache = cache_with("openai_rs_db_rehash.json", vec![query.clone()]);
+        db.insert_cache_in_chunks(&cache, 10).await.unwrap();
+        let old_hash = crate::models::hash::calculate_hash(&query.key().as_str());
+        conn.execute_unprepared(&format!("UPDATE completions SET query_key_hash = '{old_hash}'")).await.unwrap();
+
+        db.migrate().await.unwrap();
+        let rows = completions::Entity::find().all(conn).await.unwrap();
+        assert_eq!(rows[0].query_key_hash, crate::models::hash::query_key_hash(&query.key()));
+
+        // Saving again updates the row in place rather than adding a second one
+        cache.entries.insert(query.key(), Query::ChatQuery(ChatQuery { labels: Labels::project("moved"), ..query.clone().expect_as_chat() }));
+        let report = db.insert_cache_in_chunks(&cache, 10).await.unwrap();
+        assert_eq!((report.inserted, report.updated), (0, 1));
+        assert_eq!(completions::Entity::find().all(conn).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn sync_moves_only_what_changed_since_the_watermark() {
+        let db = migrated_db().await;
+        let mut laptop = cache_with("openai_rs_sync_laptop.json", vec![
+            Query::ChatQuery(chat_query("first", Utc::now())),
+            Query::ChatQuery(chat_query("second", Utc::now())),
+        ]);
+        let mut desktop = cache_with("openai_rs_sync_desktop.json", vec![]);
+        let laptop_watermark = std::env::temp_dir().join("openai_rs_sync_laptop_watermark.json");
+        let desktop_watermark = std::env::temp_dir().join("openai_rs_sync_desktop_watermark.json");
+        let _ = std::fs::remove_file(&laptop_watermark);
+        let _ = std::fs::remove_file(&desktop_watermark);
+
+        let report = db.sync(&mut laptop, &laptop_watermark, ConflictPolicy::KeepNewest).await.unwrap();
+        assert_eq!((report.pushed.len(), report.pulled.len()), (2, 0));
+        let report = db.sync(&mut desktop, &desktop_watermark, ConflictPolicy::KeepNewest).await.unwrap();
+        assert_eq!((report.pushed.len(), report.pulled.len()), (0, 2));
+
+        // Both edit the same entry; the laptop edits last
+        let key = ChatQuery::key("first");
+        for (cache, answer) in [(&mut desktop, "From the desktop"), (&mut laptop, "From the laptop")] {
+            let mut edited = cache.entries[&key].clone().expect_as_chat();
+            edited.response.choices[0].message.content = Some(answer.to_string());
+            edited.updated_at = Utc::now();
+            cache.entries.insert(key.clone(), Query::ChatQuery(edited));
+        }
+        let report = db.sync(&mut desktop, &desktop_watermark, ConflictPolicy::KeepNewest).await.unwrap();
+        assert_eq!((report.pushed.len(), report.pulled.len()), (1, 0));
+
+        let report = db.sync(&mut laptop, &laptop_watermark, ConflictPolicy::KeepNewest).await.unwrap();
+        assert_eq!(report.conflicts.len(), 1);
+        assert_eq!(report.conflicts[0].resolution, SyncResolution::Pushed);
+        assert_eq!(db.get_query(&key).await.unwrap().unwrap().content().as_deref(), Some("From the laptop"));
+
+        let report = db.sync(&mut desktop, &desktop_watermark, ConflictPolicy::KeepNewest).await.unwrap();
+        assert_eq!((report.pushed, report.pulled), (vec![], vec![key.clone()]));
+        assert_eq!(desktop.entries[&key].content().as_deref(), Some("From the laptop"));
+        // The row the desktop replaced, then its own edit
+        let buried = desktop.graveyard.versions(&key);
+        assert_eq!(buried.len(), 2);
+        assert_eq!(buried[1].query.content().as_deref(), Some("From the desktop"));
+    }
+
+    #[tokio::test]
+    async fn sync_neither_pulls_back_nor_reports_what_it_did_not_change() {
+        let db = migrated_db().await;
+        let mut laptop = cache_with("openai_rs_sync_own_rows.json", vec![Query::ChatQuery(chat_query("mine", Utc::now()))]);
+        let watermark = std::env::temp_dir().join("openai_rs_sync_own_rows_watermark.json");
+        let _ = std::fs::remove_file(&watermark);
+        let report = db.sync(&mut laptop, &watermark, ConflictPolicy::KeepNewest).await.unwrap();
+        assert_eq!(report.pushed, vec![ChatQuery::key("mine")]);
+
+        // Left unsaved, so that a pulled back row would overwrite it
+        let key = ChatQuery::key("mine");
+        let mut unsaved = laptop.entries[&key].clone().expect_as_chat();
+        unsaved.response.choices[0].message.content = Some("Not yet saved".to_string());
+        laptop.entries.insert(key.clone(), Query::ChatQuery(unsaved));
+        let report = db.sync(&mut laptop, &watermark, ConflictPolicy::KeepNewest).await.unwrap();
+        assert!(report.pulled.is_empty());
+        assert_eq!(laptop.entries[&key].content().as_deref(), Some("Not yet saved"));
+
+        // From scratch, every entry is a candidate, but only what differs is written
+        let mut desktop = cache_with("openai_rs_sync_own_rows_desktop.json", vec![]);
+        let desktop_watermark = std::env::temp_dir().join("openai_rs_sync_own_rows_desktop_watermark.json");
+        let _ = std::fs::remove_file(&desktop_watermark);
+        db.sync(&mut desktop, &desktop_watermark, ConflictPolicy::KeepNewest).await.unwrap();
+        let _ = std::fs::remove_file(&desktop_watermark);
+        let report = db.sync(&mut desktop, &desktop_watermark, ConflictPolicy::KeepNewest).await.unwrap();
+        assert!(report.pushed.is_empty());
+    }
+
+    #[tokio::test]
+    async fn sync_watermarks_compare_as_instants_whatever_their_offset() {
+        let db = migrated_db().await;
+        let before = Utc::now() - chrono::Duration::seconds(1);
+        db.insert_cache(&cache_with("openai_rs_sync_offset_source.json", vec![Query::ChatQuery(chat_query("written", Utc::now()))])).await.unwrap();
+        let after = Utc::now() + chrono::Duration::seconds(1);
+
+        // Rows are stamped in UTC, not the local time of the machine
+        let stamped = completions::Entity::find().one(db.conn().unwrap()).await.unwrap().unwrap().timestamp.and_utc();
+        assert!(before <= stamped && stamped <= after, "{stamped} is not between {before} and {after}");
+
+        let watermark = std::env::temp_dir().join("openai_rs_sync_offset_watermark.json");
+        for (since, offset_hours, pulled) in [(before, 5, 1), (after, -7, 0)] {
+            let since = since.with_timezone(&chrono::FixedOffset::east_opt(offset_hours * 3600).unwrap());
+            std::fs::write(&watermark, format!("{{\"last_synced_at\": \"{}\"}}", since.to_rfc3339())).unwrap();
+            let mut cache = cache_with("openai_rs_sync_offset.json", vec![]);
+            let report = db.sync(&mut cache, &watermark, ConflictPolicy::KeepNewest).await.unwrap();
+            assert_eq!(report.pulled.len(), pulled, "since {since}");
+        }
+        let _ = std::fs::remove_file(&watermark);
+    }
+
+    #[tokio::test]
+    async fn searches_by_save_time_whatever_the_offset_of_the_bounds() {
+        let db = migrated_db().await;
+        let east = chrono::FixedOffset::east_opt(9 * 3600).unwrap();
+        let west = chrono::FixedOffset::west_opt(5 * 3600).unwrap();
+        let before = (Utc::now() - chrono::Duration::seconds(1)).with_timezone(&east);
+        db.insert_cache(&cache_with("openai_rs_db_search_saved.json", vec![Query::ChatQuery(chat_query("saved", Utc::now()))])).await.unwrap();
+        let after = (Utc::now() + chrono::Duration::seconds(1)).with_timezone(&west);
+
+        let saved_between = |after: Option<chrono::DateTime<chrono::FixedOffset>>, before: Option<chrono::DateTime<chrono::FixedOffset>>| {
+            DbSearch::new().saved_between(after.map(|after| after.with_timezone(&Utc)), before.map(|before| before.with_timezone(&Utc)))
+        };
+        assert_eq!(db.count(&saved_between(Some(before), Some(after))).await.unwrap(), 1);
+        assert_eq!(db.count(&saved_between(Some(after), None)).await.unwrap(), 0);
+        assert_eq!(db.count(&saved_between(None, Some(before))).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn text_queries_keep_how_their_document_was_read_in_the_database() {
+        let db = migrated_db().await;
+        let mut query = text_query("Summarize", "Paper", Utc::now());
+        query.pdf = PdfOpts { pages: Some(vec![1..=3]), ..Default::default() };
+        db.insert_cache(&cache_with("openai_rs_db_pdf_opts.json", vec![Query::TextQuery(query.clone())])).await.unwrap();
+        let stored = db.get_query(&TextQuery::key("Summarize", "Paper")).await.unwrap().unwrap();
+        assert_eq!(stored.pdf(), query.pdf);
+    }
+
+    #[tokio::test]
+    async fn searches_filter_sort_and_page_in_the_database() {
+        let db = migrated_db().await;
+        let mut queries = vec![];
+        for (i, prompt) in ["About LDL", "about ldl levels", "Unrelated", "50% of LDL_C"].iter().enumerate() {
+            let mut query = chat_query(prompt, Utc::now());
+            query.cost = i as f32;
+            queries.push(Query::ChatQuery(query));
+        }
+        queries.push(Query::TextQuery(text_query("LDL in the paper", "Paper", Utc::now())));
+        db.insert_cache(&cache_with("openai_rs_db_search.json", queries)).await.unwrap();
+
+        let ldl = DbSearch::new().prompt_contains("LDL");
+        assert_eq!(db.count(&ldl).await.unwrap(), 4);
+        assert_eq!(db.count(&ldl.clone().kind(QueryKind::Chat).cost_between(Some(1.0), None)).await.unwrap(), 2);
+        assert_eq!(db.count(&DbSearch::new().prompt_contains("0% of ldl_")).await.unwrap(), 1);
+        assert_eq!(db.count(&DbSearch::new().prompt_contains("_")).await.unwrap(), 1);
+        assert_eq!(db.count(&DbSearch::new().document_title("Paper")).await.unwrap(), 1);
+
+        let by_cost = ldl.clone().kind(QueryKind::Chat).sort_by(DbSortBy::Cost, true);
+        let page = db.search(&by_cost, 0, 2).await.unwrap();
+        assert_eq!((page.total, page.pages), (3, 2));
+        let prompts: Vec<&str> = page.queries.iter().map(|(_, query)| query.prompt()).collect();
+        assert_eq!(prompts, vec!["50% of LDL_C", "about ldl levels"]);
+        assert_eq!(db.search(&by_cost, 1, 2).await.unwrap().queries.len(), 1);
+
+        let streamed: Vec<(String, Query)> = db.stream(&ldl).await.unwrap().map(|row| row.unwrap()).collect().await;
+        assert_eq!(streamed.len(), 4);
+    }
+
+    #[tokio::test]
+    async fn full_text_search_ranks_and_highlights() {
+        let db = migrated_db().await;
+        let mut paper = text_query("Summarize", "Paper", Utc::now());
+        paper.response.choices[0].message.content = Some("Statins lower LDL. Statins have side effects. LDL is not the whole story.".to_string());
+        let mut chat = chat_query("What do statins do?", Utc::now());
+        chat.response.choices[0].message.content = Some("They lower cholesterol.".to_string());
+        let unrelated = chat_query("Weather", Utc::now());
+        db.insert_cache(&cache_with("openai_rs_db_full_text.json", vec![Query::TextQuery(paper), Query::ChatQuery(chat), Query::ChatQuery(unrelated)])).await.unwrap();
+
+        let hits = db.full_text_search(&FullTextSearch::new("statins")).await.unwrap();
+        assert_eq!(hits.len(), 2);
+        assert_eq!(hits[0].key, TextQuery::key("Summarize", "Paper"));
+        assert!(hits[0].rank >= hits[1].rank);
+        assert!(hits[0].snippet.contains("<b>Statins</b>"));
+
+        let scoped = db.full_text_search(&FullTextSearch::new("statins").kind(QueryKind::Chat)).await.unwrap();
+        assert_eq!(scoped.len(), 1);
+        assert_eq!(scoped[0].query.prompt(), "What do statins do?");
+        assert!(db.full_text_search(&FullTextSearch::new("\"statins OR").document_title("Other")).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn saving_the_cache_upserts_and_buries_replaced_rows() {
+        let db = migrated_db().await;
+        let kept = Query::ChatQuery(chat_query("kept", Utc::now()));
+        let changed = Query::TextQuery(text_query("changed", "Paper", Utc::now()));
+        let mut cache = cache_with("openai_rs_db_upsert.json", vec![kept.clone(), changed.clone()]);
+
+        let report = db.insert_cache_in_chunks(&cache, 1).await.unwrap();
+        assert_eq!((report.inserted, report.updated, report.unchanged), (2, 0, 0));
+
+        let mut rewritten = text_query("changed", "Paper", Utc::now());
+        rewritten.response.choices[0].message.content = Some("A different answer".to_string());
+        let added = Query::ChatQuery(chat_query("added", Utc::now()));
+        cache.entries.insert(changed.key(), Query::TextQuery(rewritten.clone()));
+        cache.entries.insert(added.key(), added);
+
+        let report = db.insert_cache_in_chunks(&cache, 1).await.unwrap();
+        assert_eq!((report.inserted, report.updated, report.unchanged), (1, 1, 1));
+
+        let stored = db.read_all().await.unwrap();
+        assert_eq!(stored.len(), 3);
+        assert_eq!(stored[&changed.key()].response_ref(), Query::TextQuery(rewritten).response_ref());
+        let buried = cache.graveyard.versions(&changed.key());
+        assert_eq!(buried.len(), 1);
+        assert_eq!(buried[0].query.response_ref(), changed.response_ref());
+    }
+
+    #[tokio::test]
+    async fn saving_the_cache_pushes_changed_labels_with_the_same_response() {
+        let db = migrated_db().await;
+        let query = chat_query("relabeled", Utc::now());
+        let mut cache = cache_with("openai_rs_db_upsert_labels.json", vec![Query::ChatQuery(query.clone())]);
+        db.insert_cache_in_chunks(&cache, 10).await.unwrap();
+
+        let report = db.insert_cache_in_chunks(&cache, 10).await.unwrap();
+        assert_eq!((report.inserted, report.updated, report.unchanged), (0, 0, 1));
+
+        let relabeled = ChatQuery { labels: Labels::default().with_tag("reviewed"), ..query };
+        cache.entries.insert(relabeled.key(), Query::ChatQuery(relabeled.clone()));
+        let report = db.insert_cache_in_chunks(&cache, 10).await.unwrap();
+        assert_eq!((report.inserted, report.updated, report.unchanged), (0, 1, 0));
+        assert_eq!(db.read_all().await.unwrap()[&relabeled.key()].labels(), &relabeled.labels);
+    }
+
+    #[tokio::test]
+    async fn deletions_are_scoped_confirmed_and_restorable() {
+        let db = migrated_db().await;
+        let mut labeled = chat_query("labeled", Utc::now());
+        labeled.labels = Labels::project("cholesterol");
+        let cache = cache_with("openai_rs_db_delete.json", vec![
+            Query::ChatQuery(labeled),
+            Query::ChatQuery(chat_query("unlabeled", Utc::now())),
+            Query::TextQuery(text_query("summary", "Paper", Utc::now())),
+        ]);
+        db.insert_cache(&cache).await.unwrap();
+
+        // Dry run
+        let by_project = DbSearch::new().project("cholesterol");
+        let preview = db.preview_deletion(&by_project, DeleteMode::Trash).await.unwrap();
+        assert_eq!(preview.count, 1);
+        assert_eq!(db.count(&DbSearch::new()).await.unwrap(), 3);
+
+        // Tokens are tied to the mode and to the rows selected
+        assert!(matches!(db.delete(&by_project, DeleteMode::Purge, &preview.token, &cache.graveyard).await, Err(Status::ConfirmationMismatch)));
+        let trashed = db.delete(&by_project, DeleteMode::Trash, &preview.token, &cache.graveyard).await.unwrap();
+        assert_eq!(trashed.deleted, vec!["Chat: labeled".to_string()]);
+        assert!(matches!(db.delete(&by_project, DeleteMode::Trash, &preview.token, &cache.graveyard).await, Err(Status::ConfirmationMismatch)));
+
+        let trash = db.trash().await.unwrap();
+        assert_eq!(trash.len(), 1);
+        assert_eq!(trash[0].query.labels().project.as_deref(), Some("cholesterol"));
+        db.restore_from_trash(&[trash[0].id], &cache.graveyard).await.unwrap();
+        assert!(db.trash().await.unwrap().is_empty());
+        assert_eq!(db.count(&DbSearch::new()).await.unwrap(), 3);
+
+        // Purged rows go to the graveyard
+        let by_prefix = DbSearch::new().key_prefix("Paper: ");
+        assert_eq!(db.preview_deletion(&DbSearch::new().key_prefix("paper: "), DeleteMode::Purge).await.unwrap().count, 0);
+        let preview = db.preview_deletion(&by_prefix, DeleteMode::Purge).await.unwrap();
+        db.delete(&by_prefix, DeleteMode::Purge, &preview.token, &cache.graveyard).await.unwrap();
+        assert_eq!(db.count(&DbSearch::new()).await.unwrap(), 2);
+        assert_eq!(cache.graveyard.versions("Paper: summary").len(), 1);
+
+        let preview = db.preview_deletion(&DbSearch::new(), DeleteMode::Trash).await.unwrap();
+        db.delete_all(DeleteMode::Trash, &preview.token, &cache.graveyard).await.unwrap();
+        assert!(db.read_all().await.unwrap().is_empty());
+        assert_eq!(db.empty_trash(None, &cache.graveyard).await.unwrap(), 2);
+        assert!(db.trash().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn unreadable_rows_fail_a_purge_before_anything_is_deleted() {
+        let db = migrated_db().await;
+        let conn = db.conn().unwrap();
+        let cache = cache_with("openai_rs_db_delete_unreadable.json", vec![
+            Query::ChatQuery(chat_query("readable", Utc::now())),
+            Query::ChatQuery(chat_query("unreadable", Utc::now())),
+        ]);
+        db.insert_cache(&cache).await.unwrap();
+        conn.execute_unprepared("UPDATE completions SET response = '{}' WHERE prompt = 'unreadable'").await.unwrap();
+
+        let preview = db.preview_deletion(&DbSearch::new(), DeleteMode::Purge).await.unwrap();
+        assert!(db.delete_all(DeleteMode::Purge, &preview.token, &cache.graveyard).await.is_err());
+        assert_eq!(db.count(&DbSearch::new()).await.unwrap(), 2);
+        assert!(cache.graveyard.versions(&ChatQuery::key("readable")).is_empty());
+
+        let preview = db.preview_deletion(&DbSearch::new(), DeleteMode::Trash).await.unwrap();
+        db.delete_all(DeleteMode::Trash, &preview.token, &cache.graveyard).await.unwrap();
+        assert!(db.empty_trash(None, &cache.graveyard).await.is_err());
+        let count = conn.query_one(sea_orm::Statement::from_string(conn.get_database_backend(), "SELECT COUNT(*) AS n FROM completions_trash")).await.unwrap().unwrap();
+        assert_eq!(count.try_get::<i64>("", "n").unwrap(), 2);
+    }
+}
+
+#[tokio::test]
+async fn db_methods_without_a_database_are_typed_errors() {
+    let db = DbMethods { conn: None };
+    assert!(matches!(db.read_all().await, Err(Status::DatabaseNotConfigured)));
+    assert!(matches!(db.get_query("Chat: absent").await, Err(Status::DatabaseNotConfigured)));
+    assert!(matches!(db.migration_status().await, Err(Status::DatabaseNotConfigured)));
+}
@@ -0,0 +1,77 @@
+use std::{fs, path::PathBuf};
+
+use crate::models::client::directory::{find_documents, title_clashes, DirFileReport, DirOutcome, DirReport};
+
+fn documents_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(name);
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("nested")).unwrap();
+    for file in ["b.pdf", "a.pdf", "notes.txt", "nested/c.pdf"] {
+        fs::write(dir.join(file), "").unwrap();
+    }
+    dir
+}
+
+#[test]
+fn globs_find_documents() {
+    let dir = documents_dir("openai_rs_dir_find");
+
+    assert_eq!(find_documents(&dir, "*.pdf").unwrap(), vec![dir.join("a.pdf"), dir.join("b.pdf")]);
+    assert_eq!(find_documents(&dir, "**/*.pdf").unwrap(), vec![dir.join("a.pdf"), dir.join("b.pdf"), dir.join("nested/c.pdf")]);
+    assert_eq!(find_documents(&dir, "?.pdf").unwrap().len(), 2);
+    assert_eq!(find_documents(&dir, "nested/*").unwrap(), vec![dir.join("nested/c.pdf")]);
+    assert_eq!(find_documents(&dir, "*.txt").unwrap(), vec![dir.join("notes.txt")]);
+    assert!(find_documents(&dir.join("absent"), "*.pdf").is_err());
+}
+
+#[cfg(unix)]
+#[test]
+fn symlinked_directories_are_not_followed() {
+    let dir = documents_dir("openai_rs_dir_symlinks");
+    std::os::unix::fs::symlink(&dir, dir.join("nested/loop")).unwrap();
+    std::os::unix::fs::symlink(dir.join("a.pdf"), dir.join("linked.pdf")).unwrap();
+
+    assert_eq!(find_documents(&dir, "**/*.pdf").unwrap(), vec![dir.join("a.pdf"), dir.join("b.pdf"), dir.join("linked.pdf"), dir.join("nested/c.pdf")]);
+}
+
+#[test]
+fn checkpoints_resume_the_same_run_and_retry_failures() {
+    let dir = documents_dir("openai_rs_dir_resume");
+    let checkpoint = std::env::temp_dir().join("openai_rs_dir_checkpoint.json");
+    let _ = fs::remove_file(&checkpoint);
+
+    let mut report = DirReport::resume(&checkpoint, &dir, "Summarize", "*.pdf").unwrap();
+    assert!(report.files.is_empty());
+    report.files.push(DirFileReport { path: dir.join("a.pdf"), key: "a: Summarize".to_string(), outcome: DirOutcome::Succeeded, cost: 1.5, process_time: 10 });
+    report.files.push(DirFileReport { path: dir.join("b.pdf"), key: "b: Summarize".to_string(), outcome: DirOutcome::Failed("NotFoundError".to_string()), cost: 0.0, process_time: 0 });
+    report.save(&checkpoint).unwrap();
+
+    let resumed = DirReport::resume(&checkpoint, &dir, "Summarize", "*.pdf").unwrap();
+    assert_eq!(resumed.started_at, report.started_at);
+    assert_eq!(resumed.resumed, 1);
+    assert!(resumed.is_done(&dir.join("a.pdf")));
+    assert!(!resumed.is_done(&dir.join("b.pdf")));
+    assert_eq!(resumed.cost(), 1.5);
+
+    let other = DirReport::resume(&checkpoint, &dir, "Critique", "*.pdf").unwrap();
+    assert!(other.files.is_empty());
+
+    fs::write(&checkpoint, "{").unwrap();
+    assert!(DirReport::resume(&checkpoint, &dir, "Summarize", "*.pdf").is_err());
+    let _ = fs::remove_file(&checkpoint);
+}
+
+#[test]
+fn documents_sharing_a_title_in_different_directories_clash() {
+    let dir = documents_dir("openai_rs_dir_clash");
+    for nested in ["a", "b"] {
+        fs::create_dir_all(dir.join(nested)).unwrap();
+        fs::write(dir.join(nested).join("report.pdf"), "").unwrap();
+    }
+
+    let documents = find_documents(&dir, "**/*.pdf").unwrap();
+    let clashes = title_clashes(&documents);
+    assert_eq!(clashes.len(), 1);
+    assert_eq!(clashes["report"], vec![dir.join("a/report.pdf"), dir.join("b/report.pdf")]);
+    assert!(title_clashes(&find_documents(&dir, "a/*.pdf").unwrap()).is_empty());
+}
@@ -0,0 +1,349 @@
+use std::{io::{Cursor, Write}, path::Path};
+use zip::{write::FileOptions, CompressionMethod, ZipWriter};
+
+use crate::models::{
+    ChatCompletionMessage,
+    MessageRole,
+    client::document_records::{analyze, DocumentRecords},
+    documents::{*, bibliography::{find_doi, parse_references}, citations::{page_marked_text, parse_citations}, pdf::{parse_page_ranges, PdfLoader, PdfPage}, store::PageOffset},
+};
+
+fn zip(entries: &[(&str, &str)]) -> Vec<u8> {
+    let mut writer = ZipWriter::new(Cursor::new(vec![]));
+    for (name, content) in entries {
+        writer.start_file(*name, FileOptions::default().compression_method(CompressionMethod::Stored)).unwrap();
+        writer.write_all(content.as_bytes()).unwrap();
+    }
+    writer.finish().unwrap().into_inner()
+}
+
+fn docx() -> Vec<u8> {
+    zip(&[
+        ("word/document.xml", r#"<w:document><w:body><w:p><w:r><w:t>LDL &amp; mortality</w:t></w:r></w:p><w:p w:rsidR="00A1"/><w:p><w:r><w:t xml:space="preserve">Older </w:t></w:r><w:r><w:t>adults</w:t></w:r></w:p></w:body></w:document>"#),
+        ("docProps/core.xml", r#"<cp:coreProperties><dc:title>Cholesterol Paradox</dc:title><dc:creator>Ravnskov</dc:creator><cp:keywords>LDL; mortality</cp:keywords><dcterms:created xsi:type="dcterms:W3CDTF">2016-06-12T00:00:00Z</dcterms:created></cp:coreProperties>"#),
+        ("docProps/app.xml", r#"<Properties><Pages>12</Pages></Properties>"#),
+    ])
+}
+
+fn epub() -> Vec<u8> {
+    zip(&[
+        ("mimetype", "application/epub+zip"),
+        ("META-INF/container.xml", r#"<container><rootfiles><rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/></rootfiles></container>"#),
+        ("OEBPS/content.opf", r#"<package><metadata><dc:title>Is Higher Better</dc:title><dc:creator>Ravnskov</dc:creator></metadata>
+            <manifest><item href="text/two.xhtml" id="two"/><item id="one" href="text/one.xhtml"/></manifest>
+            <spine><itemref idref="one"/><itemref idref="two"/></spine></package>"#),
+        ("OEBPS/text/one.xhtml", "<html><head><title>One</title></head><body><h1>Chapter one</h1><p>First.</p></body></html>"),
+        ("OEBPS/text/two.xhtml", "<html><body><p>Second.</p></body></html>"),
+    ])
+}
+
+#[test]
+fn formats_are_picked_by_extension_or_sniffed() {
+    assert_eq!(DocumentFormat::detect(Path::new("paper.PDF"), b""), Some(DocumentFormat::Pdf));
+    assert_eq!(DocumentFormat::detect(Path::new("notes.md"), b"# Notes"), Some(DocumentFormat::Markdown));
+    assert_eq!(DocumentFormat::detect(Path::new("paper"), b"%PDF-1.4"), Some(DocumentFormat::Pdf));
+    assert_eq!(DocumentFormat::detect(Path::new("page"), b"  <!DOCTYPE html><html></html>"), Some(DocumentFormat::Html));
+    assert_eq!(DocumentFormat::detect(Path::new("notes"), b"Plain notes"), Some(DocumentFormat::Text));
+    assert_eq!(DocumentFormat::detect(Path::new("report"), &docx()), Some(DocumentFormat::Docx));
+    assert_eq!(DocumentFormat::detect(Path::new("book"), &epub()), Some(DocumentFormat::Epub));
+    assert_eq!(DocumentFormat::detect(Path::new("image"), &[0xFF, 0xD8, 0xFF, 0xE0]), None);
+}
+
+#[test]
+fn text_formats_load_normalized_with_metadata() {
+    let text = loader_for(DocumentFormat::Text).load(b"\xEF\xBB\xBFLine one  \r\n\r\n\r\n\r\nLine two\r\n").unwrap();
+    assert_eq!(text.text, "Line one\n\nLine two");
+
+    let markdown = loader_for(DocumentFormat::Markdown).load(b"---\ntitle: \"Statins\"\nauthor: Diamond\n---\n\n# Heading\n\nBody").unwrap();
+    assert_eq!(markdown.text, "# Heading\n\nBody");
+    assert_eq!(markdown.metadata.title.as_deref(), Some("Statins"));
+    assert_eq!(markdown.metadata.author.as_deref(), Some("Diamond"));
+    let markdown = loader_for(DocumentFormat::Markdown).load(b"Intro\n# Heading\nBody").unwrap();
+    assert_eq!(markdown.metadata.title.as_deref(), Some("Heading"));
+
+    let html = br#"<html><head><title>LDL &amp; Age</title><meta name="author" content="Ravnskov"><style>p { color: red }</style></head>
+        <body><script>track()</script><h1>Results</h1><p>Higher   LDL,<br>longer&nbsp;life &#8212; &#x2713;</p></body></html>"#;
+    let html = loader_for(DocumentFormat::Html).load(html).unwrap();
+    assert_eq!(html.text, "Results\n\nHigher LDL,\nlonger life — ✓");
+    assert_eq!(html.metadata.title.as_deref(), Some("LDL & Age"));
+    assert_eq!(html.metadata.author.as_deref(), Some("Ravnskov"));
+}
+
+#[test]
+fn archive_formats_load_in_reading_order() {
+    let docx = loader_for(DocumentFormat::Docx).load(&docx()).unwrap();
+    // The empty paragraph between the two, whatever its attributes, is a blank line
+    assert_eq!(docx.text, "LDL & mortality\n\nOlder adults");
+    assert_eq!(docx.metadata, DocumentMetadata {
+        format: DocumentFormat::Docx,
+        title: Some("Cholesterol Paradox".to_string()),
+        author: Some("Ravnskov".to_string()),
+        page_count: Some(12),
+        subject: None,
+        keywords: vec!["LDL".to_string(), "mortality".to_string()],
+        year: Some(2016),
+    });
+
+    let epub = loader_for(DocumentFormat::Epub).load(&epub()).unwrap();
+    assert_eq!(epub.text, "Chapter one\n\nFirst.\n\nSecond.");
+    assert_eq!(epub.metadata.title.as_deref(), Some("Is Higher Better"));
+    assert_eq!(epub.metadata.author.as_deref(), Some("Ravnskov"));
+}
+
+#[test]
+fn pdfs_load_as_before() {
+    let path = Path::new("./pdfs/Cholesterol Paradox.pdf");
+    let pdf = lopdf::Document::load(path).unwrap();
+    let mut text = String::new();
+    for page in 1..=pdf.get_pages().len() {
+        text.push_str(&pdf.extract_text(&[page as u32]).unwrap());
+    }
+
+    let document = load_document(path, &PdfOpts::default()).unwrap();
+    assert_eq!(document.text, text);
+    assert_eq!(document.metadata.format, DocumentFormat::Pdf);
+    assert_eq!(document.metadata.page_count, Some(pdf.get_pages().len()));
+}
+
+#[test]
+fn page_ranges_parse() {
+    assert_eq!(parse_page_ranges("1-3, 7,10-").unwrap(), vec![1..=3, 7..=7, 10..=u32::MAX]);
+    assert!(parse_page_ranges("0-2").is_err());
+    assert!(parse_page_ranges("1-x").is_err());
+}
+
+#[test]
+fn cleanup_drops_headers_footers_hyphens_and_references() {
+    let bodies = [
+        "Serum choles-\nterol was measured   in older adults.",
+        "Low LDL was linked to higher mortality.",
+        "Statins were not associated with benefit.",
+        "As Jean-\nPaul noted, results vary.\n\nReferences\n1. Ravnskov U. BMJ Open. 2016.",
+    ];
+    let mut pages: Vec<PdfPage> = bodies.iter().enumerate().map(|(i, body)| PdfPage {
+        number: i as u32 + 1,
+        text: format!("Journal of Lipids, Vol. {}\n{body}\n\n\n{}\n", 40 + i, i + 1),
+    }).collect();
+
+    PdfCleanup::all().apply(&mut pages);
+    let text: Vec<&str> = pages.iter().map(|page| page.text.as_str()).collect();
+    assert_eq!(text, vec![
+        "Serum cholesterol was measured in older adults.\n",
+        "Low LDL was linked to higher mortality.\n",
+        "Statins were not associated with benefit.\n",
+        "As Jean-\nPaul noted, results vary.\n",
+    ]);
+
+    // Nothing is touched with the passes off
+    let mut pages = vec![PdfPage { number: 1, text: "choles-\nterol  \n".to_string() }];
+    PdfCleanup::default().apply(&mut pages);
+    assert_eq!(pages[0].text, "choles-\nterol  \n");
+}
+
+#[test]
+fn pdf_pages_can_be_selected_and_cleaned() {
+    let pdf = lopdf::Document::load("./pdfs/Cholesterol Paradox.pdf").unwrap();
+    let page_count = pdf.get_pages().len();
+
+    let loader = PdfLoader { opts: PdfOpts { pages: Some(vec![2..=3]), ..Default::default() } };
+    let extraction = loader.extract(&pdf).unwrap();
+    assert_eq!(extraction.pages.iter().map(|page| page.number).collect::<Vec<_>>(), vec![2, 3]);
+    assert_eq!(extraction.page_count, page_count);
+    assert_eq!(extraction.tokens_saved(), 0);
+
+    let loader = PdfLoader { opts: PdfOpts { cleanup: PdfCleanup::all(), ..Default::default() } };
+    let extraction = loader.extract(&pdf).unwrap();
+    assert!(extraction.failed_pages.is_empty());
+    assert!(extraction.tokens < extraction.raw_tokens, "{} of {} tokens", extraction.tokens, extraction.raw_tokens);
+    assert_eq!(estimate_tokens(&extraction.text()), extraction.tokens);
+
+    let loader = PdfLoader { opts: PdfOpts { pages: Some(vec![page_count as u32 + 1..=u32::MAX]), ..Default::default() } };
+    assert!(loader.extract(&pdf).is_err());
+}
+
+#[test]
+fn citations_are_checked_against_their_pages() {
+    let pages = vec![
+        PdfPage { number: 1, text: "Serum cholesterol was measured\nin 19 cohorts of older adults.\n".to_string() },
+        PdfPage { number: 2, text: "Low LDL-C was associated with higher all-cause mor-\ntality in most cohorts.\n".to_string() },
+    ];
+    assert_eq!(page_marked_text(&pages), "[Page 1]\nSerum cholesterol was measured\nin 19 cohorts of older adults.\n[Page 2]\nLow LDL-C was associated with higher all-cause mor-\ntality in most cohorts.\n");
+
+    let answer = concat!(
+        "Cholesterol was measured in 19 cohorts [p. 1: \"measured in 19 cohorts\"]. ",
+        "Low LDL went with higher mortality [Page 2: “LDL-C was associated with higher all-cause mortality”], ",
+        "in most of them [p. 2: \"Low LDL-C ... in most cohorts\"]. ",
+        "Statins helped [p. 2: \"statins reduced mortality\"], as did diet [p. 7: \"older adults\"].",
+    );
+    let citations = parse_citations(answer, &pages);
+    assert_eq!(citations.iter().map(|citation| (citation.page, citation.verified)).collect::<Vec<_>>(), vec![
+        (1, true),
+        (2, true),
+        (2, true),
+        (2, false),
+        (7, false),
+    ]);
+    assert_eq!(citations[3].quote, "statins reduced mortality");
+}
+
+#[test]
+fn references_parse_in_common_styles() {
+    let mdpi = "Results are discussed below. References 1. Krause, M.R.; Regen, S.L. The structural role of cholesterol in cell membranes. Acc. Chem. Res. 2014 , 47 , 3512-3521. [ CrossRef ] 2. Hu, J.; Zhang, Z.; Shen, W.J.; Azhar, S. Cellular cholesterol delivery. Nutr. Metab. 2010 , 7 , 47. https://doi.org/10.1186/1743-7075-7-47";
+    let references = parse_references(mdpi);
+    assert_eq!(references.len(), 2);
+    assert_eq!(references[0].number, Some(1));
+    assert_eq!(references[0].authors, vec!["Krause, M.R", "Regen, S.L"]);
+    assert_eq!(references[0].year, Some(2014));
+    assert_eq!(references[0].title.as_deref(), Some("The structural role of cholesterol in cell membranes"));
+    assert_eq!(references[1].authors.len(), 4);
+    assert_eq!(references[1].doi.as_deref(), Some("10.1186/1743-7075-7-47"));
+
+    let vancouver = "Body text.\nReferences\n[1] Ravnskov U, Diamond DM. Lack of an association of LDL-C with mortality. BMJ Open. 2016;6:e010401.\n[2] Schatz IJ, Masaki K. Cholesterol and all-cause mortality. Lancet. 2001;358:351-5.\n";
+    let references = parse_references(vancouver);
+    assert_eq!(references.len(), 2);
+    assert_eq!(references[0].authors, vec!["Ravnskov U", "Diamond DM"]);
+    assert_eq!(references[0].title.as_deref(), Some("Lack of an association of LDL-C with mortality"));
+    assert_eq!(references[0].journal.as_deref(), Some("BMJ Open"));
+    assert_eq!(references[1].year, Some(2001));
+
+    let apa = "Body text.\n\nBibliography\n\nRavnskov, U., & Diamond, D. M. (2016). Lack of an association. BMJ Open, 6(6).\n\nSchatz, I. J. (2001). Cholesterol and mortality. The Lancet, 358.\n";
+    let references = parse_references(apa);
+    assert_eq!(references.len(), 2);
+    assert_eq!(references[0].number, None);
+    assert_eq!(references[0].authors, vec!["Ravnskov, U", "Diamond, D. M"]);
+    assert_eq!(references[0].year, Some(2016));
+    assert_eq!(references[1].journal.as_deref(), Some("The Lancet"));
+
+    assert!(parse_references("No reference list here.").is_empty());
+    assert_eq!(find_doi("see https://doi.org/10.3390/nu15143270)."), Some("10.3390/nu15143270".to_string()));
+}
+
+#[test]
+fn pdfs_are_analyzed_for_metadata_doi_and_references() {
+    let path = Path::new("./pdfs/Cholesterol Paradox.pdf");
+    let store = DocumentStore::new(&std::env::temp_dir().join(format!("openai_rs_analysis_store_{}", std::process::id())));
+    let doc = store.read(path, &PdfOpts::default()).unwrap();
+    let record = analyze(&doc);
+    assert_eq!(record.sha256, doc.sha256);
+    assert_eq!(record.doi.as_deref(), Some("10.3390/nu15143270"));
+    assert_eq!(record.metadata.year, Some(2023));
+    assert!(!record.metadata.keywords.is_empty());
+    assert!(record.references.len() > 10, "{} references", record.references.len());
+    assert_eq!(record.references[0].number, Some(1));
+    assert_eq!(record.document_title(), "Cholesterol Paradox");
+    assert!(record.describe().contains("DOI: 10.3390/nu15143270"));
+
+    let mut records = DocumentRecords::default();
+    records.insert(record.clone());
+    let changed = crate::DocumentRecord { sha256: "changed".to_string(), ..record.clone() };
+    records.insert(changed);
+    assert_eq!(records.records.len(), 1);
+    assert_eq!(records.by_title("Cholesterol Paradox").map(|record| record.sha256.as_str()), Some("changed"));
+    assert!(records.by_title("Is Higher Better").is_none());
+
+    // Saved and read back, but a malformed file is an error rather than no records
+    let filepath = std::env::temp_dir().join(format!("openai_rs_document_records_{}.json", std::process::id()));
+    records.save(&filepath).unwrap();
+    assert_eq!(DocumentRecords::load(&filepath).unwrap(), records);
+    std::fs::write(&filepath, "{\"records\": {").unwrap();
+    assert!(DocumentRecords::load(&filepath).is_err());
+    let _ = std::fs::remove_file(&filepath);
+    let _ = std::fs::remove_dir_all(&store.dir);
+}
+
+#[test]
+fn document_store_extracts_each_version_once() {
+    let dir = std::env::temp_dir().join(format!("openai_rs_document_store_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let store = DocumentStore::new(&dir.join("store"));
+
+    let pdf_path = dir.join("Cholesterol Paradox.pdf");
+    std::fs::copy("./pdfs/Cholesterol Paradox.pdf", &pdf_path).unwrap();
+    let opts = PdfOpts { cleanup: PdfCleanup::all(), ..Default::default() };
+    let doc = store.read(&pdf_path, &opts).unwrap();
+    let loaded = load_document(&pdf_path, &opts).unwrap();
+    assert_eq!(doc.text, loaded.text);
+    assert_eq!(doc.pages().unwrap(), loaded.pages);
+    assert_eq!(doc.tokens, estimate_tokens(&loaded.text));
+    assert_eq!(doc.tokens_saved, loaded.tokens_saved);
+    assert!(doc.tokens_saved > 0);
+    assert_eq!(load_document(&pdf_path, &PdfOpts::default()).unwrap().tokens_saved, 0);
+    assert_eq!(doc.page_offsets.iter().map(|offset| offset.tokens).collect::<Vec<_>>(), loaded.pages.iter().map(|page| estimate_tokens(&page.text)).collect::<Vec<_>>());
+
+    // Citing pages doesn't change the text, so the entry is served as stored
+    assert_eq!(store.read(&pdf_path, &PdfOpts { cite_pages: true, ..opts.clone() }).unwrap(), doc);
+    // Other pages do, so it is extracted anew, and kept alongside, so that reading either way again is served as stored
+    let first_pages = store.read(&pdf_path, &PdfOpts { pages: Some(vec![1..=2]), ..opts.clone() }).unwrap();
+    assert_eq!(first_pages.page_offsets.len(), 2);
+    assert_eq!(store.get(&doc.sha256, &first_pages.pdf), Some(first_pages.clone()));
+    assert_eq!(store.read(&pdf_path, &opts).unwrap(), doc);
+    let whole = store.read(&pdf_path, &PdfOpts::default()).unwrap();
+    assert_eq!(store.read(&pdf_path, &PdfOpts::default()).unwrap(), whole);
+    assert_eq!(store.get(&doc.sha256, &first_pages.pdf), Some(first_pages.clone()));
+
+    // Offsets that don't fall within the text are an error, not a panic
+    let corrupt = StoredDocument { page_offsets: vec![PageOffset { number: 1, start: doc.text.len() + 1, tokens: 0 }], ..doc.clone() };
+    assert!(corrupt.pages().is_err());
+    let corrupt = StoredDocument { text: "é".to_string(), page_offsets: vec![PageOffset { number: 1, start: 1, tokens: 0 }], ..doc.clone() };
+    assert!(corrupt.pages().is_err());
+
+    let text_path = dir.join("notes.txt");
+    std::fs::write(&text_path, "First version").unwrap();
+    let first = store.read(&text_path, &opts).unwrap();
+    assert!(first.page_offsets.is_empty());
+    assert_eq!(first.pdf, PdfOpts::default());
+    std::fs::write(&text_path, "Second version").unwrap();
+    let second = store.read(&text_path, &opts).unwrap();
+    assert_eq!(second.text, "Second version");
+    assert!(store.get(&first.sha256, &PdfOpts::default()).is_none());
+    assert!(store.get(&doc.sha256, &opts).is_some());
+
+    // A new version of the PDF drops its entries however they were read
+    std::fs::copy("./pdfs/Is Higher Better.pdf", &pdf_path).unwrap();
+    store.read(&pdf_path, &opts).unwrap();
+    assert!(store.get(&doc.sha256, &opts).is_none());
+    assert!(store.get(&doc.sha256, &first_pages.pdf).is_none());
+    assert!(store.get(&doc.sha256, &PdfOpts::default()).is_none());
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn documents_are_kept_on_queries_by_reference() {
+    let doc = StoredDocument::extract(Path::new("notes.txt"), b"The whole text of the document", &PdfOpts::default()).unwrap();
+    let message = |role, content: &str| ChatCompletionMessage { role, content: Some(content.to_string()), ..Default::default() };
+    let sent = vec![
+        message(MessageRole::system, "You will receive a document, and a prompt regarding the document."),
+        message(MessageRole::system, &doc.text),
+        message(MessageRole::user, "What is it about?"),
+    ];
+    let kept = doc.referenced_in(&sent);
+    assert_eq!(kept.len(), 3);
+    assert_eq!(kept[0].content, sent[0].content);
+    assert_eq!(kept[1].content, Some(format!("[document sha256:{}]", doc.sha256)));
+    assert_eq!(kept[2].content, sent[2].content);
+    assert!(!serde_json::to_string(&kept).unwrap().contains(&doc.text));
+}
+
+#[test]
+fn queries_keep_the_pages_and_cleanup_their_document_was_read_with() {
+    let pdf = Path::new("./pdfs/Cholesterol Paradox.pdf");
+    let first_pages = PdfOpts { pages: Some(vec![1..=3]), cleanup: PdfCleanup::all(), cite_pages: true };
+    let read_as = StoredDocument::read_opts(pdf, &first_pages);
+    assert_eq!(read_as, first_pages);
+    // The stored text is the same whether or not its pages are cited
+    assert_eq!(PdfOpts { cite_pages: false, ..read_as.clone() }, StoredDocument::extract(pdf, &std::fs::read(pdf).unwrap(), &first_pages).unwrap().pdf);
+    // So a whole-document call doesn't match an answer to the first pages, nor an uncited call a cited answer
+    assert_ne!(StoredDocument::read_opts(pdf, &PdfOpts::default()), read_as);
+    assert_ne!(StoredDocument::read_opts(pdf, &PdfOpts { cite_pages: false, ..first_pages.clone() }), read_as);
+    assert_eq!(StoredDocument::read_opts(Path::new("notes.txt"), &first_pages), PdfOpts::default());
+
+    let mut query = crate::tests::text_query("Summarize", "Cholesterol Paradox", chrono::Utc::now());
+    assert!(!serde_json::to_string(&query).unwrap().contains("\"pdf\""));
+    query.pdf = read_as;
+    query.tokens_saved = 120;
+    let json = serde_json::to_string(&query).unwrap();
+    let read = serde_json::from_str::<crate::TextQuery>(&json).unwrap();
+    assert_eq!((read.pdf, read.tokens_saved), (query.pdf, query.tokens_saved));
+}
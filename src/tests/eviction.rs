@@ -0,0 +1,131 @@
+use std::{collections::HashMap, time::Duration};
+use chrono::Utc;
+
+use crate::*;
+use super::{cache_with, chat_query};
+
+#[test]
+fn expired_entries_are_not_served() {
+    let mut cache = cache_with("openai_rs_eviction_ttl.json", vec![]);
+    cache.policy = EvictionPolicy {
+        chat_ttl: Some(Duration::from_secs(60 * 60)),
+        ..Default::default()
+    };
+
+    let stale = Query::ChatQuery(chat_query("stale", Utc::now() - chrono::Duration::hours(2)));
+    let fresh = Query::ChatQuery(chat_query("fresh", Utc::now()));
+    cache.entries.insert(stale.key(), stale.clone());
+    cache.entries.insert(fresh.key(), fresh.clone());
+
+    assert!(cache.get(&stale.key()).is_none());
+    assert!(!cache.entries.contains_key(&stale.key()));
+    assert!(cache.get(&fresh.key()).is_some());
+}
+
+#[test]
+fn max_entries_evicts_least_recently_accessed() {
+    let mut cache = cache_with("openai_rs_eviction_lru.json", vec![]);
+    cache.policy = EvictionPolicy {
+        max_entries: Some(2),
+        ..Default::default()
+    };
+
+    let hour_ago = Utc::now() - chrono::Duration::hours(1);
+    let first = Query::ChatQuery(chat_query("first", hour_ago));
+    let second = Query::ChatQuery(chat_query("second", hour_ago));
+    cache.entries.insert(first.key(), first.clone());
+    cache.entries.insert(second.key(), second.clone());
+
+    // Reading "first" makes "second" the least recently accessed
+    cache.get(&first.key()).expect("first is cached");
+    cache.insert(&Query::ChatQuery(chat_query("third", Utc::now())));
+
+    assert_eq!(cache.entries.len(), 2);
+    assert!(cache.entries.contains_key(&first.key()));
+    assert!(!cache.entries.contains_key(&second.key()));
+}
+
+#[test]
+fn max_bytes_bounds_serialized_size() {
+    let one = Query::ChatQuery(chat_query("one", Utc::now()));
+    let size = serde_json::to_vec(&one).unwrap().len();
+
+    let mut cache = cache_with("openai_rs_eviction_bytes.json", vec![]);
+    cache.policy = EvictionPolicy {
+        max_bytes: Some(size * 2 + size / 2),
+        ..Default::default()
+    };
+    for prompt in ["one", "two", "three", "four"] {
+        cache.insert(&Query::ChatQuery(chat_query(prompt, Utc::now())));
+    }
+
+    let total: usize = cache.entries.values().map(|q| serde_json::to_vec(q).unwrap().len()).sum();
+    assert!(total <= size * 2 + size / 2);
+    assert_eq!(cache.entries.len(), 2);
+}
+
+#[test]
+fn reads_are_saved_on_flush_not_on_every_hit() {
+    let mut cache = cache_with("openai_rs_eviction_flush.json", vec![]);
+    let hour_ago = Utc::now() - chrono::Duration::hours(1);
+    let query = Query::ChatQuery(chat_query("flushed", hour_ago));
+    cache.insert(&query);
+    let saved = std::fs::read_to_string(&cache.filepath).unwrap();
+
+    cache.get(&query.key()).expect("query is cached");
+    assert!(cache.dirty);
+    assert_eq!(std::fs::read_to_string(&cache.filepath).unwrap(), saved);
+
+    cache.flush();
+    assert!(!cache.dirty);
+    let entries: HashMap<String, Query> = serde_json::from_str(&std::fs::read_to_string(&cache.filepath).unwrap()).unwrap();
+    assert!(entries[&query.key()].last_accessed() > hour_ago);
+
+    // Dropping a dirty cache saves it as well
+    let filepath = cache.filepath.clone();
+    cache.entries.get_mut(&query.key()).unwrap().touch();
+    let touched = cache.entries[&query.key()].last_accessed();
+    cache.dirty = true;
+    drop(cache);
+    let entries: HashMap<String, Query> = serde_json::from_str(&std::fs::read_to_string(&filepath).unwrap()).unwrap();
+    assert_eq!(entries[&query.key()].last_accessed(), touched);
+}
+
+#[test]
+fn rejected_hits_are_not_counted_as_accesses() {
+    let mut cache = cache_with("openai_rs_eviction_rejected.json", vec![]);
+    let hour_ago = Utc::now() - chrono::Duration::hours(1);
+    let query = Query::ChatQuery(chat_query("rejected", hour_ago));
+    cache.insert(&query);
+
+    assert!(cache.get_if(&query.key(), |_| false).is_none());
+    assert!(!cache.dirty);
+    assert_eq!(cache.entries[&query.key()].last_accessed(), hour_ago);
+
+    assert!(cache.get_if(&query.key(), |_| true).is_some());
+    assert!(cache.dirty);
+}
+
+#[test]
+fn older_entries_are_stamped_once_with_the_load_time() {
+    let query = Query::ChatQuery(chat_query("legacy", Utc::now()));
+    let mut legacy = serde_json::to_value(HashMap::from([(query.key(), &query)])).unwrap();
+    let fields = legacy[&query.key()]["ChatQuery"].as_object_mut().unwrap();
+    for field in ["created_at", "last_accessed", "updated_at"] {
+        fields.remove(field);
+    }
+
+    let before = Utc::now();
+    let (entries, stamped) = crate::models::cache::read_entries(legacy.to_string().as_bytes()).unwrap();
+    assert!(stamped);
+    let loaded = &entries[&query.key()];
+    assert!(loaded.created_at() >= before);
+    assert_eq!(loaded.created_at(), loaded.last_accessed());
+    assert_eq!(loaded.created_at(), loaded.updated_at());
+
+    // Once saved, the stamp is read back as is
+    let saved = serde_json::to_string(&entries).unwrap();
+    let (reloaded, stamped) = crate::models::cache::read_entries(saved.as_bytes()).unwrap();
+    assert!(!stamped);
+    assert_eq!(reloaded[&query.key()].created_at(), loaded.created_at());
+}
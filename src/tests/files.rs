@@ -0,0 +1,68 @@
+use std::path::Path;
+use chrono::{TimeZone, Utc};
+
+use crate::{
+    *,
+    models::{client::files::{files_page_path, FileObject, FileRegistry}, hash::sha256_hex},
+};
+
+fn file(id: &str, filename: &str, purpose: FilePurpose, created_at: i64) -> FileObject {
+    FileObject { id: id.to_string(), bytes: 42, created_at, filename: filename.to_string(), purpose: purpose.as_str().to_string() }
+}
+
+#[test]
+fn file_searches_filter_by_purpose_name_and_date() {
+    let batch_input = file("file-1", "batch_input.jsonl", FilePurpose::Batch, 1_700_000_000);
+    let paper = file("file-2", "cholesterol_paradox.pdf", FilePurpose::Assistants, 1_800_000_000);
+
+    let search = FileSearch::new().purpose(FilePurpose::Assistants);
+    assert!(!search.matches(&batch_input));
+    assert!(search.matches(&paper));
+
+    let search = FileSearch::new().filename_contains("cholesterol");
+    assert!(!search.matches(&batch_input));
+    assert!(search.matches(&paper));
+
+    let search = FileSearch::new().created_after(Utc.timestamp_opt(1_750_000_000, 0).unwrap());
+    assert!(!search.matches(&batch_input));
+    assert!(search.matches(&paper));
+
+    let search = FileSearch::new().created_before(Utc.timestamp_opt(1_800_000_000, 0).unwrap());
+    assert!(search.matches(&batch_input));
+    assert!(!search.matches(&paper));
+}
+
+#[test]
+fn registry_finds_uploads_by_content_and_purpose() {
+    let filepath = std::env::temp_dir().join(format!("openai_rs_files_{}.json", std::process::id()));
+    let source = Path::new("./pdf_docs/Cholesterol Paradox.pdf");
+    let sha256 = sha256_hex(b"%PDF-1.4 cholesterol");
+
+    let mut registry = FileRegistry::default();
+    registry.register(file("file-1", "Cholesterol Paradox.pdf", FilePurpose::Assistants, 1_700_000_000), source, &sha256);
+    registry.save(&filepath).unwrap();
+
+    let registry = FileRegistry::load(&filepath).unwrap();
+    assert_eq!(registry.find(&sha256, FilePurpose::Assistants).map(|registered| registered.file.id.as_str()), Some("file-1"));
+    // The same content is uploaded again for another purpose, and other content is not matched
+    assert!(registry.find(&sha256, FilePurpose::FineTune).is_none());
+    assert!(registry.find(&sha256_hex(b"%PDF-1.4 another"), FilePurpose::Assistants).is_none());
+    assert_eq!(registry.by_source(source).len(), 1);
+
+    let mut registry = registry;
+    assert!(registry.forget("file-1").is_some());
+    assert!(registry.find(&sha256, FilePurpose::Assistants).is_none());
+
+    // Only an absent registry starts empty. A malformed one is an error, not something to save over
+    let _ = std::fs::remove_file(&filepath);
+    assert_eq!(FileRegistry::load(&filepath).unwrap(), FileRegistry::default());
+    std::fs::write(&filepath, "{\"files\": ").unwrap();
+    assert!(FileRegistry::load(&filepath).is_err());
+    let _ = std::fs::remove_file(&filepath);
+}
+
+#[test]
+fn file_listings_page_after_the_last_file() {
+    assert_eq!(files_page_path(None, None), "/files?limit=10000");
+    assert_eq!(files_page_path(Some(FilePurpose::Batch), Some("file-abc")), "/files?limit=10000&purpose=batch&after=file-abc");
+}
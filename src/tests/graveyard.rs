@@ -0,0 +1,64 @@
+use chrono::Utc;
+
+use crate::{*, models::client::graveyard::{Graveyard, DiffLine}};
+use super::{answered, cache_with};
+
+#[test]
+fn overwrites_are_kept_as_versions() {
+    let mut cache = cache_with("openai_rs_graveyard_versions.json", vec![]);
+    cache.insert(&answered("question", "first\nanswer", Utc::now()));
+    cache.insert(&answered("question", "second\nanswer", Utc::now()));
+    cache.insert(&answered("question", "third\nanswer", Utc::now()));
+
+    let key = ChatQuery::key("question");
+    let versions = cache.graveyard.versions(&key);
+    assert_eq!(versions.len(), 2);
+    assert_eq!(versions[0].query.content().unwrap(), "first\nanswer");
+    assert_eq!(versions[1].query.content().unwrap(), "second\nanswer");
+
+    // History is read back from file, so a new handle on the same path sees it too
+    let reopened = Graveyard { filepath: cache.graveyard.filepath.clone() };
+    assert_eq!(reopened.versions(&key), versions);
+
+    assert_eq!(
+        cache.graveyard.diff(&key, 0, 1).unwrap(),
+        vec![
+            DiffLine::Removed("first".to_string()),
+            DiffLine::Added("second".to_string()),
+            DiffLine::Same("answer".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn restore_buries_the_current_entry() {
+    let mut cache = cache_with("openai_rs_graveyard_restore.json", vec![]);
+    cache.insert(&answered("question", "old", Utc::now()));
+    cache.insert(&answered("question", "new", Utc::now()));
+
+    let key = ChatQuery::key("question");
+    let restored = cache.restore(&key, 0).expect("a buried version");
+    assert_eq!(restored.content().unwrap(), "old");
+    assert_eq!(cache.entries[&key].content().unwrap(), "old");
+
+    let versions = cache.graveyard.versions(&key);
+    assert_eq!(versions.len(), 2);
+    assert_eq!(versions[1].query.content().unwrap(), "new");
+
+    assert!(cache.restore(&key, 5).is_none());
+}
+
+#[test]
+fn unreadable_lines_do_not_hide_later_versions() {
+    let graveyard = Graveyard { filepath: std::env::temp_dir().join("openai_rs_graveyard_legacy.json") };
+    graveyard.clear();
+
+    // Older versions of this crate pretty-printed what they buried, over many lines
+    let legacy = answered("question", "legacy answer", Utc::now());
+    std::fs::write(&graveyard.filepath, serde_json::to_string_pretty(&legacy).unwrap() + "\n").unwrap();
+    graveyard.bury(&ChatQuery::key("question"), &answered("question", "kept answer", Utc::now()));
+
+    let versions = graveyard.versions(&ChatQuery::key("question"));
+    assert_eq!(versions.len(), 1);
+    assert_eq!(versions[0].query.content().unwrap(), "kept answer");
+}
@@ -0,0 +1,85 @@
+use chrono::Utc;
+
+use crate::{*, models::cache::Cache};
+use super::{cache_with, chat_query, text_query};
+
+fn labeled_cache(name: &str) -> Cache {
+    let now = Utc::now();
+    let mut first = chat_query("first", now);
+    first.labels = Labels::project("cholesterol").with_tag("review").with_run_id("run-1");
+    let mut second = text_query("second", "Is Higher Better", now);
+    second.cost = 2.0;
+    second.labels = Labels::project("cholesterol").with_tag("review").with_tag("pdf").with_run_id("run-2");
+    let unlabeled = chat_query("unlabeled", now);
+
+    cache_with(name, vec![Query::ChatQuery(first), Query::TextQuery(second), Query::ChatQuery(unlabeled)])
+}
+
+#[test]
+fn per_call_labels_lay_over_ambient_ones() {
+    let ambient = Labels::project("cholesterol").with_tag("review").with_run_id("run-1");
+    let merged = ambient.merged(&Labels::default().with_tag("pdf").with_run_id("run-2"));
+
+    assert_eq!(merged.project.as_deref(), Some("cholesterol"));
+    assert_eq!(merged.tags, vec!["review".to_string(), "pdf".to_string()]);
+    assert_eq!(merged.run_id.as_deref(), Some("run-2"));
+    assert_eq!(ambient.merged(&Labels::default()), ambient);
+}
+
+#[test]
+fn labels_survive_the_cache_file_and_are_omitted_when_empty() {
+    let mut query = chat_query("labeled", Utc::now());
+    query.labels = Labels::project("cholesterol").with_tag("review");
+    let json = serde_json::to_string(&Query::ChatQuery(query.clone())).unwrap();
+    let read: Query = serde_json::from_str(&json).unwrap();
+    assert_eq!(read.labels(), &query.labels);
+
+    let json = serde_json::to_string(&Query::ChatQuery(chat_query("unlabeled", Utc::now()))).unwrap();
+    assert!(!json.contains("labels"));
+}
+
+#[test]
+fn searches_filter_by_label() {
+    let cache = labeled_cache("openai_rs_labels_filter.json");
+
+    assert_eq!(CacheSearch::new().project("cholesterol").run(&cache).len(), 2);
+    assert_eq!(CacheSearch::new().tag("pdf").run(&cache).len(), 1);
+    assert_eq!(CacheSearch::new().run_id("run-1").run(&cache).len(), 1);
+    assert_eq!(CacheSearch::new().project("statins").run(&cache).len(), 0);
+}
+
+#[test]
+fn cost_reports_break_down_by_label() {
+    let cache = labeled_cache("openai_rs_labels_report.json");
+    let report = CacheSearch::new().cost_report(&cache);
+
+    assert_eq!(report.queries, 3);
+    assert_eq!(report.total_tokens, 90);
+    assert!((report.cost - 2.02).abs() < 1e-6);
+    assert_eq!(report.by_project["cholesterol"].queries, 2);
+    assert!((report.by_project["cholesterol"].cost - 2.01).abs() < 1e-6);
+    assert_eq!(report.by_run["run-2"].queries, 1);
+    assert_eq!(report.by_tag["review"].queries, 2);
+    assert_eq!(report.by_tag["pdf"].queries, 1);
+
+    let narrowed = CacheSearch::new().run_id("run-1").cost_report(&cache);
+    assert_eq!(narrowed.queries, 1);
+    assert_eq!(narrowed.by_tag.get("pdf"), None);
+}
+
+#[test]
+fn stamps_are_written_back_to_cached_entries() {
+    let mut cache = labeled_cache("openai_rs_labels_stamped.json");
+    let key = TextQuery::key("second", "Is Higher Better");
+    let before = cache.entries[&key].updated_at();
+    let stamp = Labels::default().with_tag("battery:lipids@2");
+
+    assert!(cache.merge_labels(&key, &stamp));
+    assert_eq!(CacheSearch::new().tag("battery:lipids@2").run(&cache).len(), 1);
+    assert_eq!(cache.entries[&key].labels().project.as_deref(), Some("cholesterol"));
+    assert!(cache.entries[&key].updated_at() > before);
+
+    // Already stamped, or absent, leaves the cache as it is
+    assert!(!cache.merge_labels(&key, &stamp));
+    assert!(!cache.merge_labels("missing", &stamp));
+}
@@ -1,3 +1,93 @@
 pub mod initializations;
 pub mod cache;
-pub mod database;
\ No newline at end of file
+pub mod database;
+pub mod eviction;
+pub mod graveyard;
+pub mod search;
+pub mod archive;
+pub mod semantic;
+pub mod labels;
+pub mod directory;
+pub mod batch;
+pub mod files;
+pub mod documents;
+pub mod battery;
+
+use chrono::{DateTime, Utc};
+use crate::{*, models::{cache::Cache, client::graveyard::Graveyard}};
+
+/// A cached-looking `ChatQuery` built without calling the API
+pub fn chat_query(prompt: &str, created_at: DateTime<Utc>) -> ChatQuery {
+    let response = serde_json::from_value(serde_json::json!({
+        "id": format!("chatcmpl-{prompt}"),
+        "object": "chat.completion",
+        "created": created_at.timestamp(),
+        "model": "gpt-3.5-turbo-0613",
+        "choices": [{
+            "index": 0,
+            "message": { "role": "assistant", "content": format!("An answer to: {prompt}") },
+            "finish_reason": "stop"
+        }],
+        "usage": { "prompt_tokens": 10, "completion_tokens": 20, "total_tokens": 30 }
+    })).expect("valid response fixture");
+
+    ChatQuery {
+        prompt: prompt.to_string(),
+        cost: 0.01,
+        response,
+        process_time: 1,
+        model: GptModel::Gpt35Turbo,
+        temperature: 0.5,
+        from_cache: false,
+        similarity: None,
+        created_at,
+        last_accessed: created_at,
+        updated_at: created_at,
+        messages: vec![],
+        labels: Labels::default(),
+    }
+}
+
+/// A cached-looking `TextQuery` built without calling the API
+pub fn text_query(prompt: &str, document_title: &str, created_at: DateTime<Utc>) -> TextQuery {
+    let chat = chat_query(prompt, created_at);
+    TextQuery {
+        prompt: chat.prompt,
+        cost: chat.cost,
+        response: chat.response,
+        process_time: chat.process_time,
+        model: chat.model,
+        document_title: document_title.to_string(),
+        temperature: chat.temperature,
+        from_cache: chat.from_cache,
+        similarity: None,
+        created_at,
+        last_accessed: created_at,
+        updated_at: created_at,
+        messages: vec![],
+        labels: Labels::default(),
+        citations: vec![],
+        pdf: PdfOpts::default(),
+        tokens_saved: 0,
+    }
+}
+
+/// A `ChatQuery` to `prompt` answered with `content`
+pub fn answered(prompt: &str, content: &str, created_at: DateTime<Utc>) -> Query {
+    let mut query = chat_query(prompt, created_at);
+    query.response.choices[0].message.content = Some(content.to_string());
+    Query::ChatQuery(query)
+}
+
+/// A cache of `queries` kept at `name` in the temp directory, with an emptied graveyard beside it and no eviction
+pub fn cache_with(name: &str, queries: Vec<Query>) -> Cache {
+    let graveyard = Graveyard { filepath: std::env::temp_dir().join(format!("graveyard_{name}")) };
+    graveyard.clear();
+    Cache {
+        entries: queries.into_iter().map(|query| (query.key(), query)).collect(),
+        filepath: std::env::temp_dir().join(name),
+        policy: EvictionPolicy::default(),
+        graveyard,
+        dirty: false,
+    }
+}
@@ -0,0 +1,62 @@
+use chrono::{Duration, Utc};
+
+use crate::{*, models::{cache::Cache, client::core::Status, response::FinishReason}};
+use super::{cache_with, chat_query, text_query};
+
+fn sample_cache(name: &str) -> Cache {
+    let now = Utc::now();
+    let mut cheap = chat_query("cheap question", now - Duration::days(3));
+    cheap.cost = 0.5;
+    let mut pricey = chat_query("pricey question", now - Duration::days(1));
+    pricey.cost = 5.0;
+    pricey.response.choices[0].finish_reason = FinishReason::length;
+    let mut summary = text_query("Summarize", "Is Higher Better", now);
+    summary.cost = 2.0;
+    summary.response.choices[0].message.content = Some("LDL and mortality".to_string());
+
+    cache_with(name, vec![Query::ChatQuery(cheap), Query::ChatQuery(pricey), Query::TextQuery(summary)])
+}
+
+#[test]
+fn filters_combine() {
+    let cache = sample_cache("openai_rs_search_filters.json");
+
+    assert_eq!(CacheSearch::new().run(&cache).len(), 3);
+    assert_eq!(CacheSearch::new().kind(QueryKind::Chat).run(&cache).len(), 2);
+    assert_eq!(CacheSearch::new().document_title("Is Higher Better").run(&cache).len(), 1);
+    assert_eq!(CacheSearch::new().cost_between(Some(1.0), None).run(&cache).len(), 2);
+    assert_eq!(CacheSearch::new().finish_reason(FinishReason::length).run(&cache).len(), 1);
+    assert_eq!(CacheSearch::new().prompt_contains("question").cost_between(None, Some(1.0)).run(&cache).len(), 1);
+    assert_eq!(CacheSearch::new().response_regex(r"(?i)^ldl").unwrap().run(&cache).len(), 1);
+    assert!(matches!(CacheSearch::new().prompt_regex("(unclosed"), Err(Status::Error(_))));
+    assert_eq!(CacheSearch::new().created_between(Some(Utc::now() - Duration::days(2)), None).run(&cache).len(), 2);
+    assert_eq!(CacheSearch::new().model(GptModel::Gpt4).run(&cache).len(), 0);
+}
+
+#[test]
+fn sorts_and_limits() {
+    let cache = sample_cache("openai_rs_search_sort.json");
+
+    let by_cost: Vec<f32> = CacheSearch::new()
+        .sort_by(SortBy::Cost, true)
+        .limit(2)
+        .run(&cache)
+        .iter()
+        .map(|(_, query)| query.cost())
+        .collect();
+    assert_eq!(by_cost, vec![5.0, 2.0]);
+
+    let oldest = CacheSearch::new().sort_by(SortBy::CreatedAt, false).limit(1).run(&cache);
+    assert_eq!(oldest[0].1.prompt(), "cheap question");
+}
+
+#[test]
+fn results_feed_removal() {
+    let mut cache = sample_cache("openai_rs_search_remove.json");
+
+    let keys: Vec<String> = CacheSearch::new().kind(QueryKind::Chat).run(&cache).into_iter().map(|(key, _)| key).collect();
+    let removed = cache.remove_many(&keys);
+
+    assert_eq!(removed.len(), 2);
+    assert_eq!(cache.entries.len(), 1);
+}
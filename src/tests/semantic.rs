@@ -0,0 +1,62 @@
+use chrono::Utc;
+
+use crate::{*, models::semantic::*};
+use super::{cache_with, chat_query, text_query};
+
+fn index_with(embeddings: &[(&str, Vec<f32>)]) -> SemanticIndex {
+    SemanticIndex {
+        opts: SemanticOpts { threshold: 0.9, ..Default::default() },
+        embeddings: embeddings.iter().map(|(key, embedding)| (key.to_string(), embedding.clone())).collect(),
+    }
+}
+
+#[test]
+fn normalization_ignores_case_punctuation_and_spacing() {
+    assert_eq!(normalize_prompt("  What's the deal\nwith   airplane food?! "), "what s the deal with airplane food");
+    assert_eq!(normalize_prompt("Summarize."), normalize_prompt("summarize"));
+}
+
+#[test]
+fn cosine_similarity_of_vectors() {
+    assert!((cosine_similarity(&[1.0, 0.0], &[1.0, 0.0]) - 1.0).abs() < 1e-6);
+    assert!(cosine_similarity(&[1.0, 0.0], &[0.0, 1.0]).abs() < 1e-6);
+    assert_eq!(cosine_similarity(&[1.0], &[1.0, 0.0]), 0.0);
+    assert_eq!(cosine_similarity(&[0.0, 0.0], &[1.0, 0.0]), 0.0);
+}
+
+#[test]
+fn normalized_match_respects_kind_and_document() {
+    let cache = cache_with("openai_rs_semantic.json", vec![
+        Query::ChatQuery(chat_query("What's the deal with airplane food?", Utc::now())),
+        Query::TextQuery(text_query("Summarize this.", "Is Higher Better", Utc::now())),
+    ]);
+    let index = index_with(&[]);
+
+    assert_eq!(
+        index.normalized_match(&cache, "what's the deal with airplane food", None),
+        Some(ChatQuery::key("What's the deal with airplane food?"))
+    );
+    assert_eq!(
+        index.normalized_match(&cache, "summarize this", Some("Is Higher Better")),
+        Some(TextQuery::key("Summarize this.", "Is Higher Better"))
+    );
+    assert_eq!(index.normalized_match(&cache, "summarize this", Some("Cholesterol Paradox")), None);
+    assert_eq!(index.normalized_match(&cache, "summarize this", None), None);
+}
+
+#[test]
+fn nearest_requires_the_threshold() {
+    let close = ChatQuery::key("close");
+    let far = ChatQuery::key("far");
+    let cache = cache_with("openai_rs_semantic.json", vec![
+        Query::ChatQuery(chat_query("close", Utc::now())),
+        Query::ChatQuery(chat_query("far", Utc::now())),
+    ]);
+    let index = index_with(&[(&close, vec![1.0, 0.1]), (&far, vec![0.0, 1.0])]);
+
+    let (key, similarity) = index.nearest(&cache, &[1.0, 0.0], None).expect("a close prompt");
+    assert_eq!(key, close);
+    assert!(similarity > 0.9 && similarity < 1.0);
+
+    assert!(index.nearest(&cache, &[-1.0, 0.0], None).is_none());
+}